@@ -1,3 +1,5 @@
+use rand::random;
+
 use crate::vectorlib::{point3::*, vector3::*, ray::*};
 
 pub struct Camera {
@@ -5,6 +7,9 @@ pub struct Camera {
     horizontal : Vector3f,
     vertical : Vector3f,
     bottom_left : Point3,
+    // Rays are fired at uniformly random times between the shutter opening and closing
+    shutter_open : f32,
+    shutter_close : f32,
 }
 
 impl Camera {
@@ -18,11 +23,45 @@ impl Camera {
             horizontal: (horizontal),
             vertical: (vertical),
             bottom_left: (bottom_left),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
+    // Camera at origin looking along forward, vertical_fov is in radians and the image plane sits one unit away
+    pub fn new_looking(origin : Point3, forward : Vector3f, up : Vector3f, vertical_fov : f32, aspect_ratio : f32) -> Camera{
+        let viewport_height = 2.0 * (vertical_fov / 2.0).tan();
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = -forward.unit_vector();
+        let u = up.cross(&w).unit_vector();
+        let v = w.cross(&u);
+
+        let horizontal = viewport_width * u;
+        let vertical = viewport_height * v;
+        let bottom_left = origin - (horizontal/2.0) - (vertical/2.0) - w;
+
+        return Camera {
+            origin,
+            horizontal,
+            vertical,
+            bottom_left,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    // Scene time runs from 0 to 1, an open shutter of [0,1] blurs over the whole of every object's motion.
+    // Moving objects hold still outside that range
+    pub fn with_shutter(mut self, shutter_open : f32, shutter_close : f32) -> Camera{
+        self.shutter_open = shutter_open;
+        self.shutter_close = shutter_close;
+        return self;
+    }
+
     // u and v should be in range [0,1]
     pub fn get_ray(& self,u : f32, v : f32) -> Ray{
-        return Ray::new(self.origin, self.bottom_left + (u*self.horizontal) + (self.vertical*v) - self.origin);
+        let time = self.shutter_open + random::<f32>() * (self.shutter_close - self.shutter_open);
+        return Ray::new_at_time(self.origin, self.bottom_left + (u*self.horizontal) + (self.vertical*v) - self.origin, time);
     }
 }
\ No newline at end of file
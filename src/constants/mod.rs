@@ -12,4 +12,14 @@ pub const SAMPLES_PER_PIXEL : u16 = 75;
 pub const MAX_BOUNCES : u16 = 64;
 
 pub const QUICK_RENDER : bool= false;
+
+// Rays that escape the scene pick up the white to blue sky, turn off for scenes lit only by emissive materials
+pub const SKY_LIGHT : bool = true;
+
+// Fraction of the scene's [0,1] time range the camera shutter is open for, equal values disable motion blur
+pub const SHUTTER_OPEN : f32 = 0.0;
+pub const SHUTTER_CLOSE : f32 = 1.0;
+
+// glTF (.gltf or .glb) scene to render instead of the built in spheres, uses the first camera in the file if it has one
+pub const SCENE_FILE : Option<&str> = None;
 pub const SEED : u64 = 41253;
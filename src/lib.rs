@@ -0,0 +1,9 @@
+#![allow(clippy::needless_return, clippy::clone_on_copy, clippy::needless_lifetimes, clippy::manual_clamp)]
+
+pub mod camera;
+pub mod constants;
+pub mod utils;
+pub mod vectorlib;
+pub mod material;
+pub mod loaders;
+pub mod texture;
@@ -0,0 +1,580 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::loaders::json::JsonValue;
+use crate::material::{Lambertian, Material, Metal};
+use crate::vectorlib::{matrix4::Matrix4, mesh::TriangleMesh, transform::Transform, vector3::Vector3f};
+
+// Importer for glTF 2.0 scenes, both .gltf (with .bin files or embedded base64 buffers) and binary .glb.
+// Meshes are baked into world space with their node transforms, materials are mapped from
+// metallic-roughness onto Lambertian / Metal. Only local files are read, external URLs are rejected.
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+// Materials at least this metallic become Metal, anything less is treated as a dielectric diffuse surface
+const METALLIC_THRESHOLD: f32 = 0.5;
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+pub struct GltfScene {
+    pub meshes: Vec<TriangleMesh>,
+    // Perspective cameras in the order they are found in the node hierarchy
+    pub cameras: Vec<Camera>,
+}
+
+fn invalid(message: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, format!("[ERR] glTF: {}", message));
+}
+
+// Cameras are built with the aspect ratio of the image being rendered rather than the one stored in the file
+pub fn load_gltf(path: &Path, aspect_ratio: f32) -> Result<GltfScene> {
+    let bytes = fs::read(path)?;
+    let base_directory = path.parent().unwrap_or(Path::new(""));
+
+    let (json_text, binary_chunk) = if bytes.starts_with(GLB_MAGIC) {
+        let (json, binary) = split_glb(&bytes)?;
+        (json.to_string(), binary)
+    } else {
+        let text = String::from_utf8(bytes).map_err(|_| invalid("file is not utf-8 text".to_string()))?;
+        (text, None)
+    };
+
+    let json = JsonValue::parse(&json_text)?;
+    let version = json.get("asset").and_then(|asset| asset.get("version")).and_then(|v| v.as_str()).unwrap_or("");
+    if !version.starts_with('2') {
+        return Err(invalid(format!("unsupported version '{}'", version)));
+    }
+
+    let buffers = load_buffers(&json, base_directory, binary_chunk)?;
+    let document = Document { json, buffers, aspect_ratio };
+    return document.build_scene();
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let raw = bytes.get(offset..offset + 4).ok_or_else(|| invalid("truncated glb".to_string()))?;
+    return Ok(u32::from_le_bytes(raw.try_into().unwrap()));
+}
+
+// Returns the JSON chunk and the optional BIN chunk of a .glb
+fn split_glb(bytes: &[u8]) -> Result<(&str, Option<Vec<u8>>)> {
+    let version = read_u32(bytes, 4)?;
+    if version != 2 {
+        return Err(invalid(format!("unsupported glb version {}", version)));
+    }
+    let length = (read_u32(bytes, 8)? as usize).min(bytes.len());
+
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(bytes, offset)? as usize;
+        let chunk_type = read_u32(bytes, offset + 4)?;
+        let chunk = bytes.get(offset + 8..offset + 8 + chunk_length).ok_or_else(|| invalid("truncated glb chunk".to_string()))?;
+
+        match chunk_type {
+            GLB_CHUNK_JSON => json = Some(std::str::from_utf8(chunk).map_err(|_| invalid("glb json is not utf-8".to_string()))?),
+            GLB_CHUNK_BIN if binary.is_none() => binary = Some(chunk.to_vec()),
+            _ => {}
+        }
+        offset += 8 + chunk_length;
+    }
+
+    let json = json.ok_or_else(|| invalid("glb has no JSON chunk".to_string()))?;
+    return Ok((json, binary));
+}
+
+fn load_buffers(json: &JsonValue, base_directory: &Path, mut binary_chunk: Option<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+    let mut buffers = Vec::new();
+    let declared = json.get("buffers").and_then(|b| b.as_array()).map(|b| b.as_slice()).unwrap_or(&[]);
+
+    for (index, buffer) in declared.iter().enumerate() {
+        let data = match buffer.get("uri").and_then(|uri| uri.as_str()) {
+            Some(uri) if uri.starts_with("data:") => {
+                let (_, encoded) = uri.split_once(";base64,").ok_or_else(|| invalid("only base64 data uris are supported".to_string()))?;
+                decode_base64(encoded)?
+            }
+            Some(uri) if uri.contains("://") => {
+                return Err(invalid(format!("buffer {} points at '{}', only local files can be read", index, uri)));
+            }
+            Some(uri) => fs::read(base_directory.join(decode_uri(uri)))?,
+            // A buffer without a uri is the BIN chunk of a glb
+            None => binary_chunk.take().ok_or_else(|| invalid(format!("buffer {} has no uri and there is no glb BIN chunk", index)))?,
+        };
+
+        let byte_length = buffer.get("byteLength").and_then(|l| l.as_usize()).unwrap_or(data.len());
+        if data.len() < byte_length {
+            return Err(invalid(format!("buffer {} is {} bytes, expected {}", index, data.len(), byte_length)));
+        }
+        buffers.push(data);
+    }
+    return Ok(buffers);
+}
+
+// Relative uris may percent-encode characters such as spaces
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                decoded.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    return String::from_utf8_lossy(&decoded).into_owned();
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    return match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    };
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ if byte.is_ascii_whitespace() => continue,
+            _ => return Err(invalid("invalid base64 data".to_string())),
+        };
+        accumulator = (accumulator << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((accumulator >> bits) as u8);
+        }
+    }
+    return Ok(decoded);
+}
+
+struct Document {
+    json: JsonValue,
+    buffers: Vec<Vec<u8>>,
+    aspect_ratio: f32,
+}
+
+impl Document {
+    // Entry number index of a top level array such as "nodes" or "accessors"
+    fn entry(&self, array: &str, index: usize) -> Result<&JsonValue> {
+        return self
+            .json
+            .get(array)
+            .and_then(|entries| entries.as_array())
+            .and_then(|entries| entries.get(index))
+            .ok_or_else(|| invalid(format!("{}[{}] does not exist", array, index)));
+    }
+
+    fn build_scene(&self) -> Result<GltfScene> {
+        let mut scene = GltfScene { meshes: Vec::new(), cameras: Vec::new() };
+
+        for root in self.root_nodes()? {
+            self.visit_node(root, &Matrix4::identity(), &mut scene, 0)?;
+        }
+        return Ok(scene);
+    }
+
+    // Nodes of the default scene, or every node that isn't a child if the file has no scenes
+    fn root_nodes(&self) -> Result<Vec<usize>> {
+        if let Some(scenes) = self.json.get("scenes").and_then(|s| s.as_array()) {
+            let scene_index = self.json.get("scene").and_then(|s| s.as_usize()).unwrap_or(0);
+            let scene = scenes.get(scene_index).ok_or_else(|| invalid(format!("scene {} does not exist", scene_index)))?;
+            return Ok(index_list(scene.get("nodes")));
+        }
+
+        let nodes = self.json.get("nodes").and_then(|n| n.as_array()).map(|n| n.as_slice()).unwrap_or(&[]);
+        let children: Vec<usize> = nodes.iter().flat_map(|node| index_list(node.get("children"))).collect();
+        return Ok((0..nodes.len()).filter(|i| !children.contains(i)).collect());
+    }
+
+    fn visit_node(&self, index: usize, parent: &Matrix4, scene: &mut GltfScene, depth: usize) -> Result<()> {
+        // glTF requires the hierarchy to be a forest, this only protects against malformed cycles
+        if depth > 256 {
+            return Err(invalid("node hierarchy is too deep, is there a cycle?".to_string()));
+        }
+
+        let node = self.entry("nodes", index)?;
+        let world = *parent * local_transform(node)?;
+
+        if let Some(mesh) = node.get("mesh").and_then(|m| m.as_usize()) {
+            // Normals need the inverse, which a node scaled to zero along some axis doesn't have
+            let transform = Transform::from_matrix(world).ok_or_else(|| invalid(format!("node {} has a transform that can't be inverted", index)))?;
+            self.add_mesh(mesh, &transform, scene)?;
+        }
+        if let Some(camera) = node.get("camera").and_then(|c| c.as_usize()) {
+            if let Some(camera) = self.camera(camera, &world)? {
+                scene.cameras.push(camera);
+            }
+        }
+
+        for child in index_list(node.get("children")) {
+            self.visit_node(child, &world, scene, depth + 1)?;
+        }
+        return Ok(());
+    }
+
+    fn camera(&self, index: usize, world: &Matrix4) -> Result<Option<Camera>> {
+        let camera = self.entry("cameras", index)?;
+        let perspective = match camera.get("perspective") {
+            Some(perspective) if camera.get("type").and_then(|t| t.as_str()) == Some("perspective") => perspective,
+            // Orthographic cameras have no equivalent in our camera model
+            _ => return Ok(None),
+        };
+        let vertical_fov = perspective.get("yfov").and_then(|f| f.as_f32()).ok_or_else(|| invalid(format!("camera {} has no yfov", index)))?;
+
+        // Cameras look down their local -z with +y up
+        let origin = world.transform_point(&Vector3f::zero());
+        let forward = world.transform_vector(&-Vector3f::unit_z());
+        let up = world.transform_vector(&Vector3f::unit_y());
+        return Ok(Some(Camera::new_looking(origin, forward, up, vertical_fov, self.aspect_ratio)));
+    }
+
+    fn add_mesh(&self, index: usize, world: &Transform, scene: &mut GltfScene) -> Result<()> {
+        let mesh = self.entry("meshes", index)?;
+        let primitives = mesh.get("primitives").and_then(|p| p.as_array()).map(|p| p.as_slice()).unwrap_or(&[]);
+
+        for primitive in primitives {
+            let mode = primitive.get("mode").and_then(|m| m.as_usize()).unwrap_or(MODE_TRIANGLES);
+            // Points and lines have no surface to hit
+            if mode != MODE_TRIANGLES && mode != MODE_TRIANGLE_STRIP && mode != MODE_TRIANGLE_FAN {
+                continue;
+            }
+
+            let attributes = primitive.get("attributes").ok_or_else(|| invalid(format!("mesh {} primitive has no attributes", index)))?;
+            let attribute = |name: &str| attributes.get(name).and_then(|a| a.as_usize());
+
+            let position_accessor = attribute("POSITION").ok_or_else(|| invalid(format!("mesh {} primitive has no POSITION", index)))?;
+            let positions: Vec<Vector3f> = self
+                .read_vectors(position_accessor)?
+                .iter()
+                .map(|p| world.point(p))
+                .collect();
+
+            let normals: Vec<Vector3f> = match attribute("NORMAL") {
+                Some(accessor) => self.read_vectors(accessor)?.iter().map(|n| world.normal(n)).collect(),
+                None => Vec::new(),
+            };
+            let colors: Vec<Vector3f> = match attribute("COLOR_0") {
+                Some(accessor) => self.read_vectors(accessor)?,
+                None => Vec::new(),
+            };
+            // glTF puts the uv origin at the top left of the image, flipped here so v points up like every other primitive
+            let uvs: Vec<[f32; 2]> = match attribute("TEXCOORD_0") {
+                Some(accessor) => {
+                    let (values, components) = self.read_accessor(accessor)?;
+                    if components != 2 {
+                        return Err(invalid(format!("accessor {} has {} components, expected 2 for TEXCOORD_0", accessor, components)));
+                    }
+                    values.chunks_exact(2).map(|uv| [uv[0] as f32, 1.0 - uv[1] as f32]).collect()
+                }
+                None => Vec::new(),
+            };
+            if (!normals.is_empty() && normals.len() != positions.len())
+                || (!colors.is_empty() && colors.len() != positions.len())
+                || (!uvs.is_empty() && uvs.len() != positions.len()) {
+                return Err(invalid(format!("mesh {} has attributes with mismatched counts", index)));
+            }
+
+            let indices: Vec<u32> = match primitive.get("indices").and_then(|i| i.as_usize()) {
+                Some(accessor) => self.read_accessor(accessor)?.0.iter().map(|i| *i as u32).collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if let Some(bad_index) = indices.iter().find(|i| **i as usize >= positions.len()) {
+                return Err(invalid(format!("mesh {} references vertex {} but has only {}", index, bad_index, positions.len())));
+            }
+
+            let triangles = assemble_triangles(&indices, mode);
+            let material = self.material(primitive.get("material").and_then(|m| m.as_usize()))?;
+            scene.meshes.push(TriangleMesh::new(positions, normals, colors, triangles, material).with_uvs(uvs));
+        }
+        return Ok(());
+    }
+
+    fn material(&self, index: Option<usize>) -> Result<Box<dyn Material>> {
+        // Defaults from the spec, used for missing materials and missing fields alike
+        let mut base_color = Vector3f::one();
+        let mut metallic = 1.0;
+        let mut roughness = 1.0;
+
+        if let Some(index) = index {
+            if let Some(pbr) = self.entry("materials", index)?.get("pbrMetallicRoughness") {
+                if let Some(factor) = pbr.get("baseColorFactor").and_then(|f| f.as_f32_array()) {
+                    if factor.len() >= 3 {
+                        base_color = Vector3f::new(factor[0], factor[1], factor[2]);
+                    }
+                }
+                metallic = pbr.get("metallicFactor").and_then(|f| f.as_f32()).unwrap_or(metallic);
+                roughness = pbr.get("roughnessFactor").and_then(|f| f.as_f32()).unwrap_or(roughness);
+            }
+        }
+
+        if metallic >= METALLIC_THRESHOLD {
+            return Ok(Box::new(Metal::new(base_color, roughness)));
+        }
+        return Ok(Box::new(Lambertian::new(base_color)));
+    }
+
+    // First three components of each element, VEC4 colours drop their alpha
+    fn read_vectors(&self, accessor: usize) -> Result<Vec<Vector3f>> {
+        let (values, components) = self.read_accessor(accessor)?;
+        if components < 3 {
+            return Err(invalid(format!("accessor {} has {} components, expected at least 3", accessor, components)));
+        }
+        return Ok(values
+            .chunks_exact(components)
+            .map(|v| Vector3f::new(v[0] as f32, v[1] as f32, v[2] as f32))
+            .collect());
+    }
+
+    // Flattened accessor values and the number of components per element
+    fn read_accessor(&self, index: usize) -> Result<(Vec<f64>, usize)> {
+        let accessor = self.entry("accessors", index)?;
+        let count = accessor.get("count").and_then(|c| c.as_usize()).ok_or_else(|| invalid(format!("accessor {} has no count", index)))?;
+        let component_type = accessor.get("componentType").and_then(|c| c.as_usize()).unwrap_or(0);
+        let normalized = accessor.get("normalized").and_then(|n| n.as_bool()).unwrap_or(false);
+        let components = match accessor.get("type").and_then(|t| t.as_str()) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT4") => 16,
+            other => return Err(invalid(format!("accessor {} has unsupported type {:?}", index, other))),
+        };
+        if accessor.get("sparse").is_some() {
+            return Err(invalid(format!("accessor {} is sparse, which is not supported", index)));
+        }
+
+        let (component_size, max_value) = match component_type {
+            5120 => (1, i8::MAX as f64),
+            5121 => (1, u8::MAX as f64),
+            5122 => (2, i16::MAX as f64),
+            5123 => (2, u16::MAX as f64),
+            5125 => (4, u32::MAX as f64),
+            5126 => (4, 1.0),
+            _ => return Err(invalid(format!("accessor {} has unknown componentType {}", index, component_type))),
+        };
+
+        let too_large = || invalid(format!("accessor {} is larger than its buffer", index));
+        let value_count = count.checked_mul(components).ok_or_else(too_large)?;
+        let element_size = component_size * components;
+
+        // Accessors without a buffer view are all zeros, still bounded by the file's data so a bad count can't exhaust memory
+        let view_index = match accessor.get("bufferView").and_then(|v| v.as_usize()) {
+            Some(view_index) => view_index,
+            None => {
+                let buffer_bytes: usize = self.buffers.iter().map(|buffer| buffer.len()).sum();
+                if value_count.checked_mul(component_size).is_none_or(|bytes| bytes > buffer_bytes) {
+                    return Err(too_large());
+                }
+                return Ok((vec![0.0; value_count], components));
+            }
+        };
+        let view = self.entry("bufferViews", view_index)?;
+        let buffer_index = view.get("buffer").and_then(|b| b.as_usize()).unwrap_or(0);
+        let buffer = self.buffers.get(buffer_index).ok_or_else(|| invalid(format!("buffer {} does not exist", buffer_index)))?;
+
+        let start = view.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0)
+            + accessor.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+        let stride = view.get("byteStride").and_then(|s| s.as_usize()).unwrap_or(element_size);
+
+        // The last element has to fit in the buffer before anything is allocated for the values
+        if count > 0 {
+            let end = (count - 1).checked_mul(stride).and_then(|offset| offset.checked_add(start)).and_then(|offset| offset.checked_add(element_size));
+            if end.is_none_or(|end| end > buffer.len()) {
+                return Err(invalid(format!("accessor {} reads past the end of buffer {}", index, buffer_index)));
+            }
+        }
+
+        let mut values = Vec::with_capacity(value_count);
+        for element in 0..count {
+            for component in 0..components {
+                let offset = start + element * stride + component * component_size;
+                let raw = buffer
+                    .get(offset..offset + component_size)
+                    .ok_or_else(|| invalid(format!("accessor {} reads past the end of buffer {}", index, buffer_index)))?;
+                let value = match component_type {
+                    5120 => raw[0] as i8 as f64,
+                    5121 => raw[0] as f64,
+                    5122 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    5123 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    5125 => u32::from_le_bytes(raw.try_into().unwrap()) as f64,
+                    _ => f32::from_le_bytes(raw.try_into().unwrap()) as f64,
+                };
+                values.push(if normalized { (value / max_value).max(-1.0) } else { value });
+            }
+        }
+        return Ok((values, components));
+    }
+}
+
+fn index_list(value: Option<&JsonValue>) -> Vec<usize> {
+    return value
+        .and_then(|v| v.as_array())
+        .map(|entries| entries.iter().filter_map(|e| e.as_usize()).collect())
+        .unwrap_or_default();
+}
+
+// Node transform, either a full matrix or translation * rotation * scale
+fn local_transform(node: &JsonValue) -> Result<Matrix4> {
+    if let Some(matrix) = node.get("matrix").and_then(|m| m.as_f32_array()) {
+        if matrix.len() != 16 {
+            return Err(invalid("node matrix must have 16 values".to_string()));
+        }
+        return Ok(Matrix4::from_column_major(&matrix));
+    }
+
+    let vector = |key: &str, default: Vector3f| match node.get(key).and_then(|v| v.as_f32_array()) {
+        Some(v) if v.len() == 3 => Vector3f::new(v[0], v[1], v[2]),
+        _ => default,
+    };
+    let translation = vector("translation", Vector3f::zero());
+    let scale = vector("scale", Vector3f::one());
+    let rotation = match node.get("rotation").and_then(|r| r.as_f32_array()) {
+        Some(q) if q.len() == 4 => Matrix4::from_quaternion(q[0], q[1], q[2], q[3]),
+        _ => Matrix4::identity(),
+    };
+
+    return Ok(Matrix4::translation(translation) * rotation * Matrix4::scale(scale));
+}
+
+fn assemble_triangles(indices: &[u32], mode: usize) -> Vec<[u32; 3]> {
+    let mut triangles = Vec::new();
+    match mode {
+        MODE_TRIANGLE_STRIP => {
+            for i in 0..indices.len().saturating_sub(2) {
+                // Every other triangle in a strip is flipped to keep the winding consistent
+                if i % 2 == 0 {
+                    triangles.push([indices[i], indices[i + 1], indices[i + 2]]);
+                } else {
+                    triangles.push([indices[i + 1], indices[i], indices[i + 2]]);
+                }
+            }
+        }
+        MODE_TRIANGLE_FAN => {
+            for i in 1..indices.len().saturating_sub(1) {
+                triangles.push([indices[0], indices[i], indices[i + 1]]);
+            }
+        }
+        _ => {
+            for triangle in indices.chunks_exact(3) {
+                triangles.push([triangle[0], triangle[1], triangle[2]]);
+            }
+        }
+    }
+    return triangles;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectorlib::{hit::Hittable, ray::Ray};
+
+    #[test]
+    fn decode_uri_handles_escapes_and_multibyte_characters() {
+        assert_eq!(decode_uri("my%20model.bin"), "my model.bin");
+        assert_eq!(decode_uri("%C3%A9t%C3%A9.bin"), "été.bin");
+        // A percent sign followed by something that isn't two hex digits is kept as written
+        assert_eq!(decode_uri("%aé"), "%aé");
+        assert_eq!(decode_uri("100%"), "100%");
+        assert_eq!(decode_uri("%zz"), "%zz");
+    }
+
+    #[test]
+    fn decode_base64_accepts_both_alphabets() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("-_8=").unwrap(), decode_base64("+/8=").unwrap());
+        assert!(decode_base64("a*b").is_err());
+    }
+
+    #[test]
+    fn assembles_strips_and_fans() {
+        assert_eq!(assemble_triangles(&[0, 1, 2, 3], MODE_TRIANGLE_STRIP), vec![[0, 1, 2], [2, 1, 3]]);
+        assert_eq!(assemble_triangles(&[0, 1, 2, 3], MODE_TRIANGLE_FAN), vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(assemble_triangles(&[0, 1, 2, 3], MODE_TRIANGLES), vec![[0, 1, 2]]);
+    }
+
+    // One triangle two units in front of a camera, with its vertices and indices in a separate .bin file
+    #[test]
+    fn loads_meshes_and_cameras() {
+        let directory = std::env::temp_dir().join(format!("gltf_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut binary: Vec<u8> = [-1.0f32, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        binary.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        fs::write(directory.join("triangle bin.bin"), &binary).unwrap();
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 42, "uri": "triangle%20bin.bin"}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 6}],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 0.8, "znear": 0.1}}],
+            "nodes": [{"mesh": 0, "translation": [0, 0, -2]}, {"camera": 0}],
+            "scenes": [{"nodes": [0, 1]}]
+        }"#;
+        let path = directory.join("scene.gltf");
+        fs::write(&path, json).unwrap();
+        let scene = load_gltf(&path, 1.5);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let scene = scene.unwrap();
+        assert_eq!((scene.meshes.len(), scene.cameras.len()), (1, 1));
+        let hit = scene.meshes[0].hit(&Ray::new(Vector3f::zero(), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rejects_meshes_under_a_singular_transform() {
+        let path = std::env::temp_dir().join(format!("gltf_singular_{}.gltf", std::process::id()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "meshes": [{"primitives": []}],
+            "nodes": [{"mesh": 0, "scale": [1, 0, 1]}]
+        }"#;
+        fs::write(&path, json).unwrap();
+        let scene = load_gltf(&path, 1.0);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scene.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_accessors_past_their_buffer() {
+        let path = std::env::temp_dir().join(format!("gltf_short_{}.gltf", std::process::id()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 4, "uri": "data:application/octet-stream;base64,AAAAAA=="}],
+            "bufferViews": [{"buffer": 0, "byteLength": 4}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "nodes": [{"mesh": 0}]
+        }"#;
+        fs::write(&path, json).unwrap();
+        let scene = load_gltf(&path, 1.0);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scene.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,144 @@
+pub mod png;
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::vectorlib::vector3::Vector3f;
+
+// Decoded image with channels in [0,1] as stored in the file (no gamma conversion), row 0 at the top
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vector3f>,
+}
+
+fn invalid(message: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, format!("[ERR] Image: {}", message));
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vector3f>) -> Image {
+        assert_eq!(pixels.len(), width * height, "[ERR] Image needs one pixel per width * height");
+        return Image { width, height, pixels };
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vector3f {
+        return self.pixels[y * self.width + x];
+    }
+
+    // Rec. 709 luma, used for greyscale inputs such as heightmaps
+    pub fn luminance(&self, x: usize, y: usize) -> f32 {
+        let pixel = self.get(x, y);
+        return 0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z;
+    }
+}
+
+// PNG, or one of the Netpbm formats the renderer writes, picked from the start of the file
+pub fn load_image(path: &Path) -> Result<Image> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(png::PNG_SIGNATURE) {
+        return png::parse_png(&bytes);
+    }
+    return parse_pnm(&bytes);
+}
+
+// Netpbm images: P2/P5 greyscale and P3/P6 colour in ascii or binary
+pub fn parse_pnm(bytes: &[u8]) -> Result<Image> {
+    let mut position = 0;
+
+    // Header tokens are separated by whitespace and may have # comments between them
+    let next_token = |position: &mut usize| -> Result<String> {
+        loop {
+            while *position < bytes.len() && bytes[*position].is_ascii_whitespace() {
+                *position += 1;
+            }
+            if *position < bytes.len() && bytes[*position] == b'#' {
+                while *position < bytes.len() && bytes[*position] != b'\n' {
+                    *position += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = *position;
+        while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() {
+            *position += 1;
+        }
+        if start == *position {
+            return Err(invalid("unexpected end of file".to_string()));
+        }
+        return Ok(String::from_utf8_lossy(&bytes[start..*position]).into_owned());
+    };
+    let next_number = |position: &mut usize| -> Result<usize> {
+        let token = next_token(position)?;
+        return token.parse().map_err(|_| invalid(format!("expected a number, found '{}'", token)));
+    };
+
+    let magic = next_token(&mut position)?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(invalid(format!("unsupported format '{}', expected P2, P3, P5 or P6", magic))),
+    };
+    let width = next_number(&mut position)?;
+    let height = next_number(&mut position)?;
+    let max_value = next_number(&mut position)?;
+    if max_value == 0 || max_value > u16::MAX as usize {
+        return Err(invalid(format!("max value {} is out of range", max_value)));
+    }
+
+    // The header is untrusted, every sample takes at least one byte so the file has to be big enough
+    // for them before anything is allocated. Binary data starts after exactly one whitespace byte
+    let sample_size = if binary && max_value > 255 { 2 } else { 1 };
+    let data_start = if binary { position + 1 } else { position };
+    let sample_count = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels));
+    let data_length = sample_count.and_then(|count| count.checked_mul(sample_size));
+    let (sample_count, data_length) = match (sample_count, data_length) {
+        (Some(count), Some(length)) if length <= bytes.len().saturating_sub(data_start) => (count, length),
+        _ => return Err(invalid(format!("{}x{} image is larger than the file's pixel data", width, height))),
+    };
+
+    let mut samples = Vec::with_capacity(sample_count);
+    if binary {
+        let data = &bytes[data_start..data_start + data_length];
+        for sample in data.chunks_exact(sample_size) {
+            let value = if sample_size == 2 { u16::from_be_bytes([sample[0], sample[1]]) as usize } else { sample[0] as usize };
+            samples.push(value as f32 / max_value as f32);
+        }
+    } else {
+        for _ in 0..sample_count {
+            samples.push(next_number(&mut position)? as f32 / max_value as f32);
+        }
+    }
+
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|sample| if channels == 1 { Vector3f::uniform(sample[0]) } else { Vector3f::new(sample[0], sample[1], sample[2]) })
+        .collect();
+    return Ok(Image::new(width, height, pixels));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii_and_binary_pnm() {
+        let ascii = parse_pnm(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        assert_eq!((ascii.width, ascii.height), (2, 1));
+        assert_eq!(ascii.get(1, 0).z, 1.0);
+
+        let binary = parse_pnm(b"P5 2 2 255\n\x00\x40\x80\xff").unwrap();
+        assert_eq!(binary.get(1, 1).x, 1.0);
+    }
+
+    #[test]
+    fn rejects_headers_larger_than_the_file() {
+        for header in [&b"P6 99999999 99999999 255\n\x00"[..], b"P6 4294967296 4294967296 255\n", b"P2 3 3 255\n1 2 3"] {
+            assert_eq!(parse_pnm(header).err().unwrap().kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::io::{Error, ErrorKind, Result};
+
+use crate::loaders::image::Image;
+use crate::vectorlib::vector3::Vector3f;
+
+// Decoder for non-interlaced PNG files of every colour type and bit depth, with its own inflate so
+// no compression library is needed. Alpha is dropped and checksums are not verified.
+
+pub const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+const COLOR_GREY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GREY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+fn invalid(message: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, format!("[ERR] PNG: {}", message));
+}
+
+pub fn parse_png(bytes: &[u8]) -> Result<Image> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(invalid("missing PNG signature".to_string()));
+    }
+
+    let mut position = PNG_SIGNATURE.len();
+    let mut header: Option<(usize, usize, u8, u8)> = None;
+    let mut palette: Vec<Vector3f> = Vec::new();
+    let mut compressed: Vec<u8> = Vec::new();
+    loop {
+        let chunk_header = bytes.get(position..position + 8).ok_or_else(|| invalid("file ends before the IEND chunk".to_string()))?;
+        let length = u32::from_be_bytes(chunk_header[0..4].try_into().unwrap()) as usize;
+        let kind = &chunk_header[4..8];
+        let data = bytes.get(position + 8..position + 8 + length).ok_or_else(|| invalid("chunk runs past the end of the file".to_string()))?;
+        // Data is followed by a 4 byte CRC
+        position += 12 + length;
+
+        match kind {
+            b"IHDR" => {
+                if length < 13 {
+                    return Err(invalid("IHDR chunk is too short".to_string()));
+                }
+                let width = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+                let height = u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+                let (bit_depth, color_type, interlace) = (data[8], data[9], data[12]);
+                if width == 0 || height == 0 {
+                    return Err(invalid(format!("image is {}x{}, both sides must be at least 1", width, height)));
+                }
+                if interlace != 0 {
+                    return Err(invalid("interlaced images are not supported".to_string()));
+                }
+                header = Some((width, height, bit_depth, color_type));
+            }
+            b"PLTE" => {
+                palette = data.chunks_exact(3).map(|rgb| Vector3f::new(rgb[0] as f32, rgb[1] as f32, rgb[2] as f32) / 255.0).collect();
+            }
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Ancillary chunks such as gamma, text and transparency are skipped
+            _ => {}
+        }
+    }
+
+    let (width, height, bit_depth, color_type) = header.ok_or_else(|| invalid("missing IHDR chunk".to_string()))?;
+    let channels = match color_type {
+        COLOR_GREY | COLOR_PALETTE => 1,
+        COLOR_GREY_ALPHA => 2,
+        COLOR_RGB => 3,
+        COLOR_RGBA => 4,
+        _ => return Err(invalid(format!("unknown colour type {}", color_type))),
+    };
+    if ![1, 2, 4, 8, 16].contains(&bit_depth) {
+        return Err(invalid(format!("unsupported bit depth {}", bit_depth)));
+    }
+    if color_type == COLOR_PALETTE && palette.is_empty() {
+        return Err(invalid("palette image has no PLTE chunk".to_string()));
+    }
+
+    let bits_per_pixel = channels * bit_depth as usize;
+    let row_bytes = (width * bits_per_pixel).div_ceil(8);
+    let data = inflate_zlib(&compressed)?;
+    if (row_bytes + 1).checked_mul(height).is_none_or(|length| data.len() < length) {
+        return Err(invalid("not enough image data".to_string()));
+    }
+    let rows = unfilter(&data, height, row_bytes, bits_per_pixel.div_ceil(8))?;
+
+    let max_value = ((1_u32 << bit_depth) - 1) as f32;
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in rows.chunks_exact(row_bytes) {
+        for x in 0..width {
+            let sample = |channel: usize| -> u32 {
+                let bit_offset = (x * channels + channel) * bit_depth as usize;
+                return match bit_depth {
+                    16 => u16::from_be_bytes([row[bit_offset / 8], row[bit_offset / 8 + 1]]) as u32,
+                    8 => row[bit_offset / 8] as u32,
+                    // Packed samples start at the most significant bit
+                    _ => ((row[bit_offset / 8] >> (8 - bit_depth as usize - bit_offset % 8)) & ((1 << bit_depth) - 1)) as u32,
+                };
+            };
+            let pixel = match color_type {
+                COLOR_PALETTE => *palette.get(sample(0) as usize).ok_or_else(|| invalid(format!("palette index {} is out of range", sample(0))))?,
+                COLOR_GREY | COLOR_GREY_ALPHA => Vector3f::uniform(sample(0) as f32 / max_value),
+                _ => Vector3f::new(sample(0) as f32, sample(1) as f32, sample(2) as f32) / max_value,
+            };
+            pixels.push(pixel);
+        }
+    }
+    return Ok(Image::new(width, height, pixels));
+}
+
+// Reverses the per row prediction filters, bytes_per_pixel is at least 1 for packed formats
+fn unfilter(data: &[u8], height: usize, row_bytes: usize, bytes_per_pixel: usize) -> Result<Vec<u8>> {
+    let mut rows = vec![0_u8; height * row_bytes];
+    for y in 0..height {
+        let filter = data[y * (row_bytes + 1)];
+        let source = &data[y * (row_bytes + 1) + 1..(y + 1) * (row_bytes + 1)];
+        let (previous_rows, current_rows) = rows.split_at_mut(y * row_bytes);
+        let above = if y > 0 { &previous_rows[(y - 1) * row_bytes..] } else { &[][..] };
+        let current = &mut current_rows[..row_bytes];
+
+        for i in 0..row_bytes {
+            let left = if i >= bytes_per_pixel { current[i - bytes_per_pixel] } else { 0 };
+            let up = if y > 0 { above[i] } else { 0 };
+            let up_left = if y > 0 && i >= bytes_per_pixel { above[i - bytes_per_pixel] } else { 0 };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid(format!("unknown filter type {} on row {}", filter, y))),
+            };
+            current[i] = source[i].wrapping_add(prediction);
+        }
+    }
+    return Ok(rows);
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        return left;
+    }
+    if distance_up <= distance_up_left {
+        return up;
+    }
+    return up_left;
+}
+
+// Least significant bit first reader over a deflate stream
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.bytes.get(self.position).ok_or_else(|| invalid("compressed data ends early".to_string()))?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        return Ok(value);
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+// Canonical Huffman code stored as the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0_u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0_u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0_u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        return Huffman { counts, symbols };
+    }
+
+    // Reads one bit at a time, codes of each length are consecutive so only the first code of each length is tracked
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return Err(invalid("invalid Huffman code".to_string()));
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order the code length code lengths are stored in for dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// zlib stream as stored in IDAT, a two byte header then raw deflate (RFC 1950 / 1951)
+fn inflate_zlib(bytes: &[u8]) -> Result<Vec<u8>> {
+    if bytes.len() < 2 || bytes[0] & 0x0F != 8 || !(bytes[0] as u16 * 256 + bytes[1] as u16).is_multiple_of(31) {
+        return Err(invalid("image data is not a zlib stream".to_string()));
+    }
+    if bytes[1] & 0x20 != 0 {
+        return Err(invalid("zlib preset dictionaries are not supported".to_string()));
+    }
+
+    let mut reader = BitReader { bytes: &bytes[2..], position: 0, bit: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last_block = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = reader.bytes.get(reader.position..reader.position + 4).ok_or_else(|| invalid("stored block ends early".to_string()))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let start = reader.position + 4;
+                let data = reader.bytes.get(start..start + length).ok_or_else(|| invalid("stored block ends early".to_string()))?;
+                output.extend_from_slice(data);
+                reader.position = start + length;
+            }
+            1 => {
+                let mut lengths = [0_u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                inflate_block(&mut reader, &mut output, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid("invalid deflate block type".to_string())),
+        }
+        if last_block {
+            return Ok(output);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0_u8; 19];
+    for slot in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*slot] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+
+    // Literal and distance lengths are run length coded together
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_lengths.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat with no previous length".to_string()))?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("code lengths overrun".to_string()));
+    }
+    return Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])));
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let length_index = symbol - 257;
+        if length_index >= LENGTH_BASE.len() {
+            return Err(invalid(format!("invalid length symbol {}", symbol)));
+        }
+        let length = LENGTH_BASE[length_index] as usize + reader.bits(LENGTH_EXTRA[length_index] as u32)? as usize;
+        let distance_index = distances.decode(reader)? as usize;
+        if distance_index >= DISTANCE_BASE.len() {
+            return Err(invalid(format!("invalid distance symbol {}", distance_index)));
+        }
+        let distance = DISTANCE_BASE[distance_index] as usize + reader.bits(DISTANCE_EXTRA[distance_index] as u32)? as usize;
+        if distance > output.len() {
+            return Err(invalid("back reference before the start of the data".to_string()));
+        }
+
+        // Copied a byte at a time since the match may overlap the bytes it is producing
+        let start = output.len() - distance;
+        for i in 0..length {
+            output.push(output[start + i]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Streams from Python's zlib, the block type is in bits 1-2 of the byte after the header
+    const STORED: [u8; 23] = [120, 1, 1, 12, 0, 243, 255, 115, 116, 111, 114, 101, 100, 32, 98, 108, 111, 99, 107, 31, 128, 4, 189];
+    const FIXED: [u8; 21] = [120, 1, 75, 76, 42, 74, 76, 78, 76, 73, 4, 82, 10, 137, 8, 54, 0, 105, 85, 8, 201];
+    const DYNAMIC: [u8; 37] = [
+        120, 218, 237, 201, 49, 1, 0, 48, 12, 195, 48, 172, 118, 18, 254, 20, 6, 99, 79, 245, 138, 180, 177, 91, 51, 88, 81, 102, 34,
+        23, 63, 226, 1, 50, 159, 154, 177,
+    ];
+    // A fixed block, an empty stored block from a sync flush, then a second fixed block
+    const MIXED: [u8; 39] = [
+        120, 1, 74, 203, 44, 42, 46, 81, 72, 202, 201, 79, 206, 214, 81, 0, 0, 0, 0, 255, 255, 43, 201, 72, 205, 3, 17, 10, 197, 169,
+        201, 249, 121, 41, 0, 149, 198, 10, 76,
+    ];
+
+    fn block_type(stream: &[u8]) -> u8 {
+        return (stream[2] >> 1) & 3;
+    }
+
+    fn assert_pixel(image: &Image, x: usize, y: usize, expected: [f32; 3]) {
+        let pixel = image.get(x, y);
+        for (actual, expected) in [pixel.x, pixel.y, pixel.z].iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-6, "pixel ({}, {}) is {} not {:?}", x, y, pixel, expected);
+        }
+    }
+
+    #[test]
+    fn inflates_stored_blocks() {
+        assert_eq!(block_type(&STORED), 0);
+        assert_eq!(inflate_zlib(&STORED).unwrap(), b"stored block");
+    }
+
+    #[test]
+    fn inflates_fixed_huffman_blocks() {
+        // The repeated word is a back reference
+        assert_eq!(block_type(&FIXED), 1);
+        assert_eq!(inflate_zlib(&FIXED).unwrap(), b"abracadabra abracadabra");
+    }
+
+    #[test]
+    fn inflates_dynamic_huffman_blocks() {
+        assert_eq!(block_type(&DYNAMIC), 2);
+        let expected: Vec<u8> = (0..400_u32).map(|i| ((i * i * 7 + i / 5) % 5) as u8 + b'a').collect();
+        assert_eq!(inflate_zlib(&DYNAMIC).unwrap(), expected);
+    }
+
+    #[test]
+    fn inflates_streams_of_several_blocks() {
+        assert_eq!(inflate_zlib(&MIXED).unwrap(), b"first block, then the second");
+    }
+
+    #[test]
+    fn rejects_bad_zlib_streams() {
+        // Wrong compression method, a header that fails its check, and a stream cut short
+        assert!(inflate_zlib(&[121, 1, 1, 0, 0, 255, 255]).is_err());
+        assert!(inflate_zlib(&[120, 2, 1, 0, 0, 255, 255]).is_err());
+        assert!(inflate_zlib(&DYNAMIC[..20]).is_err());
+    }
+
+    #[test]
+    fn decodes_two_bit_palette_images() {
+        let image = parse_png(include_bytes!("fixtures/palette_2bit.png")).unwrap();
+        assert_eq!((image.width, image.height), (4, 2));
+        let palette = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0, 1.0]];
+        for x in 0..4 {
+            assert_pixel(&image, x, 0, palette[x]);
+            assert_pixel(&image, x, 1, palette[3 - x]);
+        }
+    }
+
+    #[test]
+    fn decodes_sixteen_bit_images() {
+        let image = parse_png(include_bytes!("fixtures/rgb_16bit.png")).unwrap();
+        let expected = [[[0, 32768, 65535], [65535, 0, 1]], [[4660, 22136, 39612], [1, 2, 3]]];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                assert_pixel(&image, x, y, pixel.map(|value| value as f32 / 65535.0));
+            }
+        }
+    }
+
+    #[test]
+    fn reverses_every_filter_type() {
+        // Row y is stored with filter type y: none, sub, up, average and Paeth
+        let image = parse_png(include_bytes!("fixtures/filters.png")).unwrap();
+        assert_eq!((image.width, image.height), (4, 5));
+        for y in 0..5 {
+            for x in 0..4 {
+                let expected = [(x * 60 + y * 17) % 256, (x * 11 + y * 90) % 256, (255 - x * 30 - y * 20) % 256];
+                assert_pixel(&image, x as usize, y as usize, expected.map(|value| value as f32 / 255.0));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_zero_sized_images() {
+        let mut bytes = include_bytes!("fixtures/filters.png").to_vec();
+        // IHDR data starts after the signature and the chunk's length and type, width first
+        bytes[16..20].copy_from_slice(&0_u32.to_be_bytes());
+        assert_eq!(parse_png(&bytes).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+// Minimal JSON reader, just enough for the scene formats we import
+
+// Objects and arrays parse recursively, deeper input is refused rather than overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(HashMap<String, JsonValue>),
+}
+
+impl JsonValue {
+    pub fn parse(text: &str) -> Result<JsonValue> {
+        let mut parser = Parser { bytes: text.as_bytes(), position: 0, depth: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("trailing characters after value"));
+        }
+        return Ok(value);
+    }
+
+    // Member of an object, None for missing keys or non objects
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        return match self {
+            JsonValue::Object(members) => members.get(key),
+            _ => None,
+        };
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None,
+        };
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        return self.as_f64().map(|number| number as f32);
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        return match self {
+            JsonValue::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+            _ => None,
+        };
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        };
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        return match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        };
+    }
+
+    // Array of numbers such as a glTF matrix or colour factor
+    pub fn as_f32_array(&self) -> Option<Vec<f32>> {
+        return self.as_array()?.iter().map(|value| value.as_f32()).collect();
+    }
+}
+
+struct Parser<'t> {
+    bytes: &'t [u8],
+    position: usize,
+    // Objects and arrays currently open around the position
+    depth: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn error(&self, message: &str) -> Error {
+        return Error::new(ErrorKind::InvalidData, format!("[ERR] JSON: {} at byte {}", message, self.position));
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.bytes.len() && self.bytes[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        return self.bytes.get(self.position).copied();
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<()> {
+        if !self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            return Err(self.error(&format!("expected '{}'", literal)));
+        }
+        self.position += literal.len();
+        return Ok(());
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue> {
+        return match self.peek() {
+            Some(b'{') => self.parse_nested(Parser::parse_object),
+            Some(b'[') => self.parse_nested(Parser::parse_array),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b't') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| JsonValue::Null),
+            Some(_) => self.parse_number(),
+            None => Err(self.error("unexpected end of input")),
+        };
+    }
+
+    fn parse_nested(&mut self, parse: fn(&mut Parser<'t>) -> Result<JsonValue>) -> Result<JsonValue> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        return value;
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue> {
+        let mut members = HashMap::new();
+        self.expect("{")?;
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected object key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            let value = self.parse_value()?;
+            members.insert(key, value);
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue> {
+        let mut values = Vec::new();
+        self.expect("[")?;
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut string = String::new();
+        loop {
+            let start = self.position;
+            while self.position < self.bytes.len() && self.bytes[self.position] != b'"' && self.bytes[self.position] != b'\\' {
+                self.position += 1;
+            }
+            string.push_str(std::str::from_utf8(&self.bytes[start..self.position]).map_err(|_| self.error("invalid utf-8"))?);
+
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(string);
+                }
+                Some(b'\\') => {
+                    let escaped = *self.bytes.get(self.position + 1).ok_or_else(|| self.error("unterminated escape"))?;
+                    self.position += 2;
+                    match escaped {
+                        b'"' => string.push('"'),
+                        b'\\' => string.push('\\'),
+                        b'/' => string.push('/'),
+                        b'b' => string.push('\u{8}'),
+                        b'f' => string.push('\u{c}'),
+                        b'n' => string.push('\n'),
+                        b'r' => string.push('\r'),
+                        b't' => string.push('\t'),
+                        b'u' => string.push(self.parse_unicode_escape()?),
+                        _ => return Err(self.error("unknown escape")),
+                    }
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let hex = self.bytes.get(self.position..self.position + 4).ok_or_else(|| self.error("short unicode escape"))?;
+        let hex = std::str::from_utf8(hex).map_err(|_| self.error("bad unicode escape"))?;
+        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error("bad unicode escape"))?;
+        self.position += 4;
+        return Ok(code);
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let mut code = self.parse_hex4()?;
+        // Surrogate pairs come as two escapes
+        if (0xD800..0xDC00).contains(&code) && self.bytes[self.position..].starts_with(b"\\u") {
+            let escape_start = self.position;
+            self.position += 2;
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                // Not the other half of a pair, leave it to be read as a character of its own
+                self.position = escape_start;
+                return Ok(char::REPLACEMENT_CHARACTER);
+            }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+        }
+        return Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue> {
+        let start = self.position;
+        while self.position < self.bytes.len() && matches!(self.bytes[self.position], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or("");
+        return text.parse::<f64>().map(JsonValue::Number).map_err(|_| self.error("invalid value"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let value = JsonValue::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "d"}, "e": false} "#).unwrap();
+        let array = value.get("a").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(array[0].as_usize(), Some(1));
+        assert_eq!(array[1].as_f64(), Some(-25.0));
+        assert_eq!(array[2].as_bool(), Some(true));
+        assert!(matches!(array[3], JsonValue::Null));
+        assert_eq!(value.get("b").and_then(|b| b.get("c")).and_then(JsonValue::as_str), Some("d"));
+        assert_eq!(value.get("e").and_then(JsonValue::as_bool), Some(false));
+        assert!(value.get("missing").is_none());
+    }
+
+    #[test]
+    fn decodes_escapes_and_surrogates() {
+        let value = JsonValue::parse(r#""q\"\\\/\n\t\u00e9\ud83d\ude00""#).unwrap();
+        assert_eq!(value.as_str(), Some("q\"\\/\n\té\u{1F600}"));
+        // A high surrogate without a matching low one can't be decoded
+        assert_eq!(JsonValue::parse(r#""\ud83d""#).unwrap().as_str(), Some("\u{FFFD}"));
+        assert_eq!(JsonValue::parse(r#""\ud83d\u0041""#).unwrap().as_str(), Some("\u{FFFD}A"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for text in ["", "{", "[1,", "[1 2]", "{\"a\" 1}", "{1: 2}", "\"open", "\"\\x\"", "\"\\u12\"", "tru", "nul", "1 2", "-", "{\"a\": }"] {
+            assert_eq!(JsonValue::parse(text).err().unwrap().kind(), ErrorKind::InvalidData, "{}", text);
+        }
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(JsonValue::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(JsonValue::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(JsonValue::parse(&"[{\"a\":".repeat(50000)).is_err());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod gltf;
+pub mod image;
+pub mod json;
+pub mod ply;
@@ -0,0 +1,432 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::material::Material;
+use crate::vectorlib::{mesh::TriangleMesh, vector3::Vector3f};
+
+// Loader for Stanford PLY meshes, supports the ascii, binary_little_endian and binary_big_endian formats.
+// Reads vertex positions, normals, colours and texture coordinates and the face list, polygons are fan triangulated.
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+enum Property {
+    Scalar { name: String, scalar_type: ScalarType },
+    List { name: String, count_type: ScalarType, item_type: ScalarType },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(message: String) -> Error {
+    return Error::new(ErrorKind::InvalidData, format!("[ERR] PLY: {}", message));
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType> {
+        return match name {
+            "char" | "int8" => Ok(ScalarType::Int8),
+            "uchar" | "uint8" => Ok(ScalarType::UInt8),
+            "short" | "int16" => Ok(ScalarType::Int16),
+            "ushort" | "uint16" => Ok(ScalarType::UInt16),
+            "int" | "int32" => Ok(ScalarType::Int32),
+            "uint" | "uint32" => Ok(ScalarType::UInt32),
+            "float" | "float32" => Ok(ScalarType::Float32),
+            "double" | "float64" => Ok(ScalarType::Float64),
+            _ => Err(invalid(format!("unknown property type '{}'", name))),
+        };
+    }
+
+    fn size(&self) -> usize {
+        return match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        };
+    }
+
+    // Integer colour channels are stored in [0, max], floats are already in [0,1]
+    fn color_scale(&self) -> f64 {
+        return match self {
+            ScalarType::Int8 => i8::MAX as f64,
+            ScalarType::UInt8 => u8::MAX as f64,
+            ScalarType::Int16 => i16::MAX as f64,
+            ScalarType::UInt16 => u16::MAX as f64,
+            ScalarType::Int32 => i32::MAX as f64,
+            ScalarType::UInt32 => u32::MAX as f64,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        };
+    }
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        return match self {
+            Property::Scalar { name, .. } => name,
+            Property::List { name, .. } => name,
+        };
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    body_start: usize,
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut position = 0;
+    let mut first_line = true;
+
+    loop {
+        let line_end = bytes[position..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| invalid("header is missing end_header".to_string()))?;
+        let line = std::str::from_utf8(&bytes[position..position + line_end])
+            .map_err(|_| invalid("header is not valid text".to_string()))?
+            .trim();
+        position += line_end + 1;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first_line {
+            if line != "ply" {
+                return Err(invalid("missing 'ply' magic number".to_string()));
+            }
+            first_line = false;
+            continue;
+        }
+
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown format '{}'", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| invalid(format!("bad element count '{}'", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            }
+            ["property", "list", count_type, item_type, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before any element".to_string()))?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count_type: ScalarType::parse(count_type)?,
+                    item_type: ScalarType::parse(item_type)?,
+                });
+            }
+            ["property", scalar_type, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("property before any element".to_string()))?;
+                element.properties.push(Property::Scalar { name: name.to_string(), scalar_type: ScalarType::parse(scalar_type)? });
+            }
+            ["end_header"] => break,
+            _ => return Err(invalid(format!("unexpected header line '{}'", line))),
+        }
+    }
+
+    let format = format.ok_or_else(|| invalid("header has no format line".to_string()))?;
+    return Ok(Header { format, elements, body_start: position });
+}
+
+// Reads scalars out of the body one at a time, whatever the format
+struct BodyReader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+    format: Format,
+}
+
+impl<'b> BodyReader<'b> {
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+
+        let size = scalar_type.size();
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + size)
+            .ok_or_else(|| invalid("unexpected end of binary data".to_string()))?;
+        self.position += size;
+
+        let little_endian = self.format == Format::BinaryLittleEndian;
+        macro_rules! decode {
+            ($t:ty) => {{
+                let raw = bytes.try_into().unwrap();
+                (if little_endian { <$t>::from_le_bytes(raw) } else { <$t>::from_be_bytes(raw) }) as f64
+            }};
+        }
+
+        return Ok(match scalar_type {
+            ScalarType::Int8 => decode!(i8),
+            ScalarType::UInt8 => decode!(u8),
+            ScalarType::Int16 => decode!(i16),
+            ScalarType::UInt16 => decode!(u16),
+            ScalarType::Int32 => decode!(i32),
+            ScalarType::UInt32 => decode!(u32),
+            ScalarType::Float32 => decode!(f32),
+            ScalarType::Float64 => decode!(f64),
+        });
+    }
+
+    fn read_ascii(&mut self) -> Result<f64> {
+        while self.position < self.bytes.len() && self.bytes[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < self.bytes.len() && !self.bytes[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(invalid("unexpected end of ascii data".to_string()));
+        }
+
+        let token = std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or("");
+        return token.parse::<f64>().map_err(|_| invalid(format!("bad number '{}'", token)));
+    }
+
+    // Reads a whole property, lists come back as all their items
+    fn read_property(&mut self, property: &Property, values: &mut Vec<f64>) -> Result<()> {
+        values.clear();
+        match property {
+            Property::Scalar { scalar_type, .. } => values.push(self.read(*scalar_type)?),
+            Property::List { count_type, item_type, .. } => {
+                let count = self.read(*count_type)? as usize;
+                for _ in 0..count {
+                    values.push(self.read(*item_type)?);
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+const COLOR_NAMES: [[&str; 3]; 3] = [
+    ["red", "r", "diffuse_red"],
+    ["green", "g", "diffuse_green"],
+    ["blue", "b", "diffuse_blue"],
+];
+
+// Texture coordinates go by several names depending on the exporter
+const UV_NAMES: [[&str; 4]; 2] = [
+    ["u", "s", "texture_u", "texture_s"],
+    ["v", "t", "texture_v", "texture_t"],
+];
+
+pub fn load_ply(path: &Path, material: Box<dyn Material>) -> Result<TriangleMesh> {
+    let bytes = fs::read(path)?;
+    return parse_ply(&bytes, material);
+}
+
+pub fn parse_ply(bytes: &[u8], material: Box<dyn Material>) -> Result<TriangleMesh> {
+    let header = parse_header(bytes)?;
+    let mut reader = BodyReader { bytes, position: header.body_start, format: header.format };
+
+    let mut positions: Vec<Vector3f> = Vec::new();
+    let mut normals: Vec<Vector3f> = Vec::new();
+    let mut colors: Vec<Vector3f> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut faces: Vec<Vec<u32>> = Vec::new();
+    let mut values: Vec<f64> = Vec::new();
+
+    for element in header.elements.iter() {
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |names: &[&str]| element.properties.iter().position(|p| names.contains(&p.name()));
+                let position_slots = [find(&["x"]), find(&["y"]), find(&["z"])];
+                let normal_slots = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                let color_slots = COLOR_NAMES.map(|names| find(&names));
+                let uv_slots = UV_NAMES.map(|names| find(&names));
+                if position_slots.iter().any(|slot| slot.is_none()) {
+                    return Err(invalid("vertex element has no x, y, z properties".to_string()));
+                }
+                let has_normals = normal_slots.iter().all(|slot| slot.is_some());
+                let has_colors = color_slots.iter().all(|slot| slot.is_some());
+                let has_uvs = uv_slots.iter().all(|slot| slot.is_some());
+
+                for _ in 0..element.count {
+                    let mut position = Vector3f::zero();
+                    let mut normal = Vector3f::zero();
+                    let mut color = Vector3f::zero();
+                    let mut uv = [0.0; 2];
+
+                    for (index, property) in element.properties.iter().enumerate() {
+                        reader.read_property(property, &mut values)?;
+                        let value = values.first().copied().unwrap_or(0.0);
+                        for (component, slot) in uv_slots.iter().enumerate() {
+                            if *slot == Some(index) {
+                                uv[component] = value as f32;
+                            }
+                        }
+                        for axis in 0..3 {
+                            if position_slots[axis] == Some(index) {
+                                *position.axis_mut(axis) = value as f32;
+                            }
+                            if normal_slots[axis] == Some(index) {
+                                *normal.axis_mut(axis) = value as f32;
+                            }
+                            if color_slots[axis] == Some(index) {
+                                if let Property::Scalar { scalar_type, .. } = property {
+                                    *color.axis_mut(axis) = (value / scalar_type.color_scale()) as f32;
+                                }
+                            }
+                        }
+                    }
+
+                    positions.push(position);
+                    if has_normals {
+                        normals.push(normal);
+                    }
+                    if has_colors {
+                        colors.push(color);
+                    }
+                    if has_uvs {
+                        uvs.push(uv);
+                    }
+                }
+            }
+            "face" => {
+                let index_slot = element
+                    .properties
+                    .iter()
+                    .position(|p| matches!(p, Property::List { .. }) && (p.name() == "vertex_indices" || p.name() == "vertex_index"))
+                    .ok_or_else(|| invalid("face element has no vertex_indices list".to_string()))?;
+
+                for _ in 0..element.count {
+                    for (index, property) in element.properties.iter().enumerate() {
+                        reader.read_property(property, &mut values)?;
+                        if index == index_slot {
+                            // Casting would quietly turn a negative or fractional index into a valid one
+                            if let Some(bad_index) = values.iter().find(|v| **v < 0.0 || v.fract() != 0.0 || **v > u32::MAX as f64) {
+                                return Err(invalid(format!("bad vertex index {}", bad_index)));
+                            }
+                            faces.push(values.iter().map(|v| *v as u32).collect());
+                        }
+                    }
+                }
+            }
+            // Anything else (edges, materials, ...) is read past and ignored
+            _ => {
+                for _ in 0..element.count {
+                    for property in element.properties.iter() {
+                        reader.read_property(property, &mut values)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut triangles: Vec<[u32; 3]> = Vec::with_capacity(faces.len());
+    for face in faces.iter() {
+        if let Some(bad_index) = face.iter().find(|i| **i as usize >= positions.len()) {
+            return Err(invalid(format!("face references vertex {} but there are only {}", bad_index, positions.len())));
+        }
+        // Fan triangulation, fine for the convex polygons scanners produce
+        for i in 1..face.len().saturating_sub(1) {
+            triangles.push([face[0], face[i], face[i + 1]]);
+        }
+    }
+
+    return Ok(TriangleMesh::new(positions, normals, colors, triangles, material).with_uvs(uvs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vectorlib::{hit::Hittable, ray::Ray};
+
+    const HEADER: &str = "element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+        property uchar red\nproperty uchar green\nproperty uchar blue\nproperty float u\nproperty float v\n\
+        element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    // Unit square in the xy plane, red at the origin and blue elsewhere, with uvs matching x and y
+    const VERTICES: [[f32; 5]; 4] = [[0.0, 0.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0, 0.0], [1.0, 1.0, 0.0, 1.0, 1.0], [0.0, 1.0, 0.0, 0.0, 1.0]];
+
+    fn header(format: &str) -> Vec<u8> {
+        return format!("ply\nformat {} 1.0\ncomment test square\n{}", format, HEADER).into_bytes();
+    }
+
+    fn binary_square(format: &str, encode_f32: fn(f32) -> [u8; 4], encode_i32: fn(i32) -> [u8; 4], face: &[i32]) -> Vec<u8> {
+        let mut bytes = header(format);
+        for (index, [x, y, z, u, v]) in VERTICES.iter().enumerate() {
+            for coordinate in [x, y, z] {
+                bytes.extend(encode_f32(*coordinate));
+            }
+            bytes.extend(if index == 0 { [255, 0, 0] } else { [0, 0, 255] });
+            bytes.extend(encode_f32(*u));
+            bytes.extend(encode_f32(*v));
+        }
+        bytes.push(face.len() as u8);
+        for index in face {
+            bytes.extend(encode_i32(*index));
+        }
+        return bytes;
+    }
+
+    fn assert_square(mesh: &TriangleMesh) {
+        assert_eq!(mesh.triangle_count(), 2);
+        let ray = Ray::new(Vector3f::new(0.25, 0.5, 1.0), Vector3f::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        let color = hit.vertex_color.unwrap();
+        assert!(color.x > 0.0 && color.x < 1.0 && color.y == 0.0 && color.z > 0.0);
+    }
+
+    fn material() -> Box<dyn Material> {
+        return Box::new(Lambertian::new(Vector3f::one()));
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let mut bytes = header("ascii");
+        bytes.extend(b"0 0 0 255 0 0 0 0\n1 0 0 0 0 255 1 0\n1 1 0 0 0 255 1 1\n0 1 0 0 0 255 0 1\n4 0 1 2 3\n");
+        assert_square(&parse_ply(&bytes, material()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_little_and_big_endian() {
+        let little = binary_square("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes, &[0, 1, 2, 3]);
+        assert_square(&parse_ply(&little, material()).unwrap());
+        let big = binary_square("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes, &[0, 1, 2, 3]);
+        assert_square(&parse_ply(&big, material()).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_vertex_indices() {
+        for face in [[0, 1, 2, 4], [0, 1, 2, -1]] {
+            let bytes = binary_square("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes, &face);
+            assert_eq!(parse_ply(&bytes, material()).err().unwrap().kind(), ErrorKind::InvalidData);
+        }
+        let mut fractional = header("ascii");
+        fractional.extend(b"0 0 0 255 0 0 0 0\n1 0 0 0 0 255 1 0\n1 1 0 0 0 255 1 1\n0 1 0 0 0 255 0 1\n4 0 1 2.5 3\n");
+        assert_eq!(parse_ply(&fractional, material()).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+}
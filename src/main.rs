@@ -1,24 +1,16 @@
-mod camera;
-mod constants;
-mod utils;
-mod vectorlib;
-mod material;
+#![allow(clippy::needless_return)]
 
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use std::rc::Rc;
 
-use chrono;
 use rand::prelude::*;
-use rand::rngs::StdRng;
-use rand::SeedableRng;
 
-use camera::*;
-use constants::*;
-use vectorlib::{color::*, hit::*, point3::*, ray::*, sphere::*, vector3::*};
-
-use crate::material::{Lambertian, Material, Metal};
+use raytrace_1::camera::*;
+use raytrace_1::constants::*;
+use raytrace_1::loaders::gltf::load_gltf;
+use raytrace_1::material::{Lambertian, Metal};
+use raytrace_1::vectorlib::{color::*, hit::*, plane::*, ray::*, sphere::*, vector3::*};
 
 
 fn main() -> std::io::Result<()> {
@@ -28,23 +20,20 @@ fn main() -> std::io::Result<()> {
     );
     let full_path = Path::new("").join(FOLDER_NAME).join(file_name);
 
-    let mut file = File::create(full_path.clone()).expect(
-        format!(
+    let mut file = File::create(full_path.clone()).unwrap_or_else(|_| {
+        panic!(
             "[ERR] Unable to create file! {}",
             full_path.to_str().unwrap_or("[ERR] Cannot unwrap")
         )
-        .as_str(),
-    );
+    });
 
-    file.write(b"P3\n").expect("[ERR] Cannot write to file!");
-    file.write(format!("{} {}\n", IMAGE_WIDTH, IMAGE_HEIGHT).as_bytes())
+    file.write_all(b"P3\n").expect("[ERR] Cannot write to file!");
+    file.write_all(format!("{} {}\n", IMAGE_WIDTH, IMAGE_HEIGHT).as_bytes())
         .expect("[ERR] Cannot write to file!");
-    file.write(b"255\n").expect("[ERR] Cannot write to file!");
+    file.write_all(b"255\n").expect("[ERR] Cannot write to file!");
 
     let mut meshes: HittableList = HittableList::new();
 
-    let mut rng: StdRng = StdRng::seed_from_u64(SEED);
-
     /* 
     let x_pos_range = &(-5.0..5.0);
     let y_pos_range = &(-0.5..1.2);
@@ -67,31 +56,45 @@ fn main() -> std::io::Result<()> {
     // Start timer
     let start: chrono::DateTime<chrono::Local> = chrono::offset::Local::now();
 
-    let matte_green = Lambertian::new(Vector3f::new(0.502, 0.502, 0.0));
-
-    let metal_yellow = Metal::new(Vector3f::new(0.98,0.96,0.56),0.25);
-    let metal_cyan = Lambertian::new(Vector3f::new(0.73,0.87,0.93));
-    let metal_magenta = Metal::new(Vector3f::new(1.0,0.64,0.97),0.05);
-
-    meshes.add(Sphere::new(Vector3f::new(0.0, -100.5, -1.0), 100.0,Box::new(matte_green)));
-
-    meshes.add(Sphere::new(Vector3f::new(-1.0, 0.0, -1.3), 0.5,Box::new(metal_yellow)));
-    meshes.add(Sphere::new(Vector3f::new(0.0, 0.0, -1.0), 0.5,Box::new(metal_cyan)));
-    meshes.add(Sphere::new(Vector3f::new(1.0, 0.0, -1.3), 0.5,Box::new(metal_magenta)));
-
-
-    let camera = Camera::new(
+    let mut camera = Camera::new(
         VIEWPORT_WIDTH,
         VIEWPORT_HEIGHT,
         FOCAL_LENGTH,
         Vector3f::zero(),
     );
 
+    if let Some(scene_file) = SCENE_FILE {
+        let mut scene = load_gltf(Path::new(scene_file), ASPECT_RATIO)?;
+        println!("Loaded {} meshes and {} cameras from {}", scene.meshes.len(), scene.cameras.len(), scene_file);
+
+        if !scene.cameras.is_empty() {
+            camera = scene.cameras.swap_remove(0);
+        }
+        for mesh in scene.meshes {
+            meshes.add(mesh);
+        }
+    } else {
+        let matte_green = Lambertian::new(Vector3f::new(0.502, 0.502, 0.0));
+
+        let metal_yellow = Metal::new(Vector3f::new(0.98,0.96,0.56),0.25);
+        let metal_cyan = Lambertian::new(Vector3f::new(0.73,0.87,0.93));
+        let metal_magenta = Metal::new(Vector3f::new(1.0,0.64,0.97),0.05);
+
+        meshes.add(Plane::new(Vector3f::new(0.0, -0.5, 0.0), Vector3f::unit_y(), Box::new(matte_green)));
+
+        meshes.add(Sphere::new(Vector3f::new(-1.0, 0.0, -1.3), 0.5,Box::new(metal_yellow)));
+        meshes.add(Sphere::new(Vector3f::new(0.0, 0.0, -1.0), 0.5,Box::new(metal_cyan)));
+        meshes.add(Sphere::new(Vector3f::new(1.0, 0.0, -1.3), 0.5,Box::new(metal_magenta)));
+    }
+
+    meshes.build_bvh();
+    let camera = camera.with_shutter(SHUTTER_OPEN, SHUTTER_CLOSE);
+
     //iterate throught width and height of image
     for j in (0..IMAGE_HEIGHT).rev() {
         println!(
             "...{:.2}%",
-            ((IMAGE_HEIGHT - j) as f32 / IMAGE_HEIGHT as f32) * 100 as f32
+            ((IMAGE_HEIGHT - j) as f32 / IMAGE_HEIGHT as f32) * 100.0
         );
         for i in 0..IMAGE_WIDTH {
 
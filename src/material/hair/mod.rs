@@ -0,0 +1,134 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::{albedo_at, BsdfSample, Material};
+use crate::texture::{SolidColor, Texture};
+use crate::vectorlib::{hit::HitData, vector3::*};
+
+// Reflectance of a hair cuticle (index of refraction about 1.55) at normal incidence
+const CUTICLE_REFLECTANCE : f32 = 0.046;
+
+// Simplified Marschner hair. Each bounce picks one of three lobes: a white highlight reflected off
+// the cuticle (R), light passing straight through the fibre (TT) and light reflected once inside it
+// (TRT), the last two tinted by the fibre colour each time they cross it. All lobes leave on a cone
+// around the fibre, tilted by the cuticle scales and blurred by roughness
+pub struct Hair {
+    // Can vary along the strand through u, such as darker roots
+    color: Box<dyn Texture>,
+    // Spread of the lobes along the fibre, in radians
+    roughness: f32,
+    // Tilt of the cuticle scales in radians, shifts the highlights towards the root or the tip
+    cuticle_tilt: f32,
+}
+
+impl Hair {
+    pub fn new(color: Vector3f, roughness: f32, cuticle_tilt: f32) -> Hair {
+        return Hair::textured(Box::new(SolidColor::new(color)), roughness, cuticle_tilt);
+    }
+
+    pub fn textured(color: Box<dyn Texture>, roughness: f32, cuticle_tilt: f32) -> Hair {
+        return Hair { color, roughness, cuticle_tilt };
+    }
+
+    // Typical human hair, 10 degrees of roughness and a 3 degree cuticle tilt
+    pub fn with_color(color: Vector3f) -> Hair {
+        return Hair::new(color, 10.0_f32.to_radians(), 3.0_f32.to_radians());
+    }
+
+    // Rotates direction towards the tip by tilt plus a random blur, and swings it around the fibre a little
+    fn perturb(&self, direction: &Vector3f, tangent: &Vector3f, tilt: f32) -> Vector3f {
+        let direction = direction.unit_vector();
+        let sin_theta = direction.dot(tangent).clamp(-1.0, 1.0);
+        let around = direction - sin_theta * *tangent;
+        if around.near_zero() {
+            return direction;
+        }
+        let around = around.unit_vector();
+
+        // Sum of two uniforms, a cheap bell shaped blur
+        let blur = (random::<f32>() + random::<f32>() - 1.0) * self.roughness;
+        let theta = sin_theta.asin() + tilt + blur;
+        let phi = (random::<f32>() - 0.5) * self.roughness * PI;
+        let around = phi.cos() * around + phi.sin() * tangent.cross(&around);
+        return theta.sin() * *tangent + theta.cos() * around;
+    }
+}
+
+impl Material for Hair {
+    // Only sampling is supported, the lobes are worked out around the fibre in world space
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        // Curves run u along the fibre
+        let frame = hit_data.shading_frame();
+        let tangent = hit_data.dpdu.unit_vector();
+        let direction = -frame.vector_to_world(outgoing);
+        let reflected = direction.get_reflected(&hit_data.shading_normal);
+        let color = albedo_at(self.color.as_ref(), hit_data);
+
+        // Schlick's approximation for how much the cuticle reflects at this angle
+        let cos_theta = (-direction.dot(&hit_data.shading_normal)).clamp(0.0, 1.0);
+        let fresnel = CUTICLE_REFLECTANCE + (1.0 - CUTICLE_REFLECTANCE) * (1.0 - cos_theta).powi(5);
+
+        let choice = random::<f32>();
+        let (attenuation, scattered) = if choice < fresnel {
+            (Vector3f::one(), self.perturb(&reflected, &tangent, -2.0 * self.cuticle_tilt))
+        } else if choice < fresnel + (1.0 - fresnel) / 2.0 {
+            (color, self.perturb(&direction, &tangent, self.cuticle_tilt))
+        } else {
+            (color.multiply_element_wise(&color), self.perturb(&reflected, &tangent, 4.0 * self.cuticle_tilt))
+        };
+        return Some(BsdfSample::delta(frame.vector_to_local(&scattered), attenuation));
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.color.as_ref(), hit_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_hair_reflects_or_passes_straight_through() {
+        // Without roughness or tilt the R and TRT lobes mirror and TT carries straight on, tinted once per crossing
+        let hair = Hair::new(Vector3f::uniform(0.5), 0.0, 0.0);
+        let normal = Vector3f::new(0.0, 0.0, 1.0);
+        let direction = Vector3f::new(0.6, 0.0, -0.8);
+        let hit = HitData::new(1.0, Vector3f::zero(), normal, &direction, &normal, &hair)
+            .with_tangents(Vector3f::new(1.0, 0.0, 0.0), Vector3f::new(0.0, 1.0, 0.0));
+        let outgoing = Vector3f::new(-0.6, 0.0, 0.8);
+        let close = |a: Vector3f, b: Vector3f| (a - b).magnitude() < 1e-4;
+
+        let mut lobes_seen = [false; 3];
+        for _ in 0..500 {
+            let sample = hair.sample(&outgoing, &hit).unwrap();
+            if (sample.weight.x - 1.0).abs() < 1e-5 {
+                assert!(close(sample.incoming, Vector3f::new(0.6, 0.0, 0.8)));
+                lobes_seen[0] = true;
+            } else if (sample.weight.x - 0.5).abs() < 1e-5 {
+                assert!(close(sample.incoming, direction));
+                lobes_seen[1] = true;
+            } else {
+                assert!((sample.weight.x - 0.25).abs() < 1e-5);
+                assert!(close(sample.incoming, Vector3f::new(0.6, 0.0, 0.8)));
+                lobes_seen[2] = true;
+            }
+        }
+        assert!(lobes_seen.iter().all(|seen| *seen));
+    }
+
+    #[test]
+    fn rough_hair_keeps_unit_directions_and_bounded_weights() {
+        let hair = Hair::with_color(Vector3f::new(0.8, 0.5, 0.2));
+        let normal = Vector3f::new(0.0, 0.0, 1.0);
+        let direction = Vector3f::new(0.0, 0.6, -0.8);
+        let hit = HitData::new(1.0, Vector3f::zero(), normal, &direction, &normal, &hair)
+            .with_tangents(Vector3f::new(1.0, 0.0, 0.0), Vector3f::new(0.0, 1.0, 0.0));
+        for _ in 0..500 {
+            let sample = hair.sample(&-direction, &hit).unwrap();
+            assert!((sample.incoming.magnitude() - 1.0).abs() < 1e-4);
+            assert!(sample.weight.x <= 1.0 && sample.weight.z >= 0.0);
+        }
+    }
+}
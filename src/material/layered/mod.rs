@@ -0,0 +1,173 @@
+use rand::random;
+
+use crate::material::microfacet::{eval_reflection, pdf_reflection, sample_reflection, TrowbridgeReitz};
+use crate::material::{fresnel_dielectric, mixture_sample, BsdfSample, Material};
+use crate::texture::{constant, Texture};
+use crate::vectorlib::{hit::HitData, ray::Ray, vector3::*};
+
+// Thin clear dielectric coat over any other material, such as car paint or varnished wood.
+// Light either reflects off the coat, picked with its Fresnel reflectance, or passes through it to the
+// base and back out again, losing what the coat reflects back in on the way out and what its tint
+// absorbs along both crossings. Light a transmissive base lets through only crosses the coat on the
+// way in. Light reflected back and forth inside the coat is dropped
+pub struct Coated {
+    base : Box<dyn Material>,
+    // Index of refraction of the coat
+    ior : f32,
+    // Perceptual roughness of the coat, usually low
+    roughness : Box<dyn Texture>,
+    // Colour white light takes on crossing the coat once straight through, white for a clear coat
+    tint : Vector3f,
+}
+
+impl Coated{
+    pub fn new(base : Box<dyn Material>, ior : f32, roughness : f32) -> Coated{
+        return Coated{base, ior, roughness : constant(roughness), tint : Vector3f::one()};
+    }
+
+    // Glossy polyurethane like clearcoat
+    pub fn clearcoat(base : Box<dyn Material>) -> Coated{
+        return Coated::new(base, 1.5, 0.05);
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Coated{
+        self.roughness = roughness;
+        return self;
+    }
+
+    pub fn with_tint(mut self, tint : Vector3f) -> Coated{
+        self.tint = tint;
+        return self;
+    }
+
+    fn distribution(&self, hit_data: &HitData) -> TrowbridgeReitz{
+        return TrowbridgeReitz::from_roughness(self.roughness.scalar_at(hit_data));
+    }
+
+    fn reflectance(&self, cos_theta : f32) -> f32{
+        return fresnel_dielectric(cos_theta.abs(), 1.0 / self.ior);
+    }
+
+    // What makes it through the coat at a direction with the given cosine, in both Fresnel and tint.
+    // Light travels further through the coat the more slanted it is once refracted
+    fn transmittance(&self, cos_theta : f32) -> Vector3f{
+        let sin2_inside = (1.0 - cos_theta * cos_theta) / (self.ior * self.ior);
+        let cos_inside = (1.0 - sin2_inside).max(1e-4).sqrt();
+        let exponent = 1.0 / cos_inside;
+        let tint = Vector3f::new(self.tint.x.powf(exponent), self.tint.y.powf(exponent), self.tint.z.powf(exponent));
+        return (1.0 - self.reflectance(cos_theta)) * tint;
+    }
+
+    // Light reaching the base has crossed the coat along outgoing. The Fresnel part of that was paid for by
+    // picking the base, leaving the tint. Light reflected by the base also has to leave through the coat along incoming
+    fn base_weight(&self, outgoing : &Vector3f, incoming : &Vector3f) -> Vector3f{
+        let way_in_tint = self.transmittance(outgoing.z) / (1.0 - self.reflectance(outgoing.z)).max(1e-6);
+        if incoming.z <= 0.0 {
+            return way_in_tint;
+        }
+        return way_in_tint.multiply_element_wise(&self.transmittance(incoming.z));
+    }
+}
+
+impl Material for Coated{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        // Seen from underneath the coat plays no part
+        if outgoing.z <= 0.0 {
+            return self.base.sample(outgoing, hit_data);
+        }
+
+        let coat_probability = self.reflectance(outgoing.z);
+        if random::<f32>() < coat_probability {
+            // The coat is picked with the reflectance towards the viewer, the weight corrects that to the sampled microfacet
+            let sample = sample_reflection(outgoing, &self.distribution(hit_data), |cos| Vector3f::uniform(self.reflectance(cos) / coat_probability))?;
+            return mixture_sample(self, outgoing, hit_data, sample);
+        }
+
+        let mut sample = self.base.sample(outgoing, hit_data)?;
+        sample.weight = sample.weight.multiply_element_wise(&self.base_weight(outgoing, &sample.incoming));
+        return mixture_sample(self, outgoing, hit_data, sample);
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        let base = self.base.eval(outgoing, incoming, hit_data);
+        if outgoing.z <= 0.0 {
+            return base;
+        }
+        if incoming.z <= 0.0 {
+            return self.transmittance(outgoing.z).multiply_element_wise(&base);
+        }
+        let coat = eval_reflection(outgoing, incoming, &self.distribution(hit_data), |cos| Vector3f::uniform(self.reflectance(cos)));
+        let through = self.transmittance(outgoing.z).multiply_element_wise(&self.transmittance(incoming.z));
+        return coat + through.multiply_element_wise(&base);
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        let base = self.base.pdf(outgoing, incoming, hit_data);
+        if outgoing.z <= 0.0 {
+            return base;
+        }
+        let coat_probability = self.reflectance(outgoing.z);
+        let coat = pdf_reflection(outgoing, incoming, &self.distribution(hit_data));
+        return coat_probability * coat + (1.0 - coat_probability) * base;
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return self.base.get_albedo(hit_data);
+    }
+
+    // Glowing bases shine through the coat
+    fn emitted(&self, ray_in: &Ray, hit_data: &HitData) -> Vector3f {
+        let cos_theta = -ray_in.direction().unit_vector().dot(&hit_data.shading_normal);
+        return self.transmittance(cos_theta).multiply_element_wise(&self.base.emitted(ray_in, hit_data));
+    }
+}
+
+// Blend of two materials, amount 0 is all first and 1 all second. Driven by a texture it paints
+// one material over another, such as rust patches on metal or moss on stone
+pub struct Mix {
+    first : Box<dyn Material>,
+    second : Box<dyn Material>,
+    amount : Box<dyn Texture>,
+}
+
+impl Mix{
+    pub fn new(first : Box<dyn Material>, second : Box<dyn Material>, amount : f32) -> Mix{
+        return Mix::textured(first, second, constant(amount));
+    }
+
+    pub fn textured(first : Box<dyn Material>, second : Box<dyn Material>, amount : Box<dyn Texture>) -> Mix{
+        return Mix{first, second, amount};
+    }
+
+    fn amount_at(&self, hit_data: &HitData) -> f32{
+        return self.amount.scalar_at(hit_data).clamp(0.0, 1.0);
+    }
+}
+
+impl Material for Mix{
+    // One material is picked with its share of the blend, delta samples keep its weight
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let material = if random::<f32>() < self.amount_at(hit_data) { &self.second } else { &self.first };
+        let sample = material.sample(outgoing, hit_data)?;
+        return mixture_sample(self, outgoing, hit_data, sample);
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        let first = self.first.eval(outgoing, incoming, hit_data);
+        let second = self.second.eval(outgoing, incoming, hit_data);
+        return Vector3f::lerp(&first, &second, self.amount_at(hit_data));
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        let amount = self.amount_at(hit_data);
+        return (1.0 - amount) * self.first.pdf(outgoing, incoming, hit_data) + amount * self.second.pdf(outgoing, incoming, hit_data);
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return Vector3f::lerp(&self.first.get_albedo(hit_data), &self.second.get_albedo(hit_data), self.amount_at(hit_data));
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_data: &HitData) -> Vector3f {
+        return Vector3f::lerp(&self.first.emitted(ray_in, hit_data), &self.second.emitted(ray_in, hit_data), self.amount_at(hit_data));
+    }
+}
@@ -0,0 +1,414 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::{fresnel_dielectric, BsdfSample};
+use crate::vectorlib::vector3::*;
+
+// Below this alpha the surface is treated as a perfect mirror, the distribution is too sharp to sample reliably
+const SMOOTH_ALPHA : f32 = 1e-3;
+
+// GGX / Trowbridge-Reitz distribution of microfacet normals. Everything works in a local shading
+// frame with z along the shading normal and x along dpdu, alpha_x and alpha_y are the widths along
+// those two tangents so brushed surfaces can stretch the highlight
+#[derive(Copy, Clone)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> TrowbridgeReitz {
+        return TrowbridgeReitz { alpha_x, alpha_y };
+    }
+
+    // From the perceptual roughness artists work in, squared so that the highlight widens evenly over [0,1]
+    pub fn from_roughness(roughness: f32) -> TrowbridgeReitz {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        return TrowbridgeReitz::new(alpha, alpha);
+    }
+
+    // anisotropy in [-1,1] stretches the highlight along dpdu when positive and along dpdv when negative,
+    // like the scratches of brushed metal, with the aspect ratio Disney's principled BRDF uses
+    pub fn anisotropic(roughness: f32, anisotropy: f32) -> TrowbridgeReitz {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.abs().min(1.0)).sqrt();
+        let (along, across) = (alpha / aspect, alpha * aspect);
+        if anisotropy >= 0.0 {
+            return TrowbridgeReitz::new(along, across);
+        }
+        return TrowbridgeReitz::new(across, along);
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        return self.alpha_x.max(self.alpha_y) < SMOOTH_ALPHA;
+    }
+
+    // Density of microfacets facing along normal
+    pub fn d(&self, normal: &Vector3f) -> f32 {
+        let cos2_theta = normal.z * normal.z;
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+        let x = normal.x / self.alpha_x;
+        let y = normal.y / self.alpha_y;
+        let e = (x * x + y * y) / cos2_theta;
+        return 1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e) * (1.0 + e));
+    }
+
+    // Smith's auxiliary function, the amount of microfacet area hidden per unit of visible area
+    pub fn lambda(&self, direction: &Vector3f) -> f32 {
+        let cos2_theta = direction.z * direction.z;
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+        let x = direction.x * self.alpha_x;
+        let y = direction.y * self.alpha_y;
+        let alpha2_tan2_theta = (x * x + y * y) / cos2_theta;
+        return ((1.0 + alpha2_tan2_theta).sqrt() - 1.0) / 2.0;
+    }
+
+    // Fraction of microfacets visible from direction
+    pub fn g1(&self, direction: &Vector3f) -> f32 {
+        return 1.0 / (1.0 + self.lambda(direction));
+    }
+
+    // Height correlated masking and shadowing for the pair of directions
+    pub fn g(&self, outgoing: &Vector3f, incoming: &Vector3f) -> f32 {
+        return 1.0 / (1.0 + self.lambda(outgoing) + self.lambda(incoming));
+    }
+
+    // Distribution of the normals actually seen from direction, integrates to one over the hemisphere.
+    // Microfacets facing away from direction can't be seen and have none
+    pub fn visible_normal_density(&self, direction: &Vector3f, normal: &Vector3f) -> f32 {
+        if direction.z == 0.0 {
+            return 0.0;
+        }
+        let cos_normal = (direction.dot(normal) * direction.z.signum()).max(0.0);
+        return self.g1(direction) / direction.z.abs() * self.d(normal) * cos_normal;
+    }
+
+    // Heitz's sampling of visible normals: stretch the view into the space where the distribution is a
+    // hemisphere, pick a point on the projected disk the hemisphere shows and map it back.
+    // u1 and u2 are uniform in [0,1)
+    pub fn sample_visible_normal(&self, direction: &Vector3f, u1: f32, u2: f32) -> Vector3f {
+        let mut stretched = Vector3f::new(self.alpha_x * direction.x, self.alpha_y * direction.y, direction.z).unit_vector();
+        if stretched.z < 0.0 {
+            stretched = -stretched;
+        }
+        let t1 = if stretched.z < 0.99999 { Vector3f::unit_z().cross(&stretched).unit_vector() } else { Vector3f::unit_x() };
+        let t2 = stretched.cross(&t1);
+
+        // Uniform point on the disk, the half hidden behind the hemisphere is squashed towards the visible edge
+        let radius = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let px = radius * phi.cos();
+        let py = radius * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + stretched.z) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let normal = px * t1 + py * t2 + pz * stretched;
+        return Vector3f::new(self.alpha_x * normal.x, self.alpha_y * normal.y, normal.z.max(1e-6)).unit_vector();
+    }
+}
+
+// Mirror direction of outgoing about normal, both pointing away from the surface
+pub fn reflect_local(outgoing: &Vector3f, normal: &Vector3f) -> Vector3f {
+    return 2.0 * outgoing.dot(normal) * *normal - *outgoing;
+}
+
+// GGX reflection off a conductor like surface, the pieces shared by every material built on it.
+// fresnel gives the reflectance for the cosine between the outgoing direction and the microfacet normal
+pub fn sample_reflection(outgoing: &Vector3f, distribution: &TrowbridgeReitz, fresnel: impl Fn(f32) -> Vector3f) -> Option<BsdfSample> {
+    if outgoing.z <= 0.0 {
+        return None;
+    }
+    if distribution.is_smooth() {
+        let incoming = Vector3f::new(-outgoing.x, -outgoing.y, outgoing.z);
+        return Some(BsdfSample::delta(incoming, fresnel(outgoing.z)));
+    }
+
+    let microfacet_normal = distribution.sample_visible_normal(outgoing, random::<f32>(), random::<f32>());
+    let incoming = reflect_local(outgoing, &microfacet_normal);
+    // Reflections that end up below the surface are lost
+    if incoming.z <= 0.0 {
+        return None;
+    }
+    // With visible normal sampling the BRDF times cosine over the pdf comes down to the Fresnel term
+    // and the share of the reflected light that isn't shadowed on the way out
+    let cos_microfacet = outgoing.dot(&microfacet_normal);
+    let shadowing = distribution.g(outgoing, &incoming) / distribution.g1(outgoing);
+    let pdf = distribution.visible_normal_density(outgoing, &microfacet_normal) / (4.0 * cos_microfacet);
+    return Some(BsdfSample::new(incoming, shadowing * fresnel(cos_microfacet), pdf));
+}
+
+pub fn eval_reflection(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, fresnel: impl Fn(f32) -> Vector3f) -> Vector3f {
+    if distribution.is_smooth() || outgoing.z <= 0.0 || incoming.z <= 0.0 {
+        return Vector3f::zero();
+    }
+    let microfacet_normal = (*outgoing + *incoming).unit_vector();
+    let value = distribution.d(&microfacet_normal) * distribution.g(outgoing, incoming) / (4.0 * outgoing.z * incoming.z);
+    return value * fresnel(outgoing.dot(&microfacet_normal));
+}
+
+pub fn pdf_reflection(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz) -> f32 {
+    if distribution.is_smooth() || outgoing.z <= 0.0 || incoming.z <= 0.0 {
+        return 0.0;
+    }
+    let microfacet_normal = (*outgoing + *incoming).unit_vector();
+    return distribution.visible_normal_density(outgoing, &microfacet_normal) / (4.0 * outgoing.dot(&microfacet_normal));
+}
+
+// Rough boundary between two dielectrics after Walter et al., reflecting or refracting with the Fresnel
+// reflectance of each microfacet. relative_ior is eta_transmitted / eta_incident for light crossing from the
+// side outgoing is on, which the shading normal always faces. Smooth boundaries become a delta mirror or refraction
+pub fn sample_dielectric(outgoing: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> Option<BsdfSample> {
+    if outgoing.z <= 0.0 {
+        return None;
+    }
+    if distribution.is_smooth() {
+        let reflectance = fresnel_dielectric(outgoing.z, 1.0 / relative_ior);
+        let incoming = match (-*outgoing).get_refracted(&Vector3f::unit_z(), 1.0 / relative_ior) {
+            Some(refracted) if random::<f32>() >= reflectance => refracted,
+            _ => Vector3f::new(-outgoing.x, -outgoing.y, outgoing.z),
+        };
+        return Some(BsdfSample::delta(incoming, Vector3f::one()));
+    }
+
+    let microfacet_normal = distribution.sample_visible_normal(outgoing, random::<f32>(), random::<f32>());
+    let cos_microfacet = outgoing.dot(&microfacet_normal);
+    let reflectance = fresnel_dielectric(cos_microfacet, 1.0 / relative_ior);
+    let incoming = match (-*outgoing).get_refracted(&microfacet_normal, 1.0 / relative_ior) {
+        Some(refracted) if random::<f32>() >= reflectance => {
+            // Refractions that stay on the outgoing side are lost
+            if refracted.z >= 0.0 {
+                return None;
+            }
+            refracted.unit_vector()
+        }
+        _ => {
+            let reflected = reflect_local(outgoing, &microfacet_normal);
+            if reflected.z <= 0.0 {
+                return None;
+            }
+            reflected
+        }
+    };
+
+    // Choosing reflection or refraction with the reflectance cancels the Fresnel term from the weight
+    let shadowing = distribution.g(outgoing, &incoming) / distribution.g1(outgoing);
+    let pdf = pdf_dielectric(outgoing, &incoming, distribution, relative_ior);
+    return Some(BsdfSample::new(incoming, Vector3f::uniform(shadowing), pdf));
+}
+
+// Microfacet normal that takes outgoing to incoming and the relative_ior to use for it,
+// None for pairs no microfacet can link
+fn dielectric_half_vector(outgoing: &Vector3f, incoming: &Vector3f, relative_ior: f32) -> Option<(Vector3f, f32)> {
+    let reflects = outgoing.z * incoming.z > 0.0;
+    let relative_ior = if reflects { 1.0 } else { relative_ior };
+    let half = relative_ior * *incoming + *outgoing;
+    if outgoing.z == 0.0 || incoming.z == 0.0 || half.near_zero() {
+        return None;
+    }
+    let half = half.unit_vector();
+    let half = if half.z < 0.0 { -half } else { half };
+
+    // Both directions have to be on the visible side of the microfacet
+    if half.dot(incoming) * incoming.z < 0.0 || half.dot(outgoing) * outgoing.z < 0.0 {
+        return None;
+    }
+    return Some((half, relative_ior));
+}
+
+pub fn eval_dielectric(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> Vector3f {
+    if distribution.is_smooth() || outgoing.z <= 0.0 {
+        return Vector3f::zero();
+    }
+    let (half, half_ior) = match dielectric_half_vector(outgoing, incoming, relative_ior) {
+        Some(half) => half,
+        None => return Vector3f::zero(),
+    };
+    let reflectance = fresnel_dielectric(outgoing.dot(&half), 1.0 / relative_ior);
+    if incoming.z > 0.0 {
+        let masking = distribution.d(&half) * distribution.g(outgoing, incoming);
+        return Vector3f::uniform(masking * reflectance / (4.0 * outgoing.z * incoming.z).abs());
+    }
+    return (1.0 - reflectance) * transmission_value(outgoing, incoming, distribution, &half, half_ior);
+}
+
+pub fn pdf_dielectric(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> f32 {
+    if distribution.is_smooth() || outgoing.z <= 0.0 {
+        return 0.0;
+    }
+    let (half, half_ior) = match dielectric_half_vector(outgoing, incoming, relative_ior) {
+        Some(half) => half,
+        None => return 0.0,
+    };
+    let reflectance = fresnel_dielectric(outgoing.dot(&half), 1.0 / relative_ior);
+    if incoming.z > 0.0 {
+        let density = distribution.visible_normal_density(outgoing, &half);
+        return density / (4.0 * outgoing.dot(&half).abs()) * reflectance;
+    }
+    return (1.0 - reflectance) * transmission_density(outgoing, incoming, distribution, &half, half_ior);
+}
+
+// The refraction half of the rough dielectric without its Fresnel term, for materials that pick between
+// reflecting and refracting some other way. Refracting is then the only option, so the weight of a
+// sample is just the shadowing. relative_ior is eta_transmitted / eta_incident as for sample_dielectric
+pub fn sample_transmission(outgoing: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> Option<BsdfSample> {
+    if outgoing.z <= 0.0 {
+        return None;
+    }
+    if distribution.is_smooth() {
+        let incoming = (-*outgoing).get_refracted(&Vector3f::unit_z(), 1.0 / relative_ior)?;
+        return Some(BsdfSample::delta(incoming, Vector3f::one()));
+    }
+
+    let microfacet_normal = distribution.sample_visible_normal(outgoing, random::<f32>(), random::<f32>());
+    let incoming = (-*outgoing).get_refracted(&microfacet_normal, 1.0 / relative_ior)?.unit_vector();
+    // Refractions that stay on the outgoing side are lost
+    if incoming.z >= 0.0 {
+        return None;
+    }
+    let shadowing = distribution.g(outgoing, &incoming) / distribution.g1(outgoing);
+    let pdf = pdf_transmission(outgoing, &incoming, distribution, relative_ior);
+    return Some(BsdfSample::new(incoming, Vector3f::uniform(shadowing), pdf));
+}
+
+pub fn eval_transmission(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> Vector3f {
+    if distribution.is_smooth() || outgoing.z <= 0.0 || incoming.z >= 0.0 {
+        return Vector3f::zero();
+    }
+    return match dielectric_half_vector(outgoing, incoming, relative_ior) {
+        Some((half, half_ior)) => transmission_value(outgoing, incoming, distribution, &half, half_ior),
+        None => Vector3f::zero(),
+    };
+}
+
+pub fn pdf_transmission(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, relative_ior: f32) -> f32 {
+    if distribution.is_smooth() || outgoing.z <= 0.0 || incoming.z >= 0.0 {
+        return 0.0;
+    }
+    return match dielectric_half_vector(outgoing, incoming, relative_ior) {
+        Some((half, half_ior)) => transmission_density(outgoing, incoming, distribution, &half, half_ior),
+        None => 0.0,
+    };
+}
+
+// Walter's refraction BTDF through the microfacet half, before the Fresnel transmittance
+fn transmission_value(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, half: &Vector3f, half_ior: f32) -> Vector3f {
+    let masking = distribution.d(half) * distribution.g(outgoing, incoming);
+    let denominator = incoming.dot(half) + outgoing.dot(half) / half_ior;
+    let denominator = denominator * denominator * incoming.z * outgoing.z;
+    return Vector3f::uniform(masking * (incoming.dot(half) * outgoing.dot(half) / denominator).abs());
+}
+
+// Density of refracting through half with visible normal sampling, a change of variables from the microfacet normal
+fn transmission_density(outgoing: &Vector3f, incoming: &Vector3f, distribution: &TrowbridgeReitz, half: &Vector3f, half_ior: f32) -> f32 {
+    let density = distribution.visible_normal_density(outgoing, half);
+    let denominator = incoming.dot(half) + outgoing.dot(half) / half_ior;
+    return density * incoming.dot(half).abs() / (denominator * denominator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Midpoint rule over the hemisphere of f(direction) dω
+    fn integrate_hemisphere(f: impl Fn(&Vector3f) -> f32) -> f32 {
+        let (theta_steps, phi_steps) = (1000, 200);
+        let mut total = 0.0;
+        for i in 0..theta_steps {
+            let theta = (i as f32 + 0.5) / theta_steps as f32 * PI / 2.0;
+            for j in 0..phi_steps {
+                let phi = (j as f32 + 0.5) / phi_steps as f32 * 2.0 * PI;
+                let direction = Vector3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                total += f(&direction) * theta.sin();
+            }
+        }
+        return total * (PI / 2.0 / theta_steps as f32) * (2.0 * PI / phi_steps as f32);
+    }
+
+    #[test]
+    fn distribution_projects_to_unit_area() {
+        for distribution in [TrowbridgeReitz::from_roughness(0.6), TrowbridgeReitz::anisotropic(0.7, 0.8)] {
+            let projected = integrate_hemisphere(|normal| distribution.d(normal) * normal.z);
+            assert!((projected - 1.0).abs() < 0.01);
+
+            let outgoing = Vector3f::new(0.6, 0.0, 0.8);
+            let visible = integrate_hemisphere(|normal| distribution.visible_normal_density(&outgoing, normal));
+            assert!((visible - 1.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn anisotropy_stretches_along_the_tangents() {
+        let along_u = TrowbridgeReitz::anisotropic(0.5, 0.8);
+        let along_v = TrowbridgeReitz::anisotropic(0.5, -0.8);
+        assert!(along_u.alpha_x > along_u.alpha_y);
+        assert_eq!(along_u.alpha_x, along_v.alpha_y);
+        assert!(TrowbridgeReitz::from_roughness(0.0).is_smooth());
+        assert!(!TrowbridgeReitz::from_roughness(0.1).is_smooth());
+    }
+
+    #[test]
+    fn reflection_samples_match_eval_and_pdf() {
+        let white = |_: f32| Vector3f::one();
+        let outgoing = Vector3f::new(0.3, -0.2, 0.5f32.sqrt()).unit_vector();
+        for distribution in [TrowbridgeReitz::from_roughness(0.4), TrowbridgeReitz::anisotropic(0.6, -0.5)] {
+            for _ in 0..1000 {
+                let sample = match sample_reflection(&outgoing, &distribution, white) {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                assert!(!sample.is_delta);
+                let pdf = pdf_reflection(&outgoing, &sample.incoming, &distribution);
+                assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                let eval = eval_reflection(&outgoing, &sample.incoming, &distribution, white);
+                assert!((eval.x * sample.incoming.z / pdf - sample.weight.x).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn dielectric_pdf_integrates_to_the_samples_kept() {
+        let below = |f: &dyn Fn(&Vector3f) -> f32| integrate_hemisphere(|direction| f(&Vector3f::new(direction.x, direction.y, -direction.z)));
+        let outgoing = Vector3f::new(0.6, 0.0, 0.8);
+        for relative_ior in [1.5, 1.0 / 1.5] {
+            let distribution = TrowbridgeReitz::from_roughness(0.6);
+            let reflected = integrate_hemisphere(|incoming| pdf_dielectric(&outgoing, incoming, &distribution, relative_ior));
+            let refracted = below(&|incoming| pdf_dielectric(&outgoing, incoming, &distribution, relative_ior));
+            let transmitted = below(&|incoming| pdf_transmission(&outgoing, incoming, &distribution, relative_ior));
+
+            let samples = 50000;
+            let (mut sampled_reflected, mut sampled_refracted, mut sampled_transmitted) = (0, 0, 0);
+            for _ in 0..samples {
+                match sample_dielectric(&outgoing, &distribution, relative_ior) {
+                    Some(sample) if sample.incoming.z > 0.0 => sampled_reflected += 1,
+                    Some(_) => sampled_refracted += 1,
+                    None => {}
+                }
+                if sample_transmission(&outgoing, &distribution, relative_ior).is_some() {
+                    sampled_transmitted += 1;
+                }
+            }
+            let fraction = |count: i32| count as f32 / samples as f32;
+            assert!((reflected - fraction(sampled_reflected)).abs() < 0.02);
+            assert!((refracted - fraction(sampled_refracted)).abs() < 0.02);
+            assert!((transmitted - fraction(sampled_transmitted)).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn smooth_transmission_follows_snell() {
+        let outgoing = Vector3f::new(0.6, 0.0, 0.8);
+        let sample = sample_transmission(&outgoing, &TrowbridgeReitz::from_roughness(0.0), 1.5).unwrap();
+        assert!(sample.is_delta);
+        assert!((sample.incoming.x + 0.6 / 1.5).abs() < 1e-5 && sample.incoming.z < 0.0);
+        // Past the critical angle nothing gets through
+        let steep = Vector3f::new(0.8, 0.0, 0.6);
+        assert!(sample_transmission(&steep, &TrowbridgeReitz::from_roughness(0.0), 1.0 / 1.5).is_none());
+    }
+}
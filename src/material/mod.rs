@@ -1,12 +1,103 @@
-use std::cell::Ref;
+pub mod hair;
+pub mod layered;
+pub mod microfacet;
+pub mod oren_nayar;
+pub mod phase;
+pub mod principled;
+pub mod sheen;
+pub mod subsurface;
+pub mod thin_film;
 
-use rand::random;
+use std::f32::consts::PI;
 
-use crate::vectorlib::{color::Color, hit::HitData, ray::{Ray, self}, vector3::*, sphere::*};
+use crate::material::microfacet::{eval_dielectric, eval_reflection, pdf_dielectric, pdf_reflection, sample_dielectric, sample_reflection, TrowbridgeReitz};
+use crate::texture::{constant, SolidColor, Texture};
+use crate::vectorlib::{hit::{HitData, HittableList}, ray::Ray, vector3::*};
 
+// Incoming direction picked by a material, in the local shading frame
+pub struct BsdfSample {
+    pub incoming: Vector3f,
+    // BSDF times cosine over pdf, what the light arriving along incoming is multiplied by
+    pub weight: Vector3f,
+    // Density incoming was picked with per unit solid angle, 1 for delta samples
+    pub pdf: f32,
+    // Picked from a lobe that eval and pdf can't reproduce: a perfect mirror or refraction, or a material
+    // that only supports sampling. Light sampling has to leave these to the sampled ray
+    pub is_delta: bool,
+}
+
+impl BsdfSample {
+    pub fn new(incoming: Vector3f, weight: Vector3f, pdf: f32) -> BsdfSample {
+        return BsdfSample { incoming, weight, pdf, is_delta: false };
+    }
+
+    pub fn delta(incoming: Vector3f, weight: Vector3f) -> BsdfSample {
+        return BsdfSample { incoming, weight, pdf: 1.0, is_delta: true };
+    }
+}
+
+// A sample picked from one lobe of a material made of several, given the value and density of the whole
+// material so it agrees with eval and pdf. Delta samples can't be evaluated and are left as they are
+pub(crate) fn mixture_sample(material : &dyn Material, outgoing : &Vector3f, hit_data : &HitData, sample : BsdfSample) -> Option<BsdfSample>{
+    if sample.is_delta {
+        return Some(sample);
+    }
+    let pdf = material.pdf(outgoing, &sample.incoming, hit_data);
+    if pdf <= 0.0 {
+        return None;
+    }
+    let weight = material.eval(outgoing, &sample.incoming, hit_data) * sample.incoming.z.abs() / pdf;
+    return Some(BsdfSample::new(sample.incoming, weight, pdf));
+}
+
+// How light scatters at a hit. Everything but scatter works in the local shading frame of the hit, with
+// z along the shading normal and x along dpdu. outgoing points back along the arriving ray and incoming
+// is where light is gathered from, both unit length and pointing away from the surface
 pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_data: &HitData) -> (&Vector3f,Ray);
-    fn get_albedo(&self) -> &Vector3f;
+    // Picks an incoming direction for light leaving along outgoing, None when the ray is absorbed
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample>;
+
+    // BSDF value for a pair of directions, without the cosine. Delta lobes can't be hit this way and give black
+    fn eval(&self, _outgoing: &Vector3f, _incoming: &Vector3f, _hit_data: &HitData) -> Vector3f {
+        return Vector3f::zero();
+    }
+
+    // Density sample picks incoming with for this outgoing, 0 for delta lobes
+    fn pdf(&self, _outgoing: &Vector3f, _incoming: &Vector3f, _hit_data: &HitData) -> f32 {
+        return 0.0;
+    }
+
+    // Surface colour at the hit
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f;
+
+    // Radiance given off at the hit towards where ray_in came from, black for anything that isn't a light
+    fn emitted(&self, _ray_in: &Ray, _hit_data: &HitData) -> Vector3f {
+        return Vector3f::zero();
+    }
+
+    // sample in world space, the weight to multiply the light by and the ray to follow for it
+    fn scatter(&self, ray_in: &Ray, hit_data: &HitData) -> Option<(Vector3f, Ray)> {
+        let frame = hit_data.shading_frame();
+        let outgoing = frame.vector_to_local(&-ray_in.direction().unit_vector());
+        let sample = self.sample(&outgoing, hit_data)?;
+        let scattered_ray = Ray::new_at_time(hit_data.at, frame.vector_to_world(&sample.incoming), ray_in.time());
+        return Some((sample.weight, scattered_ray));
+    }
+
+    // scatter with the whole scene at hand, for materials that follow light through the object before
+    // it comes back out, such as subsurface scattering. Everything else just scatters at the surface
+    fn scatter_in_world(&self, ray_in: &Ray, hit_data: &HitData, _meshes: &HittableList) -> Option<(Vector3f, Ray)> {
+        return self.scatter(ray_in, hit_data);
+    }
+}
+
+// Texture at the hit tinted by the vertex colour, if the geometry has one
+pub(crate) fn albedo_at(albedo : &dyn Texture, hit_data: &HitData) -> Vector3f{
+    let albedo = albedo.value_at(hit_data);
+    return match hit_data.vertex_color {
+        Some(color) => albedo.multiply_element_wise(&color),
+        None => albedo,
+    };
 }
 
 const VECTOR3F_NEAR_ZERO_TOLERANCE : f32 = 1e-8;
@@ -16,60 +107,709 @@ impl Vector3f{
     }
 }
 
-#[derive(Copy, Clone)]
 pub struct Lambertian {
-    albedo: Vector3f,
+    albedo: Box<dyn Texture>,
 }
 
 impl Lambertian{
     pub fn new(albedo : Vector3f) -> Lambertian{
+        return Lambertian::textured(Box::new(SolidColor::new(albedo)));
+    }
+
+    pub fn textured(albedo : Box<dyn Texture>) -> Lambertian{
         return Lambertian{albedo};
     }
 }
 
+// Lambertian reflection only happens on the side outgoing is on, incoming is flipped across if needed
+fn same_side(outgoing : &Vector3f, incoming : &Vector3f) -> bool{
+    return outgoing.z * incoming.z > 0.0;
+}
+
 impl Material for Lambertian{
-    fn scatter(&self, ray_in: &Ray, hit_data: &HitData) -> (&Vector3f , Ray) {
-        let mut scater_direction = hit_data.normal + random_vec_in_unit_sphere();
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let mut incoming = sample_cosine_hemisphere(rand::random::<f32>(), rand::random::<f32>());
+        if outgoing.z < 0.0 {
+            incoming.z = -incoming.z;
+        }
+        // The cosine and the pdf cancel, leaving just the albedo
+        return Some(BsdfSample::new(incoming, albedo_at(self.albedo.as_ref(), hit_data), incoming.z.abs() / PI));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        if !same_side(outgoing, incoming) {
+            return Vector3f::zero();
+        }
+        return albedo_at(self.albedo.as_ref(), hit_data) / PI;
+    }
 
-        if scater_direction.near_zero(){
-            scater_direction = hit_data.normal;
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, _hit_data: &HitData) -> f32 {
+        if !same_side(outgoing, incoming) {
+            return 0.0;
         }
+        return incoming.z.abs() / PI;
+    }
 
-        let scattered_ray = Ray::new(hit_data.at, scater_direction);
-        return (&self.albedo, scattered_ray);
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.albedo.as_ref(), hit_data);
     }
+}
+
+// Direction about the z axis with density cos(theta) / pi, u1 and u2 are uniform in [0,1)
+pub fn sample_cosine_hemisphere(u1 : f32, u2 : f32) -> Vector3f{
+    let radius = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    return Vector3f::new(radius * phi.cos(), radius * phi.sin(), (1.0 - u1).max(0.0).sqrt());
+}
 
-    fn get_albedo(&self) -> &Vector3f {
-        return &self.albedo;
+// Average weight a material gives rays arriving at cos_theta to the normal, which is the light it sends
+// back when lit evenly from every direction with white. Anything that neither absorbs nor glows should
+// come out at its albedo, above 1 means the material creates energy
+pub fn white_furnace(material : &dyn Material, cos_theta : f32, samples : u32) -> Vector3f{
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let direction = Vector3f::new(sin_theta, 0.0, -cos_theta);
+    let normal = Vector3f::unit_z();
+    let hit_data = HitData::new(1.0, Vector3f::zero(), normal, &direction, &normal, material);
+    let ray_in = Ray::new(-direction, direction);
+
+    let mut total = Vector3f::zero();
+    for _ in 0..samples {
+        if let Some((weight, _)) = material.scatter(&ray_in, &hit_data) {
+            total = total + weight;
+        }
     }
+    return total / samples.max(1) as f32;
+}
+
+// Schlick's approximation, reflectance rising from f0 at normal incidence to white at grazing angles
+pub fn fresnel_schlick(cos_incident : f32, f0 : &Vector3f) -> Vector3f{
+    let m = (1.0 - cos_incident.clamp(0.0, 1.0)).powi(5);
+    return *f0 + m * (Vector3f::one() - *f0);
 }
 
-#[derive(Copy, Clone)]
+// Artist friendly metal, a GGX microfacet reflector whose colour at normal incidence is albedo
 pub struct Metal {
-    albedo: Vector3f,
-    roughness : f32,
+    albedo: Box<dyn Texture>,
+    // Perceptual roughness in [0,1], 0 is a mirror
+    roughness : Box<dyn Texture>,
+    // In [-1,1], stretches the highlight along dpdu or dpdv
+    anisotropy : f32,
 }
 
 impl Metal{
     pub fn new(albedo : Vector3f, roughness : f32) -> Metal{
-        return Metal{albedo, roughness};
+        return Metal::textured(Box::new(SolidColor::new(albedo)), roughness);
     }
 
     pub fn new_perfect(albedo : Vector3f) -> Metal{
-        return Metal{albedo, roughness : 0.0};
+        return Metal::new(albedo, 0.0);
+    }
+
+    pub fn textured(albedo : Box<dyn Texture>, roughness : f32) -> Metal{
+        return Metal{albedo, roughness : constant(roughness), anisotropy : 0.0};
+    }
+
+    // Roughness read from a texture instead, such as a pattern of polished and scuffed patches
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Metal{
+        self.roughness = roughness;
+        return self;
+    }
+
+    // Brushed metal, the brushing runs along the u direction of the surface for positive anisotropy
+    pub fn with_anisotropy(mut self, anisotropy : f32) -> Metal{
+        self.anisotropy = anisotropy;
+        return self;
+    }
+
+    fn distribution(&self, hit_data: &HitData) -> TrowbridgeReitz{
+        return TrowbridgeReitz::anisotropic(self.roughness.scalar_at(hit_data), self.anisotropy);
     }
 }
 
 impl Material for Metal{
-    fn scatter(&self, ray_in: &Ray, hit_data: &HitData) -> (&Vector3f , Ray) {
-        let mut reflected = ray_in.direction().get_reflected(&hit_data.normal);
-        reflected = reflected + self.roughness * random_vec_in_unit_sphere();
-        let scattered_ray = Ray::new(hit_data.at, reflected);
-        
-        return (&self.albedo, scattered_ray);
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let albedo = albedo_at(self.albedo.as_ref(), hit_data);
+        return sample_reflection(outgoing, &self.distribution(hit_data), |cos| fresnel_schlick(cos, &albedo));
     }
 
-    fn get_albedo(&self) -> &Vector3f {
-        return &self.albedo;
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        let albedo = albedo_at(self.albedo.as_ref(), hit_data);
+        return eval_reflection(outgoing, incoming, &self.distribution(hit_data), |cos| fresnel_schlick(cos, &albedo));
     }
-}
\ No newline at end of file
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        return pdf_reflection(outgoing, incoming, &self.distribution(hit_data));
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.albedo.as_ref(), hit_data);
+    }
+}
+// Light source that glows evenly in every direction. It absorbs everything that hits it,
+// so paths end at lights rather than bouncing off them
+pub struct DiffuseLight {
+    color : Box<dyn Texture>,
+    // Scale on color, values above 1 are what make lights brighter than the surfaces they light
+    intensity : f32,
+    // Only the front face glows, the back stays black
+    one_sided : bool,
+}
+
+impl DiffuseLight{
+    pub fn new(color : Vector3f, intensity : f32) -> DiffuseLight{
+        return DiffuseLight::textured(Box::new(SolidColor::new(color)), intensity);
+    }
+
+    pub fn textured(color : Box<dyn Texture>, intensity : f32) -> DiffuseLight{
+        return DiffuseLight{color, intensity, one_sided : false};
+    }
+
+    pub fn with_one_sided(mut self, one_sided : bool) -> DiffuseLight{
+        self.one_sided = one_sided;
+        return self;
+    }
+}
+
+impl Material for DiffuseLight{
+    fn sample(&self, _outgoing: &Vector3f, _hit_data: &HitData) -> Option<BsdfSample> {
+        return None;
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.color.as_ref(), hit_data);
+    }
+
+    fn emitted(&self, _ray_in: &Ray, hit_data: &HitData) -> Vector3f {
+        if self.one_sided && !hit_data.hit_front_face {
+            return Vector3f::zero();
+        }
+        return self.intensity * albedo_at(self.color.as_ref(), hit_data);
+    }
+}
+
+// Fraction of light reflected at a smooth boundary between dielectrics, from the full Fresnel equations
+// averaged over both polarisations. cos_incident is against the normal and eta_ratio is eta_incident / eta_transmitted
+pub fn fresnel_dielectric(cos_incident : f32, eta_ratio : f32) -> f32{
+    let cos_incident = cos_incident.clamp(0.0, 1.0);
+    let sin_squared_transmitted = eta_ratio * eta_ratio * (1.0 - cos_incident * cos_incident);
+    if sin_squared_transmitted >= 1.0 {
+        return 1.0;
+    }
+    let cos_transmitted = (1.0 - sin_squared_transmitted).sqrt();
+
+    let parallel = (cos_incident - eta_ratio * cos_transmitted) / (cos_incident + eta_ratio * cos_transmitted);
+    let perpendicular = (eta_ratio * cos_incident - cos_transmitted) / (eta_ratio * cos_incident + cos_transmitted);
+    return (parallel * parallel + perpendicular * perpendicular) / 2.0;
+}
+
+// Reflectance of a conductor with complex index of refraction eta + ik, for one wavelength.
+// Closed form of the Fresnel equations for an absorbing medium, averaged over both polarisations
+fn fresnel_conductor_channel(cos_incident : f32, eta : f32, k : f32) -> f32{
+    let cos2 = cos_incident * cos_incident;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * a * cos_incident;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    return (parallel + perpendicular) / 2.0;
+}
+
+// Conductor Fresnel for the red, green and blue channels, each with its own eta and k
+pub fn fresnel_conductor(cos_incident : f32, eta : &Vector3f, k : &Vector3f) -> Vector3f{
+    let cos_incident = cos_incident.clamp(0.0, 1.0);
+    return Vector3f::new(
+        fresnel_conductor_channel(cos_incident, eta.x, k.x),
+        fresnel_conductor_channel(cos_incident, eta.y, k.y),
+        fresnel_conductor_channel(cos_incident, eta.z, k.z),
+    );
+}
+
+// Physically based metal, a GGX microfacet reflector with the colour coming out of the complex
+// index of refraction rather than being painted on. eta and k are at red, green and blue wavelengths
+pub struct Conductor {
+    eta : Vector3f,
+    k : Vector3f,
+    // Perceptual roughness in [0,1], 0 is a mirror
+    roughness : Box<dyn Texture>,
+    // In [-1,1], stretches the highlight along dpdu or dpdv
+    anisotropy : f32,
+}
+
+impl Conductor{
+    pub fn new(eta : Vector3f, k : Vector3f, roughness : f32) -> Conductor{
+        return Conductor{eta, k, roughness : constant(roughness), anisotropy : 0.0};
+    }
+
+    pub fn gold(roughness : f32) -> Conductor{
+        return Conductor::new(Vector3f::new(0.143, 0.374, 1.442), Vector3f::new(3.983, 2.385, 1.603), roughness);
+    }
+
+    pub fn copper(roughness : f32) -> Conductor{
+        return Conductor::new(Vector3f::new(0.200, 0.924, 1.102), Vector3f::new(3.912, 2.452, 2.142), roughness);
+    }
+
+    pub fn aluminium(roughness : f32) -> Conductor{
+        return Conductor::new(Vector3f::new(1.657, 0.880, 0.521), Vector3f::new(9.224, 6.270, 4.837), roughness);
+    }
+
+    pub fn silver(roughness : f32) -> Conductor{
+        return Conductor::new(Vector3f::new(0.155, 0.117, 0.138), Vector3f::new(4.828, 3.122, 2.147), roughness);
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Conductor{
+        self.roughness = roughness;
+        return self;
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy : f32) -> Conductor{
+        self.anisotropy = anisotropy;
+        return self;
+    }
+
+    fn distribution(&self, hit_data: &HitData) -> TrowbridgeReitz{
+        return TrowbridgeReitz::anisotropic(self.roughness.scalar_at(hit_data), self.anisotropy);
+    }
+}
+
+impl Material for Conductor{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        return sample_reflection(outgoing, &self.distribution(hit_data), |cos| fresnel_conductor(cos, &self.eta, &self.k));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        return eval_reflection(outgoing, incoming, &self.distribution(hit_data), |cos| fresnel_conductor(cos, &self.eta, &self.k));
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        return pdf_reflection(outgoing, incoming, &self.distribution(hit_data));
+    }
+
+    // Colour seen head on
+    fn get_albedo(&self, _hit_data: &HitData) -> Vector3f {
+        return fresnel_conductor(1.0, &self.eta, &self.k);
+    }
+}
+
+// Clear refractive material such as glass or water. Each bounce either reflects or refracts,
+// chosen with the Fresnel reflectance so the split between the two comes out right on average.
+// Rough surfaces scatter both through GGX microfacets, giving frosted glass
+pub struct Dielectric {
+    // Index of refraction of the inside of the object relative to the outside
+    ior : f32,
+    // Colour multiplied in at every bounce, white for clear materials
+    tint : Box<dyn Texture>,
+    // Perceptual roughness in [0,1], 0 is polished
+    roughness : Box<dyn Texture>,
+    // Beer-Lambert absorption coefficient per unit distance travelled inside, for each channel
+    absorption : Vector3f,
+}
+
+impl Dielectric{
+    pub fn new(ior : f32) -> Dielectric{
+        return Dielectric{ior, tint : Box::new(SolidColor::new(Vector3f::one())), roughness : constant(0.0), absorption : Vector3f::zero()};
+    }
+
+    pub fn glass() -> Dielectric{
+        return Dielectric::new(1.5);
+    }
+
+    pub fn water() -> Dielectric{
+        return Dielectric::new(1.333);
+    }
+
+    pub fn diamond() -> Dielectric{
+        return Dielectric::new(2.42);
+    }
+
+    pub fn with_tint(mut self, tint : Box<dyn Texture>) -> Dielectric{
+        self.tint = tint;
+        return self;
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Dielectric{
+        self.roughness = roughness;
+        return self;
+    }
+
+    // Coloured liquid or glass that gets deeper in colour the further light travels through it.
+    // color is what white light has turned into after distance inside. Only closed objects work,
+    // since the distance is measured from where a ray enters to where it next hits the surface from inside
+    pub fn with_absorption(mut self, color : Vector3f, distance : f32) -> Dielectric{
+        let coefficient = |channel : f32| -channel.clamp(1e-6, 1.0).ln() / distance;
+        self.absorption = Vector3f::new(coefficient(color.x), coefficient(color.y), coefficient(color.z));
+        return self;
+    }
+
+    // Inside the surface is above for rays leaving through the back face, so the material's own ior is the incident one
+    fn relative_ior(&self, hit_data: &HitData) -> f32{
+        return if hit_data.hit_front_face { self.ior } else { 1.0 / self.ior };
+    }
+
+    fn distribution(&self, hit_data: &HitData) -> TrowbridgeReitz{
+        return TrowbridgeReitz::from_roughness(self.roughness.scalar_at(hit_data));
+    }
+
+    // Tint of the bounce, plus what was absorbed on the way to a hit from inside. Scattered rays have
+    // unit length directions so t is the distance travelled
+    fn attenuation(&self, hit_data: &HitData) -> Vector3f{
+        let tint = albedo_at(self.tint.as_ref(), hit_data);
+        if hit_data.hit_front_face || self.absorption.near_zero() {
+            return tint;
+        }
+        let optical_depth = hit_data.t * self.absorption;
+        let transmittance = Vector3f::new((-optical_depth.x).exp(), (-optical_depth.y).exp(), (-optical_depth.z).exp());
+        return tint.multiply_element_wise(&transmittance);
+    }
+}
+
+impl Material for Dielectric{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let mut sample = sample_dielectric(outgoing, &self.distribution(hit_data), self.relative_ior(hit_data))?;
+        sample.weight = sample.weight.multiply_element_wise(&self.attenuation(hit_data));
+        return Some(sample);
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        let value = eval_dielectric(outgoing, incoming, &self.distribution(hit_data), self.relative_ior(hit_data));
+        return value.multiply_element_wise(&self.attenuation(hit_data));
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        return pdf_dielectric(outgoing, incoming, &self.distribution(hit_data), self.relative_ior(hit_data));
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.tint.as_ref(), hit_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::layered::{Coated, Mix};
+    use crate::material::oren_nayar::OrenNayar;
+    use crate::material::principled::Principled;
+    use crate::material::sheen::Sheen;
+    use crate::material::subsurface::Subsurface;
+    use crate::material::thin_film::ThinFilm;
+
+    const COS_THETAS : [f32; 4] = [1.0, 0.7, 0.4, 0.1];
+    const ROUGHNESSES : [f32; 4] = [0.05, 0.3, 0.6, 1.0];
+    const SAMPLES : u32 = 20000;
+    // Allowance for the Monte Carlo noise of the furnace
+    const EPSILON : f32 = 0.03;
+
+    fn assert_conserves_energy(name : &str, make : impl Fn(f32) -> Box<dyn Material>) {
+        for roughness in ROUGHNESSES {
+            let material = make(roughness);
+            for cos_theta in COS_THETAS {
+                let albedo = white_furnace(material.as_ref(), cos_theta, SAMPLES);
+                for channel in 0..3 {
+                    assert!(albedo.axis(channel) <= 1.0 + EPSILON, "{} reflects {} at roughness {} and cos {}", name, albedo.axis(channel), roughness, cos_theta);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lambertian_reflects_its_albedo() {
+        let albedo = Vector3f::new(0.9, 0.5, 0.2);
+        let material = Lambertian::new(albedo);
+        for cos_theta in COS_THETAS {
+            let reflected = white_furnace(&material, cos_theta, SAMPLES);
+            for channel in 0..3 {
+                assert!((reflected.axis(channel) - albedo.axis(channel)).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn materials_do_not_create_energy() {
+        assert_conserves_energy("dielectric principled", |roughness| Box::new(Principled::new(Vector3f::one()).with_roughness(constant(roughness))));
+        assert_conserves_energy("metallic principled", |roughness| {
+            Box::new(Principled::new(Vector3f::one()).with_metallic(constant(1.0)).with_roughness(constant(roughness)))
+        });
+        assert_conserves_energy("conductor", |roughness| Box::new(Conductor::new(Vector3f::uniform(0.01), Vector3f::uniform(100.0), roughness)));
+        assert_conserves_energy("coated", |roughness| Box::new(Coated::new(Box::new(Lambertian::new(Vector3f::one())), 1.5, roughness)));
+        assert_conserves_energy("coated glass", |roughness| {
+            Box::new(Coated::new(Box::new(Dielectric::glass().with_roughness(constant(roughness))), 1.5, roughness))
+        });
+        assert_conserves_energy("sheen", |roughness| Box::new(Sheen::new(Vector3f::one(), roughness)));
+        assert_conserves_energy("oren nayar", |roughness| Box::new(OrenNayar::new(Vector3f::one(), 90.0 * roughness)));
+        assert_conserves_energy("dielectric", |roughness| Box::new(Dielectric::glass().with_roughness(constant(roughness))));
+        assert_conserves_energy("mix", |roughness| {
+            let metal = Conductor::new(Vector3f::uniform(0.01), Vector3f::uniform(100.0), roughness);
+            Box::new(Mix::new(Box::new(Lambertian::new(Vector3f::one())), Box::new(metal), 0.5))
+        });
+        assert_conserves_energy("thin film", |roughness| Box::new(ThinFilm::dielectric(1.5, 300.0, 1.33).with_roughness(constant(roughness))));
+        assert_conserves_energy("subsurface", |roughness| {
+            Box::new(Subsurface::new(Vector3f::one(), Vector3f::one(), Vector3f::zero(), 1.5).with_roughness(constant(roughness)))
+        });
+    }
+
+    #[test]
+    fn white_principled_loses_only_what_its_highlight_does() {
+        // The diffuse base gets the light the dielectric highlight doesn't reflect and sends all of it back like
+        // a white Lambertian would, even at roughness 1. Single scattering GGX loses some of the highlight
+        for roughness in ROUGHNESSES {
+            let material = Principled::new(Vector3f::one()).with_roughness(constant(roughness));
+            for cos_theta in COS_THETAS {
+                let reflected = white_furnace(&material, cos_theta, SAMPLES).x;
+                let diffuse_share = 1.0 - fresnel_schlick(cos_theta, &Vector3f::uniform(0.04)).x;
+                assert!(reflected >= diffuse_share - EPSILON, "reflects {} at roughness {} and cos {}", reflected, roughness, cos_theta);
+            }
+        }
+    }
+
+    fn hit_from(direction : Vector3f, material : &dyn Material) -> HitData<'_> {
+        let normal = Vector3f::unit_z();
+        return HitData::new(1.0, Vector3f::zero(), normal, &direction, &normal, material);
+    }
+
+    #[test]
+    fn fresnel_dielectric_matches_reference_values() {
+        // ((n - 1) / (n + 1))^2 head on, from either side
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-5);
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-5);
+        // Air to glass at 45 degrees, Rs = 0.0920 and Rp = 0.0085
+        assert!((fresnel_dielectric(0.5f32.sqrt(), 1.0 / 1.5) - 0.0503).abs() < 1e-3);
+        assert!(fresnel_dielectric(0.7, 1.0).abs() < 1e-6);
+        assert!((fresnel_dielectric(0.0, 1.0 / 1.5) - 1.0).abs() < 1e-6);
+        // Past the critical angle of about 41.8 degrees inside glass everything reflects
+        assert_eq!(fresnel_dielectric(0.5, 1.5), 1.0);
+        assert!(fresnel_dielectric(0.8, 1.5) < 1.0);
+    }
+
+    #[test]
+    fn smooth_dielectric_splits_by_fresnel_and_refracts_by_snell() {
+        let glass = Dielectric::glass();
+        let head_on = hit_from(Vector3f::new(0.0, 0.0, -1.0), &glass);
+        let outgoing = Vector3f::unit_z();
+        let mut reflected = 0;
+        for _ in 0..SAMPLES {
+            let sample = glass.sample(&outgoing, &head_on).unwrap();
+            assert!(sample.is_delta);
+            assert!((sample.weight.x - 1.0).abs() < 1e-6);
+            if sample.incoming.z > 0.0 {
+                reflected += 1;
+            }
+        }
+        assert!((reflected as f32 / SAMPLES as f32 - 0.04).abs() < 0.01);
+
+        let sin_45 = 0.5f32.sqrt();
+        let oblique = hit_from(Vector3f::new(sin_45, 0.0, -sin_45), &glass);
+        let outgoing = Vector3f::new(-sin_45, 0.0, sin_45);
+        for _ in 0..100 {
+            let incoming = glass.sample(&outgoing, &oblique).unwrap().incoming;
+            if incoming.z < 0.0 {
+                assert!((incoming.x - sin_45 / 1.5).abs() < 1e-4);
+            } else {
+                assert!((incoming.x - sin_45).abs() < 1e-4 && (incoming.z - sin_45).abs() < 1e-4);
+            }
+        }
+
+        // Leaving through the back face at 60 degrees is past the critical angle
+        let inside = hit_from(Vector3f::new(0.75f32.sqrt(), 0.0, 0.5), &glass);
+        assert!(!inside.hit_front_face);
+        let outgoing = Vector3f::new(-0.75f32.sqrt(), 0.0, 0.5);
+        for _ in 0..100 {
+            assert!(glass.sample(&outgoing, &inside).unwrap().incoming.z > 0.0);
+        }
+    }
+
+    #[test]
+    fn dielectric_weight_is_its_tint() {
+        let tint = Vector3f::new(1.0, 0.5, 0.25);
+        let glass = Dielectric::glass().with_tint(Box::new(SolidColor::new(tint)));
+        for cos_theta in COS_THETAS {
+            let reflected = white_furnace(&glass, cos_theta, 1000);
+            assert!((reflected.y - 0.5).abs() < 1e-4 && (reflected.z - 0.25).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn diffuse_light_glows_and_absorbs() {
+        let light = DiffuseLight::new(Vector3f::new(1.0, 0.5, 0.25), 4.0);
+        let ray = Ray::new(Vector3f::unit_z(), Vector3f::new(0.0, 0.0, -1.0));
+        let front = hit_from(Vector3f::new(0.0, 0.0, -1.0), &light);
+        assert!(light.sample(&Vector3f::unit_z(), &front).is_none());
+        let emitted = light.emitted(&ray, &front);
+        assert!((emitted.x - 4.0).abs() < 1e-6 && (emitted.y - 2.0).abs() < 1e-6 && (emitted.z - 1.0).abs() < 1e-6);
+
+        let back = hit_from(Vector3f::new(0.0, 0.0, 1.0), &light);
+        assert!((light.emitted(&ray, &back).x - 4.0).abs() < 1e-6);
+        let one_sided = DiffuseLight::new(Vector3f::one(), 4.0).with_one_sided(true);
+        assert!(one_sided.emitted(&ray, &hit_from(Vector3f::new(0.0, 0.0, 1.0), &one_sided)).near_zero());
+        assert!((one_sided.emitted(&ray, &hit_from(Vector3f::new(0.0, 0.0, -1.0), &one_sided)).x - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fresnel_conductor_matches_reference_values() {
+        // Without absorption the conductor formula is the dielectric one
+        for cos in [1.0, 0.8, 0.5, 0.2] {
+            let conductor = fresnel_conductor(cos, &Vector3f::uniform(1.5), &Vector3f::zero());
+            assert!((conductor.x - fresnel_dielectric(cos, 1.0 / 1.5)).abs() < 1e-4);
+        }
+        // ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2) head on, gold's red channel is about 0.967
+        let gold = Conductor::gold(0.0);
+        let head_on = gold.get_albedo(&hit_from(Vector3f::new(0.0, 0.0, -1.0), &gold));
+        assert!((head_on.x - 0.9667).abs() < 1e-3);
+        assert!(head_on.x > head_on.y && head_on.y > head_on.z);
+        // Everything reflects at grazing angles and for very absorbing metals
+        assert!((fresnel_conductor(0.0, &Vector3f::uniform(0.2), &Vector3f::uniform(3.0)).x - 1.0).abs() < 1e-4);
+        assert!(fresnel_conductor(0.6, &Vector3f::uniform(0.01), &Vector3f::uniform(100.0)).x > 0.999);
+    }
+
+    #[test]
+    fn smooth_conductor_mirrors_with_its_fresnel() {
+        let silver = Conductor::silver(0.0);
+        let sin_45 = 0.5f32.sqrt();
+        let hit = hit_from(Vector3f::new(sin_45, 0.0, -sin_45), &silver);
+        let sample = silver.sample(&Vector3f::new(-sin_45, 0.0, sin_45), &hit).unwrap();
+        assert!(sample.is_delta);
+        assert!((sample.incoming.x - sin_45).abs() < 1e-5 && (sample.incoming.z - sin_45).abs() < 1e-5);
+        let expected = fresnel_conductor(sin_45, &Vector3f::new(0.155, 0.117, 0.138), &Vector3f::new(4.828, 3.122, 2.147));
+        assert!((sample.weight.y - expected.y).abs() < 1e-6);
+    }
+
+    fn assert_samples_match_eval_and_pdf(name : &str, material : &dyn Material, from_inside : bool) {
+        for cos_theta in COS_THETAS {
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let direction = Vector3f::new(sin_theta, 0.0, if from_inside { cos_theta } else { -cos_theta });
+            let hit = hit_from(direction, material);
+            let outgoing = hit.shading_frame().vector_to_local(&-direction);
+            for _ in 0..2000 {
+                let sample = match material.sample(&outgoing, &hit) {
+                    Some(sample) if !sample.is_delta => sample,
+                    _ => continue,
+                };
+                let pdf = material.pdf(&outgoing, &sample.incoming, &hit);
+                assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf, "{} samples with pdf {} but pdf gives {}", name, sample.pdf, pdf);
+                let expected = material.eval(&outgoing, &sample.incoming, &hit) * sample.incoming.z.abs() / pdf;
+                for channel in 0..3 {
+                    let (weight, expected) = (sample.weight.axis(channel), expected.axis(channel));
+                    assert!((weight - expected).abs() <= 1e-3 * expected.max(1.0), "{} weighs {} but eval gives {}", name, weight, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_agree_with_eval_and_pdf() {
+        // Phase materials have no cosine in their weight and are checked on their own
+        let white = Vector3f::one();
+        let color = Vector3f::new(0.9, 0.6, 0.3);
+        let materials : Vec<(&str, Box<dyn Material>)> = vec![
+            ("lambertian", Box::new(Lambertian::new(color))),
+            ("metal", Box::new(Metal::new(color, 0.4).with_anisotropy(0.6))),
+            ("conductor", Box::new(Conductor::gold(0.3))),
+            ("rough dielectric", Box::new(Dielectric::glass().with_roughness(constant(0.4)))),
+            ("principled", Box::new(Principled::new(color))),
+            ("metallic principled", Box::new(Principled::new(color).with_metallic(constant(0.5)).with_roughness(constant(0.3)))),
+            ("coated principled", Box::new(Principled::new(color).with_clearcoat(constant(1.0), constant(0.3)).with_sheen(constant(1.0), constant(0.5)))),
+            ("transmissive principled", Box::new(Principled::new(color).with_transmission(constant(0.7), 1.5).with_roughness(constant(0.4)))),
+            ("coated", Box::new(Coated::new(Box::new(Lambertian::new(color)), 1.5, 0.2).with_tint(color))),
+            ("coated metal", Box::new(Coated::new(Box::new(Metal::new(color, 0.5)), 1.5, 0.2))),
+            ("coated glass", Box::new(Coated::new(Box::new(Dielectric::glass().with_roughness(constant(0.4))), 1.5, 0.2).with_tint(color))),
+            ("mix", Box::new(Mix::new(Box::new(Lambertian::new(color)), Box::new(Conductor::copper(0.4)), 0.3))),
+            ("thin film", Box::new(ThinFilm::dielectric(1.5, 400.0, 1.33).with_roughness(constant(0.3)))),
+            ("thin film conductor", Box::new(ThinFilm::conductor(Vector3f::uniform(0.2), Vector3f::uniform(3.0), 300.0, 1.4).with_roughness(constant(0.3)))),
+            ("sheen", Box::new(Sheen::new(white, 0.5))),
+            ("oren nayar", Box::new(OrenNayar::new(color, 30.0))),
+            ("subsurface", Box::new(Subsurface::skin(1.0).with_roughness(constant(0.3)))),
+        ];
+        for (name, material) in materials.iter() {
+            assert_samples_match_eval_and_pdf(name, material.as_ref(), false);
+            assert_samples_match_eval_and_pdf(name, material.as_ref(), true);
+        }
+    }
+
+    #[test]
+    fn dielectric_absorbs_along_the_path_inside() {
+        let color = Vector3f::new(0.8, 0.5, 0.2);
+        let glass = Dielectric::glass().with_absorption(color, 2.0);
+        let normal = Vector3f::unit_z();
+        let outgoing = Vector3f::unit_z();
+        // Hitting the surface from inside after travelling t, leaving color^(t / 2)
+        for (t, exponent) in [(2.0, 1.0), (4.0, 2.0), (1.0, 0.5)] {
+            let inside = HitData::new(t, Vector3f::zero(), normal, &normal, &normal, &glass);
+            let weight = glass.sample(&outgoing, &inside).unwrap().weight;
+            assert!((weight.x - color.x.powf(exponent)).abs() < 1e-4);
+            assert!((weight.z - color.z.powf(exponent)).abs() < 1e-4);
+        }
+        // Nothing is absorbed before the light gets in
+        let outside = HitData::new(4.0, Vector3f::zero(), normal, &-normal, &normal, &glass);
+        assert!((glass.sample(&outgoing, &outside).unwrap().weight.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rough_dielectric_spreads_refraction_around_snell() {
+        let sin_45 = 0.5f32.sqrt();
+        let direction = Vector3f::new(sin_45, 0.0, -sin_45);
+        for roughness in [0.1, 0.5] {
+            let glass = Dielectric::glass().with_roughness(constant(roughness));
+            let hit = hit_from(direction, &glass);
+            let outgoing = hit.shading_frame().vector_to_local(&-direction);
+            let (mut total, mut count) = (0.0, 0);
+            for _ in 0..SAMPLES {
+                if let Some(sample) = glass.sample(&outgoing, &hit) {
+                    if sample.incoming.z < 0.0 {
+                        let world = hit.shading_frame().vector_to_world(&sample.incoming);
+                        total += world.x;
+                        count += 1;
+                    }
+                }
+            }
+            // Refracted light carries on into the glass bent towards the normal, on average close to Snell's direction
+            let mean_sin = total / count as f32;
+            assert!((mean_sin - sin_45 / 1.5).abs() < 0.05 * (1.0 + 4.0 * roughness), "mean sin {} at roughness {}", mean_sin, roughness);
+            assert!(count as f32 / SAMPLES as f32 > 0.8);
+        }
+    }
+
+    #[test]
+    fn coat_tints_light_passing_into_a_transmissive_base() {
+        let tint = Vector3f::new(0.9, 0.6, 0.3);
+        let coated = Coated::new(Box::new(Dielectric::glass()), 1.5, 0.0).with_tint(tint);
+        let hit = hit_from(Vector3f::new(0.0, 0.0, -1.0), &coated);
+        let mut refracted = 0;
+        for _ in 0..2000 {
+            let sample = coated.sample(&Vector3f::unit_z(), &hit).unwrap();
+            // Head on the light crosses the coat once on its way into the glass
+            if sample.incoming.z < 0.0 {
+                assert!((sample.weight.y - tint.y).abs() < 1e-4 && (sample.weight.z - tint.z).abs() < 1e-4);
+                refracted += 1;
+            }
+        }
+        assert!(refracted > 1000);
+    }
+
+    #[test]
+    fn anisotropic_metal_stretches_its_highlight_along_the_brushing() {
+        let spread = |anisotropy : f32| {
+            let metal = Metal::new(Vector3f::one(), 0.5).with_anisotropy(anisotropy);
+            let hit = hit_from(Vector3f::new(0.0, 0.0, -1.0), &metal).with_tangents(Vector3f::new(1.0, 0.0, 0.0), Vector3f::new(0.0, 1.0, 0.0));
+            let (mut along_u, mut along_v) = (0.0, 0.0);
+            for _ in 0..SAMPLES {
+                if let Some(sample) = metal.sample(&Vector3f::unit_z(), &hit) {
+                    along_u += sample.incoming.x.abs();
+                    along_v += sample.incoming.y.abs();
+                }
+            }
+            return along_u / along_v;
+        };
+        assert!((spread(0.0) - 1.0).abs() < 0.05);
+        assert!(spread(0.8) > 1.5);
+        assert!(spread(-0.8) < 1.0 / 1.5);
+    }
+}
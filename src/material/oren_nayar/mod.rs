@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::{albedo_at, sample_cosine_hemisphere, BsdfSample, Material};
+use crate::texture::{SolidColor, Texture};
+use crate::vectorlib::{hit::HitData, vector3::*};
+
+// Rough diffuse surface made of tiny Lambertian v-grooves, after Oren and Nayar 1994. Unlike
+// Lambertian it looks flatter and brighter towards the light at grazing angles, like clay, plaster,
+// concrete or the moon. A sigma of 0 is exactly Lambertian
+pub struct OrenNayar {
+    albedo : Box<dyn Texture>,
+    // Constants of the qualitative model, worked out once from sigma
+    a : f32,
+    b : f32,
+}
+
+impl OrenNayar{
+    // sigma_degrees is the standard deviation of the groove slopes, 20 to 40 suits most rough materials
+    pub fn new(albedo : Vector3f, sigma_degrees : f32) -> OrenNayar{
+        return OrenNayar::textured(Box::new(SolidColor::new(albedo)), sigma_degrees);
+    }
+
+    pub fn textured(albedo : Box<dyn Texture>, sigma_degrees : f32) -> OrenNayar{
+        let sigma2 = sigma_degrees.to_radians().powi(2);
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+        return OrenNayar{albedo, a, b};
+    }
+
+    // The BRDF over albedo / pi
+    fn factor(&self, outgoing : &Vector3f, incoming : &Vector3f) -> f32{
+        let sin_outgoing = (1.0 - outgoing.z * outgoing.z).max(0.0).sqrt();
+        let sin_incoming = (1.0 - incoming.z * incoming.z).max(0.0).sqrt();
+        // Cosine of the azimuth between the two directions, only light from the same side brightens
+        let cos_azimuth = if sin_outgoing > 1e-4 && sin_incoming > 1e-4 {
+            ((outgoing.x * incoming.x + outgoing.y * incoming.y) / (sin_outgoing * sin_incoming)).max(0.0)
+        } else {
+            0.0
+        };
+        // alpha is the larger angle to the normal and beta the smaller
+        let (sin_alpha, tan_beta) = if incoming.z.abs() > outgoing.z.abs() {
+            (sin_outgoing, sin_incoming / incoming.z.abs())
+        } else {
+            (sin_incoming, sin_outgoing / outgoing.z.abs().max(1e-6))
+        };
+        return self.a + self.b * cos_azimuth * sin_alpha * tan_beta;
+    }
+}
+
+impl Material for OrenNayar{
+    // Cosine sampling as for Lambertian, the grooves only change the brightness by a bounded factor
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let mut incoming = sample_cosine_hemisphere(random::<f32>(), random::<f32>());
+        if outgoing.z < 0.0 {
+            incoming.z = -incoming.z;
+        }
+        let weight = self.factor(outgoing, &incoming) * albedo_at(self.albedo.as_ref(), hit_data);
+        return Some(BsdfSample::new(incoming, weight, incoming.z.abs() / PI));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        if outgoing.z * incoming.z <= 0.0 {
+            return Vector3f::zero();
+        }
+        return self.factor(outgoing, incoming) * albedo_at(self.albedo.as_ref(), hit_data) / PI;
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, _hit_data: &HitData) -> f32 {
+        if outgoing.z * incoming.z <= 0.0 {
+            return 0.0;
+        }
+        return incoming.z.abs() / PI;
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.albedo.as_ref(), hit_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(degrees : f32, azimuth_degrees : f32) -> Vector3f {
+        let (theta, phi) = (degrees.to_radians(), azimuth_degrees.to_radians());
+        return Vector3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+    }
+
+    #[test]
+    fn smooth_grooves_are_lambertian() {
+        let material = OrenNayar::new(Vector3f::one(), 0.0);
+        for (outgoing, incoming) in [(0.0, 0.0), (60.0, 30.0), (80.0, 10.0), (45.0, 85.0)] {
+            assert!((material.factor(&direction(outgoing, 0.0), &direction(incoming, 40.0)) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rough_grooves_match_the_qualitative_model() {
+        // sigma 20 degrees gives A = 0.8652 and B = 0.2588
+        let material = OrenNayar::new(Vector3f::one(), 20.0);
+        assert!((material.a - 0.8652).abs() < 1e-3 && (material.b - 0.2588).abs() < 1e-3);
+
+        // Same azimuth, alpha 60 and beta 30 degrees: A + B sin 60 tan 30 = A + B / 2
+        let same_side = material.factor(&direction(60.0, 0.0), &direction(30.0, 0.0));
+        assert!((same_side - (material.a + material.b / 2.0)).abs() < 1e-4);
+        assert!((material.factor(&direction(30.0, 0.0), &direction(60.0, 0.0)) - same_side).abs() < 1e-4);
+        // Light from the far side gets no boost
+        assert!((material.factor(&direction(60.0, 0.0), &direction(30.0, 180.0)) - material.a).abs() < 1e-4);
+    }
+}
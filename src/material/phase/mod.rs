@@ -0,0 +1,128 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::{BsdfSample, Material};
+use crate::vectorlib::{frame::Frame, hit::HitData, vector3::*};
+
+// Describes which directions light scatters into inside a participating medium
+#[derive(Copy, Clone)]
+pub enum PhaseFunction {
+    // Every direction equally likely
+    Isotropic,
+    // g in (-1,1), positive favours forward scattering (fog, clouds), negative backward, 0 is isotropic
+    HenyeyGreenstein(f32),
+}
+
+impl PhaseFunction {
+    // Probability density of scattering by an angle with the given cosine, per unit solid angle
+    pub fn evaluate(&self, cos_theta: f32) -> f32 {
+        return match self {
+            PhaseFunction::Isotropic => 1.0 / (4.0 * PI),
+            PhaseFunction::HenyeyGreenstein(g) => {
+                let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+                (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+            }
+        };
+    }
+
+    // New unit direction for light that was travelling along direction_in, sampled proportional to evaluate
+    pub fn sample(&self, direction_in: &Vector3f) -> Vector3f {
+        let cos_theta = match self {
+            PhaseFunction::HenyeyGreenstein(g) if g.abs() > 1e-3 => {
+                let square = (1.0 - g * g) / (1.0 - g + 2.0 * g * random::<f32>());
+                ((1.0 + g * g - square * square) / (2.0 * g)).clamp(-1.0, 1.0)
+            }
+            _ => 1.0 - 2.0 * random::<f32>(),
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random::<f32>();
+
+        let frame = Frame::from_z(Vector3f::zero(), *direction_in);
+        return frame.vector_to_world(&Vector3f::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+    }
+}
+
+// Material given to the scattering events a medium produces, it picks the new direction with the phase
+// function. albedo is the fraction of light that scatters rather than being absorbed at each event
+#[derive(Copy, Clone)]
+pub struct PhaseMaterial {
+    albedo: Vector3f,
+    phase: PhaseFunction,
+}
+
+impl PhaseMaterial {
+    pub fn new(albedo: Vector3f, phase: PhaseFunction) -> PhaseMaterial {
+        return PhaseMaterial { albedo, phase };
+    }
+
+    pub fn isotropic(albedo: Vector3f) -> PhaseMaterial {
+        return PhaseMaterial::new(albedo, PhaseFunction::Isotropic);
+    }
+}
+
+// The ray arrived travelling along -outgoing and leaves towards incoming, so the scattering angle is between those two
+impl Material for PhaseMaterial {
+    fn sample(&self, outgoing: &Vector3f, _hit_data: &HitData) -> Option<BsdfSample> {
+        let incoming = self.phase.sample(&-*outgoing);
+        // Sampled exactly in proportion to the phase function, so only the albedo is left
+        let pdf = self.phase.evaluate(-outgoing.dot(&incoming));
+        return Some(BsdfSample::new(incoming, self.albedo, pdf));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, _hit_data: &HitData) -> Vector3f {
+        return self.phase.evaluate(-outgoing.dot(incoming)) * self.albedo;
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, _hit_data: &HitData) -> f32 {
+        return self.phase.evaluate(-outgoing.dot(incoming));
+    }
+
+    fn get_albedo(&self, _hit_data: &HitData) -> Vector3f {
+        return self.albedo;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_functions_integrate_to_one() {
+        let steps = 20000;
+        for phase in [PhaseFunction::Isotropic, PhaseFunction::HenyeyGreenstein(0.0), PhaseFunction::HenyeyGreenstein(0.6), PhaseFunction::HenyeyGreenstein(-0.3)] {
+            // Integrated over the sphere, with cos_theta uniform in [-1,1] and 2 PI around the axis
+            let total = (0..steps)
+                .map(|i| phase.evaluate(-1.0 + 2.0 * (i as f32 + 0.5) / steps as f32))
+                .sum::<f32>() * 2.0 / steps as f32 * 2.0 * PI;
+            assert!((total - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_samples_have_mean_cosine_g() {
+        let direction = Vector3f::new(0.0, 0.6, 0.8);
+        for g in [0.0, 0.7, -0.4] {
+            let phase = PhaseFunction::HenyeyGreenstein(g);
+            let samples = 50000;
+            let mean = (0..samples).map(|_| phase.sample(&direction).dot(&direction)).sum::<f32>() / samples as f32;
+            assert!((mean - g).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn phase_material_sample_matches_eval_and_pdf() {
+        let albedo = Vector3f::new(0.9, 0.5, 0.1);
+        let material = PhaseMaterial::new(albedo, PhaseFunction::HenyeyGreenstein(0.5));
+        let hit = HitData::new(1.0, Vector3f::zero(), Vector3f::new(0.0, 0.0, 1.0), &Vector3f::new(0.0, 0.0, -1.0), &Vector3f::new(0.0, 0.0, 1.0), &material);
+        let outgoing = Vector3f::new(0.0, 0.0, 1.0);
+        for _ in 0..100 {
+            let sample = material.sample(&outgoing, &hit).unwrap();
+            assert!((sample.incoming.magnitude() - 1.0).abs() < 1e-4);
+            assert!((sample.pdf - material.pdf(&outgoing, &sample.incoming, &hit)).abs() < 1e-4 * sample.pdf.max(1.0));
+            let eval = material.eval(&outgoing, &sample.incoming, &hit);
+            assert!((eval.x / sample.pdf - sample.weight.x).abs() < 1e-4);
+            assert!((eval.z / sample.pdf - sample.weight.z).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::microfacet::{
+    eval_dielectric, eval_reflection, eval_transmission, pdf_dielectric, pdf_reflection, pdf_transmission, sample_dielectric,
+    sample_reflection, sample_transmission, TrowbridgeReitz,
+};
+use crate::material::{albedo_at, fresnel_schlick, mixture_sample, sample_cosine_hemisphere, BsdfSample, Material};
+use crate::texture::{constant, SolidColor, Texture};
+use crate::vectorlib::{hit::HitData, vector3::*};
+
+// Reflectance at normal incidence of a dielectric with specular 1, the default specular of 0.5 gives 0.04 (glass, ior 1.5)
+const MAX_DIELECTRIC_REFLECTANCE : f32 = 0.08;
+// Reflectance of the clearcoat layer at normal incidence, a polyurethane like coat
+const CLEARCOAT_REFLECTANCE : f32 = 0.04;
+// Clearcoat alpha at gloss 0 and 1
+const CLEARCOAT_ALPHA_RANGE : [f32; 2] = [0.1, 0.001];
+
+// Disney's principled BSDF, one material that covers plastics, metals, fabrics, glass and paints
+// through a handful of parameters in [0,1]. Every parameter can be driven by a texture.
+// Each bounce picks one lobe at random: the clearcoat on top, then the metal or the dielectric
+// specular, then either refraction into the object or the diffuse base underneath. A lobe is picked
+// with roughly the share of light it reflects, so the weight of the bounce stays near its colour
+pub struct Principled {
+    base_color : Box<dyn Texture>,
+    // 0 is a dielectric, 1 is a metal whose reflection takes the base colour
+    metallic : Box<dyn Texture>,
+    // Perceptual roughness shared by the specular, diffuse and transmission lobes
+    roughness : Box<dyn Texture>,
+    // Strength of the dielectric highlight, 0.5 is a common 4% reflectance
+    specular : Box<dyn Texture>,
+    // Soft white rim at grazing angles, mostly for cloth
+    sheen : Box<dyn Texture>,
+    // How far the sheen is tinted towards the base colour
+    sheen_tint : Box<dyn Texture>,
+    // Strength of a second, clear, glossy layer on top
+    clearcoat : Box<dyn Texture>,
+    // 0 is a satin coat, 1 a mirror like one
+    clearcoat_gloss : Box<dyn Texture>,
+    // 0 is opaque, 1 lets the light not reflected by the specular pass into the object, tinted by the base colour
+    transmission : Box<dyn Texture>,
+    // Index of refraction used by transmission
+    ior : f32,
+}
+
+impl Principled{
+    // Rough, non metallic surface of the given colour, the builders below change the rest
+    pub fn new(base_color : Vector3f) -> Principled{
+        return Principled::textured(Box::new(SolidColor::new(base_color)));
+    }
+
+    pub fn textured(base_color : Box<dyn Texture>) -> Principled{
+        return Principled{
+            base_color,
+            metallic : constant(0.0),
+            roughness : constant(0.5),
+            specular : constant(0.5),
+            sheen : constant(0.0),
+            sheen_tint : constant(0.5),
+            clearcoat : constant(0.0),
+            clearcoat_gloss : constant(1.0),
+            transmission : constant(0.0),
+            ior : 1.5,
+        };
+    }
+
+    pub fn with_metallic(mut self, metallic : Box<dyn Texture>) -> Principled{
+        self.metallic = metallic;
+        return self;
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Principled{
+        self.roughness = roughness;
+        return self;
+    }
+
+    pub fn with_specular(mut self, specular : Box<dyn Texture>) -> Principled{
+        self.specular = specular;
+        return self;
+    }
+
+    pub fn with_sheen(mut self, sheen : Box<dyn Texture>, sheen_tint : Box<dyn Texture>) -> Principled{
+        self.sheen = sheen;
+        self.sheen_tint = sheen_tint;
+        return self;
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat : Box<dyn Texture>, clearcoat_gloss : Box<dyn Texture>) -> Principled{
+        self.clearcoat = clearcoat;
+        self.clearcoat_gloss = clearcoat_gloss;
+        return self;
+    }
+
+    pub fn with_transmission(mut self, transmission : Box<dyn Texture>, ior : f32) -> Principled{
+        self.transmission = transmission;
+        self.ior = ior;
+        return self;
+    }
+}
+
+// Parameters looked up from the textures at one hit
+struct Parameters {
+    base_color : Vector3f,
+    metallic : f32,
+    roughness : f32,
+    specular_reflectance : Vector3f,
+    sheen : Vector3f,
+    clearcoat : f32,
+    clearcoat_alpha : f32,
+    transmission : f32,
+}
+
+// Chance of each lobe being picked for a given outgoing direction, which is also its share of the BSDF
+struct Lobes {
+    clearcoat : f32,
+    metal : f32,
+    specular : f32,
+    transmission : f32,
+    diffuse : f32,
+    // Reflectance of the dielectric highlight towards the viewer, what the specular lobe was picked with
+    specular_probability : f32,
+}
+
+impl Principled{
+    fn parameters(&self, hit_data: &HitData) -> Parameters{
+        let base_color = albedo_at(self.base_color.as_ref(), hit_data);
+        let sheen_tint = self.sheen_tint.scalar_at(hit_data).clamp(0.0, 1.0);
+        let luminance = 0.2126 * base_color.x + 0.7152 * base_color.y + 0.0722 * base_color.z;
+        let tint = if luminance > 0.0 { base_color / luminance } else { Vector3f::one() };
+        let gloss = self.clearcoat_gloss.scalar_at(hit_data).clamp(0.0, 1.0);
+
+        return Parameters{
+            base_color,
+            metallic : self.metallic.scalar_at(hit_data).clamp(0.0, 1.0),
+            roughness : self.roughness.scalar_at(hit_data).clamp(0.0, 1.0),
+            specular_reflectance : Vector3f::uniform(MAX_DIELECTRIC_REFLECTANCE * self.specular.scalar_at(hit_data).clamp(0.0, 1.0)),
+            sheen : self.sheen.scalar_at(hit_data).max(0.0) * Vector3f::lerp(&Vector3f::one(), &tint, sheen_tint),
+            clearcoat : self.clearcoat.scalar_at(hit_data).clamp(0.0, 1.0),
+            clearcoat_alpha : CLEARCOAT_ALPHA_RANGE[0] + gloss * (CLEARCOAT_ALPHA_RANGE[1] - CLEARCOAT_ALPHA_RANGE[0]),
+            transmission : self.transmission.scalar_at(hit_data).clamp(0.0, 1.0),
+        };
+    }
+
+    // Clearcoat on top, picked by how much of the light it reflects straight back, then the metal or the
+    // dielectric highlight, then refraction into the object or the diffuse base underneath
+    fn lobes(parameters : &Parameters, cos_outgoing : f32) -> Lobes{
+        let clearcoat = parameters.clearcoat * fresnel_schlick(cos_outgoing, &Vector3f::uniform(CLEARCOAT_REFLECTANCE)).x;
+        let metal = (1.0 - clearcoat) * parameters.metallic;
+        let dielectric = (1.0 - clearcoat) * (1.0 - parameters.metallic);
+        let specular_probability = fresnel_schlick(cos_outgoing, &parameters.specular_reflectance).x;
+        let underneath = dielectric * (1.0 - specular_probability);
+        return Lobes{
+            clearcoat,
+            metal,
+            specular : dielectric * specular_probability,
+            transmission : underneath * parameters.transmission,
+            diffuse : underneath * (1.0 - parameters.transmission),
+            specular_probability,
+        };
+    }
+
+    // Leaving a transmissive object, only the rough glass interface applies
+    fn is_exiting(&self, parameters : &Parameters, hit_data: &HitData) -> bool{
+        return !hit_data.hit_front_face && parameters.transmission > 0.0;
+    }
+
+    // The dielectric highlight, picked with its reflectance towards the viewer and weighted by how that
+    // differs from the reflectance at the microfacet actually used
+    fn specular_fresnel(parameters : &Parameters, lobes : &Lobes) -> impl Fn(f32) -> Vector3f{
+        let (f0, probability) = (parameters.specular_reflectance, lobes.specular_probability.max(1e-6));
+        return move |cos : f32| fresnel_schlick(cos, &f0) / probability;
+    }
+
+    // Burley diffuse with sheen on top, over pi so that a cosine sample's weight is the value times pi
+    fn diffuse(parameters : &Parameters, outgoing : &Vector3f, incoming : &Vector3f) -> Vector3f{
+        let half = (*incoming + *outgoing).unit_vector();
+        let cos_half = incoming.dot(&half);
+        let diffuse = diffuse_factor(parameters.roughness, outgoing.z, incoming.z, cos_half) * parameters.base_color;
+        let sheen = (1.0 - cos_half).powi(5) * parameters.sheen;
+        return (diffuse + sheen) / PI;
+    }
+}
+
+// Burley's diffuse with retro-reflection at grazing angles on rough surfaces, divided by its directional
+// albedo so that a white base sends back all the light the specular leaves, at any roughness and angle
+fn diffuse_factor(roughness : f32, cos_outgoing : f32, cos_incoming : f32, cos_half : f32) -> f32{
+    let f90 = 0.5 * roughness + 2.0 * cos_half * cos_half * roughness;
+    let towards_light = 1.0 + (f90 - 1.0) * (1.0 - cos_incoming).powi(5);
+    let towards_view = 1.0 + (f90 - 1.0) * (1.0 - cos_outgoing).powi(5);
+    return towards_light * towards_view / diffuse_albedo(roughness, cos_outgoing);
+}
+
+// Cosine weighted average of Burley's factor over the incoming hemisphere, in closed form. With
+// 2 cos_half^2 = 1 + outgoing . incoming, f90 - 1 is a + b cos_incoming + d sin_incoming cos(phi), and
+// the integrals left after averaging over phi are moments of (1 - cos_incoming)^5
+fn diffuse_albedo(roughness : f32, cos_outgoing : f32) -> f32{
+    let sin2_outgoing = (1.0 - cos_outgoing * cos_outgoing).max(0.0);
+    let (a, b, d2) = (1.5 * roughness - 1.0, roughness * cos_outgoing, roughness * roughness * sin2_outgoing);
+    let towards_view = (1.0 - cos_outgoing).powi(5);
+    // Integrals of cos_incoming^n (1 - cos_incoming)^5 over the cosine weighted hemisphere, n = 0, 1, 2
+    let (k0, k1, k2) = (1.0 / 21.0, 1.0 / 84.0, 1.0 / 252.0);
+
+    let light = a * k0 + b * k1;
+    let view = a + b * 2.0 / 3.0;
+    let both = a * a * k0 + 2.0 * a * b * k1 + b * b * k2 + d2 / 2.0 * (k0 - k2);
+    return 1.0 + light + towards_view * (view + both);
+}
+
+// The BSDF is the mix of its lobes, each weighted by the chance of picking it. A sample comes from one
+// lobe and takes the value and density of the whole mix, like the other mixes
+impl Material for Principled{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let parameters = self.parameters(hit_data);
+        let distribution = TrowbridgeReitz::from_roughness(parameters.roughness);
+        if self.is_exiting(&parameters, hit_data) {
+            return sample_dielectric(outgoing, &distribution, 1.0 / self.ior);
+        }
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+
+        let lobes = Principled::lobes(&parameters, outgoing.z);
+        let mut pick = random::<f32>();
+        let sample = 'lobe: {
+            if pick < lobes.clearcoat {
+                let coat = TrowbridgeReitz::new(parameters.clearcoat_alpha, parameters.clearcoat_alpha);
+                break 'lobe sample_reflection(outgoing, &coat, |_| Vector3f::one())?;
+            }
+            pick -= lobes.clearcoat;
+            if pick < lobes.metal {
+                let base_color = parameters.base_color;
+                break 'lobe sample_reflection(outgoing, &distribution, |cos| fresnel_schlick(cos, &base_color))?;
+            }
+            pick -= lobes.metal;
+            if pick < lobes.specular {
+                break 'lobe sample_reflection(outgoing, &distribution, Principled::specular_fresnel(&parameters, &lobes))?;
+            }
+            pick -= lobes.specular;
+            if pick < lobes.transmission {
+                // Light entering the object, bent through the rough interface and tinted by the base colour
+                let mut sample = sample_transmission(outgoing, &distribution, self.ior)?;
+                sample.weight = sample.weight.multiply_element_wise(&parameters.base_color);
+                break 'lobe sample;
+            }
+
+            let incoming = sample_cosine_hemisphere(random::<f32>(), random::<f32>());
+            let weight = PI * Principled::diffuse(&parameters, outgoing, &incoming);
+            BsdfSample::new(incoming, weight, incoming.z / PI)
+        };
+
+        return mixture_sample(self, outgoing, hit_data, sample);
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        let parameters = self.parameters(hit_data);
+        let distribution = TrowbridgeReitz::from_roughness(parameters.roughness);
+        if self.is_exiting(&parameters, hit_data) {
+            return eval_dielectric(outgoing, incoming, &distribution, 1.0 / self.ior);
+        }
+        if outgoing.z <= 0.0 {
+            return Vector3f::zero();
+        }
+
+        let lobes = Principled::lobes(&parameters, outgoing.z);
+        if incoming.z < 0.0 {
+            let transmission = eval_transmission(outgoing, incoming, &distribution, self.ior);
+            return lobes.transmission * transmission.multiply_element_wise(&parameters.base_color);
+        }
+
+        let coat = TrowbridgeReitz::new(parameters.clearcoat_alpha, parameters.clearcoat_alpha);
+        let base_color = parameters.base_color;
+        let clearcoat = eval_reflection(outgoing, incoming, &coat, |_| Vector3f::one());
+        let metal = eval_reflection(outgoing, incoming, &distribution, |cos| fresnel_schlick(cos, &base_color));
+        let specular = eval_reflection(outgoing, incoming, &distribution, Principled::specular_fresnel(&parameters, &lobes));
+        let diffuse = Principled::diffuse(&parameters, outgoing, incoming);
+        return lobes.clearcoat * clearcoat + lobes.metal * metal + lobes.specular * specular + lobes.diffuse * diffuse;
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        let parameters = self.parameters(hit_data);
+        let distribution = TrowbridgeReitz::from_roughness(parameters.roughness);
+        if self.is_exiting(&parameters, hit_data) {
+            return pdf_dielectric(outgoing, incoming, &distribution, 1.0 / self.ior);
+        }
+        if outgoing.z <= 0.0 {
+            return 0.0;
+        }
+
+        let lobes = Principled::lobes(&parameters, outgoing.z);
+        if incoming.z < 0.0 {
+            return lobes.transmission * pdf_transmission(outgoing, incoming, &distribution, self.ior);
+        }
+
+        let coat = TrowbridgeReitz::new(parameters.clearcoat_alpha, parameters.clearcoat_alpha);
+        let clearcoat = pdf_reflection(outgoing, incoming, &coat);
+        let specular = pdf_reflection(outgoing, incoming, &distribution);
+        let diffuse = incoming.z / PI;
+        return lobes.clearcoat * clearcoat + (lobes.metal + lobes.specular) * specular + lobes.diffuse * diffuse;
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.base_color.as_ref(), hit_data);
+    }
+}
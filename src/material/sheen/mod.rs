@@ -0,0 +1,143 @@
+use std::f32::consts::PI;
+
+use rand::random;
+
+use crate::material::microfacet::reflect_local;
+use crate::material::{albedo_at, sample_cosine_hemisphere, BsdfSample, Material};
+use crate::texture::{constant, SolidColor, Texture};
+use crate::vectorlib::{hit::HitData, vector3::*};
+
+// Share of samples taken from the sheen's own microfacets, the rest are cosine weighted for the base
+// and for the grazing reflections the microfacets would send below the surface
+const MICROFACET_SAMPLE_PROBABILITY : f32 = 0.5;
+
+// Velvet and other fabrics, whose fibres stand up from the surface and catch the light at grazing
+// angles, giving a soft bright rim. Uses the "Charlie" distribution of Estevez and Kulla with
+// Neubelt's visibility, over an optional Lambertian base for the colour of the cloth itself
+pub struct Sheen {
+    color : Box<dyn Texture>,
+    // In (0,1], low gives a tight rim and high spreads it towards the middle
+    roughness : f32,
+    base : Box<dyn Texture>,
+}
+
+impl Sheen{
+    pub fn new(color : Vector3f, roughness : f32) -> Sheen{
+        return Sheen::textured(Box::new(SolidColor::new(color)), roughness);
+    }
+
+    pub fn textured(color : Box<dyn Texture>, roughness : f32) -> Sheen{
+        return Sheen{color, roughness : roughness.clamp(0.01, 1.0), base : constant(0.0)};
+    }
+
+    // Diffuse colour under the sheen, black by default. A dark base under a bright sheen makes velvet,
+    // keep the two adding up to less than 1 or the cloth will reflect more light than it gets
+    pub fn with_base(mut self, base : Box<dyn Texture>) -> Sheen{
+        self.base = base;
+        return self;
+    }
+
+    // Density of microfacet normals, with the fibres mostly lying sideways
+    fn distribution(&self, cos_half : f32) -> f32{
+        let inverse_roughness = 1.0 / self.roughness;
+        let sin_half = (1.0 - cos_half * cos_half).max(0.0).sqrt();
+        return (2.0 + inverse_roughness) * sin_half.powf(inverse_roughness) / (2.0 * PI);
+    }
+
+    // Microfacet normal with density D(h) cos(theta_h), which has a closed form inverse
+    fn sample_half(&self, u1 : f32, u2 : f32) -> Vector3f{
+        let sin_half = u1.powf(1.0 / (2.0 + 1.0 / self.roughness));
+        let cos_half = (1.0 - sin_half * sin_half).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        return Vector3f::new(sin_half * phi.cos(), sin_half * phi.sin(), cos_half);
+    }
+
+    fn sheen(&self, outgoing : &Vector3f, incoming : &Vector3f) -> f32{
+        let half = (*outgoing + *incoming).unit_vector();
+        let visibility = 1.0 / (4.0 * (incoming.z + outgoing.z - incoming.z * outgoing.z));
+        return self.distribution(half.z) * visibility;
+    }
+
+    fn microfacet_pdf(&self, outgoing : &Vector3f, incoming : &Vector3f) -> f32{
+        let half = (*outgoing + *incoming).unit_vector();
+        return self.distribution(half.z) * half.z / (4.0 * outgoing.dot(&half));
+    }
+}
+
+impl Material for Sheen{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+        let incoming = if random::<f32>() < MICROFACET_SAMPLE_PROBABILITY {
+            reflect_local(outgoing, &self.sample_half(random::<f32>(), random::<f32>()))
+        } else {
+            sample_cosine_hemisphere(random::<f32>(), random::<f32>())
+        };
+        if incoming.z <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(outgoing, &incoming, hit_data);
+        let weight = self.eval(outgoing, &incoming, hit_data) * incoming.z / pdf;
+        return Some(BsdfSample::new(incoming, weight, pdf));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return Vector3f::zero();
+        }
+        let diffuse = albedo_at(self.base.as_ref(), hit_data) / PI;
+        return diffuse + self.sheen(outgoing, incoming) * self.color.value_at(hit_data);
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, _hit_data: &HitData) -> f32 {
+        if outgoing.z <= 0.0 || incoming.z <= 0.0 {
+            return 0.0;
+        }
+        let cosine = incoming.z / PI;
+        return MICROFACET_SAMPLE_PROBABILITY * self.microfacet_pdf(outgoing, incoming) + (1.0 - MICROFACET_SAMPLE_PROBABILITY) * cosine;
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return albedo_at(self.base.as_ref(), hit_data) + self.color.value_at(hit_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charlie_distribution_projects_to_unit_area() {
+        for roughness in [0.2, 0.5, 1.0] {
+            let sheen = Sheen::new(Vector3f::one(), roughness);
+            // Over the hemisphere of normals, d(omega) = 2 pi sin(theta) d(theta)
+            let steps = 20000;
+            let projected = (0..steps).map(|i| {
+                let theta = (i as f32 + 0.5) / steps as f32 * PI / 2.0;
+                sheen.distribution(theta.cos()) * theta.cos() * theta.sin()
+            }).sum::<f32>() * 2.0 * PI * (PI / 2.0 / steps as f32);
+            assert!((projected - 1.0).abs() < 1e-3);
+
+            // Sampled normals have the same mean cosine as the distribution
+            let samples = 20000;
+            let sampled = (0..samples).map(|_| sheen.sample_half(random::<f32>(), random::<f32>()).z).sum::<f32>() / samples as f32;
+            let expected = (0..steps).map(|i| {
+                let theta = (i as f32 + 0.5) / steps as f32 * PI / 2.0;
+                sheen.distribution(theta.cos()) * theta.cos() * theta.cos() * theta.sin()
+            }).sum::<f32>() * 2.0 * PI * (PI / 2.0 / steps as f32);
+            assert!((sampled - expected).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn sheen_glows_at_grazing_angles() {
+        let sheen = Sheen::new(Vector3f::one(), 0.3);
+        let head_on = Vector3f::unit_z();
+        let tilted = Vector3f::new(0.5, 0.0, 0.75f32.sqrt());
+        // Light coming back along a grazing view meets the fibres side on
+        let grazing = Vector3f::new(0.995f32.sqrt(), 0.0, 0.05f32.sqrt()).unit_vector();
+        assert!(sheen.sheen(&grazing, &grazing) > 10.0 * sheen.sheen(&head_on, &tilted));
+        assert_eq!(sheen.sheen(&head_on, &head_on), 0.0);
+    }
+}
@@ -0,0 +1,237 @@
+use rand::random;
+
+use crate::material::microfacet::{sample_dielectric, TrowbridgeReitz};
+use crate::material::phase::PhaseFunction;
+use crate::material::{BsdfSample, Material};
+use crate::texture::{constant, Texture};
+use crate::vectorlib::{hit::{HitData, HittableList}, ray::Ray, vector3::*};
+
+// Longest walk followed inside an object before the light is counted as absorbed
+const MAX_WALK_STEPS : u32 = 1024;
+// Gap left after each boundary hit so the walk doesn't find the same point again
+const WALK_TOLERANCE : f32 = 0.0001;
+
+// Translucent material such as skin, wax, milk or marble. Light refracts in through a dielectric
+// surface and takes a random walk through the inside, scattering and being absorbed as in a medium,
+// until it reaches the surface again and refracts out somewhere else. Everything is per colour channel,
+// red light usually travels much further than blue. The walk intersects the scene, so the object must
+// be closed and anything else inside it is treated as part of its boundary
+pub struct Subsurface {
+    // Chance of scattering rather than being absorbed at each interaction
+    albedo : Vector3f,
+    // Average distance between interactions, in scene units
+    mean_free_path : Vector3f,
+    // Henyey-Greenstein g of each channel, positive scatters forwards
+    anisotropy : Vector3f,
+    ior : f32,
+    // Perceptual roughness of the surface
+    roughness : Box<dyn Texture>,
+}
+
+impl Subsurface{
+    pub fn new(albedo : Vector3f, mean_free_path : Vector3f, anisotropy : Vector3f, ior : f32) -> Subsurface{
+        return Subsurface{albedo, mean_free_path, anisotropy, ior, roughness : constant(0.0)};
+    }
+
+    // From absorption and scattering coefficients per unit distance, the form measured data comes in
+    pub fn from_coefficients(absorption : Vector3f, scattering : Vector3f, anisotropy : Vector3f, ior : f32) -> Subsurface{
+        let extinction = absorption + scattering;
+        let albedo = Vector3f::new(scattering.x / extinction.x, scattering.y / extinction.y, scattering.z / extinction.z);
+        let mean_free_path = Vector3f::new(1.0 / extinction.x, 1.0 / extinction.y, 1.0 / extinction.z);
+        return Subsurface::new(albedo, mean_free_path, anisotropy, ior);
+    }
+
+    // Measured materials from Jensen et al. 2001, given in millimetres. units_per_mm is how many scene
+    // units one millimetre is, translucency depends heavily on the size of the object
+    fn measured(absorption : Vector3f, reduced_scattering : Vector3f, ior : f32, units_per_mm : f32) -> Subsurface{
+        return Subsurface::from_coefficients(absorption / units_per_mm, reduced_scattering / units_per_mm, Vector3f::zero(), ior);
+    }
+
+    pub fn marble(units_per_mm : f32) -> Subsurface{
+        return Subsurface::measured(Vector3f::new(0.0021, 0.0041, 0.0071), Vector3f::new(2.19, 2.62, 3.00), 1.5, units_per_mm);
+    }
+
+    pub fn skin(units_per_mm : f32) -> Subsurface{
+        return Subsurface::measured(Vector3f::new(0.032, 0.17, 0.48), Vector3f::new(0.74, 0.88, 1.01), 1.3, units_per_mm);
+    }
+
+    pub fn milk(units_per_mm : f32) -> Subsurface{
+        return Subsurface::measured(Vector3f::new(0.0011, 0.0024, 0.014), Vector3f::new(2.55, 3.21, 3.77), 1.3, units_per_mm);
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> Subsurface{
+        self.roughness = roughness;
+        return self;
+    }
+
+    fn relative_ior(&self, hit_data: &HitData) -> f32{
+        return if hit_data.hit_front_face { self.ior } else { 1.0 / self.ior };
+    }
+
+    fn extinction(&self) -> Vector3f{
+        return Vector3f::new(1.0 / self.mean_free_path.x, 1.0 / self.mean_free_path.y, 1.0 / self.mean_free_path.z);
+    }
+
+    // Crossing the surface at a hit, outgoing and the result are in world space
+    fn sample_boundary(&self, direction : &Vector3f, hit_data: &HitData) -> Option<(Vector3f, Vector3f)>{
+        let frame = hit_data.shading_frame();
+        let sample = self.sample(&frame.vector_to_local(&-*direction), hit_data)?;
+        return Some((sample.weight, frame.vector_to_world(&sample.incoming)));
+    }
+
+    // Walks from a point just inside the surface until the light leaves, returns the weight of the
+    // walk and the ray leaving the object. Distances are sampled with one channel, picked in proportion
+    // to how much light each still carries, and weighted by the mix of all three so the others stay correct
+    fn walk(&self, ray_in: &Ray, start : &Vector3f, direction : &Vector3f, meshes : &HittableList) -> Option<(Vector3f, Ray)>{
+        let extinction = self.extinction();
+        let mut throughput = Vector3f::one();
+        let mut ray = Ray::new_at_time(*start, *direction, ray_in.time());
+
+        for _ in 0..MAX_WALK_STEPS {
+            let boundary = meshes.hit(&ray, WALK_TOLERANCE, f32::INFINITY)?;
+            let channel_probabilities = channel_probabilities_of(&throughput)?;
+            let distance = -(1.0 - random::<f32>()).ln() / extinction.axis(pick_channel(&channel_probabilities));
+            let transmittance = |distance : f32| Vector3f::new((-extinction.x * distance).exp(), (-extinction.y * distance).exp(), (-extinction.z * distance).exp());
+
+            if distance >= boundary.t {
+                // Reached the surface, the chance of that is the mixed transmittance
+                let transmittance = transmittance(boundary.t);
+                throughput = throughput.multiply_element_wise(&transmittance) / channel_probabilities.dot(&transmittance);
+
+                let (weight, next) = self.sample_boundary(ray.direction(), &boundary)?;
+                throughput = throughput.multiply_element_wise(&weight);
+                if next.dot(&boundary.normal) < 0.0 {
+                    return Some((throughput, Ray::new_at_time(boundary.at, next, ray_in.time())));
+                }
+                // Reflected back inside
+                ray = Ray::new_at_time(boundary.at, next, ray_in.time());
+                continue;
+            }
+
+            // Scattering event, weighted by the scattering coefficient over the mixed distance pdf
+            let transmittance = transmittance(distance);
+            let pdf = channel_probabilities.dot(&extinction.multiply_element_wise(&transmittance));
+            let scattering = self.albedo.multiply_element_wise(&extinction);
+            throughput = throughput.multiply_element_wise(&scattering.multiply_element_wise(&transmittance)) / pdf;
+
+            // Dark walks are ended early, survivors are brightened to make up for the ones cut short.
+            // A black albedo leaves nothing to carry on with
+            let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+            if survival <= 0.0 || random::<f32>() >= survival {
+                return None;
+            }
+            throughput = throughput / survival;
+
+            // Direction from one channel's phase function picked the same way, weighted by each channel's over the mix
+            let channel_probabilities = channel_probabilities_of(&throughput)?;
+            let phases = [0, 1, 2].map(|axis| PhaseFunction::HenyeyGreenstein(self.anisotropy.axis(axis)));
+            let old_direction = ray.direction().unit_vector();
+            let new_direction = phases[pick_channel(&channel_probabilities)].sample(&old_direction);
+            let cos_theta = old_direction.dot(&new_direction);
+            let values = Vector3f::new(phases[0].evaluate(cos_theta), phases[1].evaluate(cos_theta), phases[2].evaluate(cos_theta));
+            throughput = throughput.multiply_element_wise(&values) / channel_probabilities.dot(&values);
+            ray = Ray::new_at_time(ray.at(distance), new_direction, ray_in.time());
+        }
+        return None;
+    }
+}
+
+// Chance of picking each channel, in proportion to the light it carries. None once there is none left
+fn channel_probabilities_of(throughput : &Vector3f) -> Option<Vector3f>{
+    let total = throughput.x + throughput.y + throughput.z;
+    if total.is_nan() || total <= 0.0 {
+        return None;
+    }
+    return Some(*throughput / total);
+}
+
+// Index of a colour channel picked with the given probabilities
+fn pick_channel(probabilities : &Vector3f) -> usize{
+    let pick = random::<f32>();
+    if pick < probabilities.x {
+        return 0;
+    }
+    if pick < probabilities.x + probabilities.y {
+        return 1;
+    }
+    return 2;
+}
+
+impl Material for Subsurface{
+    // The surface on its own, a dielectric boundary. The walk inside needs the scene and happens in scatter_in_world
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        let distribution = TrowbridgeReitz::from_roughness(self.roughness.scalar_at(hit_data));
+        let mut sample = sample_dielectric(outgoing, &distribution, self.relative_ior(hit_data))?;
+        sample.is_delta = true;
+        return Some(sample);
+    }
+
+    fn get_albedo(&self, _hit_data: &HitData) -> Vector3f {
+        return self.albedo;
+    }
+
+    fn scatter_in_world(&self, ray_in: &Ray, hit_data: &HitData, meshes: &HittableList) -> Option<(Vector3f, Ray)> {
+        let (weight, direction) = self.sample_boundary(&ray_in.direction().unit_vector(), hit_data)?;
+        // Reflected off the surface, or a ray that started inside the object leaving it
+        if direction.dot(&hit_data.normal) > 0.0 || !hit_data.hit_front_face {
+            return Some((weight, Ray::new_at_time(hit_data.at, direction, ray_in.time())));
+        }
+        let (walk_weight, ray_out) = self.walk(ray_in, &hit_data.at, &direction, meshes)?;
+        return Some((weight.multiply_element_wise(&walk_weight), ray_out));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectorlib::sphere::Sphere;
+
+    fn walk_through(material : Subsurface, mut check : impl FnMut(Option<(Vector3f, Ray)>)) {
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Vector3f::zero(), 1.0, Box::new(material)));
+        let ray = Ray::new(Vector3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let hit = world.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        for _ in 0..500 {
+            check(hit.material.scatter_in_world(&ray, &hit, &world));
+        }
+    }
+
+    #[test]
+    fn white_walks_come_back_out_with_all_their_light() {
+        // Without a Fresnel boundary or absorption walks leave the sphere with all their light. The odd one
+        // scatters closer to the surface than the walk tolerance and misses its way out
+        let material = Subsurface::new(Vector3f::one(), Vector3f::uniform(0.2), Vector3f::uniform(0.3), 1.0);
+        let mut lost = 0;
+        walk_through(material, |scattered| {
+            let (weight, ray_out) = match scattered {
+                Some(scattered) => scattered,
+                None => {
+                    lost += 1;
+                    return;
+                }
+            };
+            assert!((weight.x - 1.0).abs() < 1e-3 && (weight.z - 1.0).abs() < 1e-3);
+            assert!((ray_out.origin().magnitude() - 1.0).abs() < 1e-3);
+            assert!(ray_out.direction().dot(ray_out.origin()) > 0.0);
+        });
+        assert!(lost < 5);
+    }
+
+    #[test]
+    fn black_walks_are_absorbed_without_nan() {
+        let material = Subsurface::from_coefficients(Vector3f::one(), Vector3f::zero(), Vector3f::zero(), 1.5);
+        walk_through(material, |scattered| {
+            if let Some((weight, _)) = scattered {
+                assert!(!weight.x.is_nan() && !weight.y.is_nan() && !weight.z.is_nan());
+            }
+        });
+
+        // One channel dark, the others still carry light out
+        let material = Subsurface::from_coefficients(Vector3f::new(1.0, 0.0, 0.0), Vector3f::new(0.0, 5.0, 5.0), Vector3f::zero(), 1.0);
+        walk_through(material, |scattered| {
+            if let Some((weight, _)) = scattered {
+                assert!(weight.x.is_finite() && weight.y.is_finite() && weight.x >= 0.0);
+            }
+        });
+    }
+}
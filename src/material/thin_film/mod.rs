@@ -0,0 +1,299 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+use rand::random;
+
+use crate::material::microfacet::{eval_reflection, pdf_reflection, sample_reflection, TrowbridgeReitz};
+use crate::material::{mixture_sample, BsdfSample, Material};
+use crate::texture::{constant, Texture};
+use crate::vectorlib::{hit::HitData, vector3::*};
+
+// Wavelength bands in nanometres that the red, green and blue channels average over. Averaging a band
+// rather than taking one wavelength lets thick films wash out to white the way real ones do
+const CHANNEL_BANDS : [[f32; 2]; 3] = [[600.0, 700.0], [500.0, 600.0], [400.0, 500.0]];
+const WAVELENGTHS_PER_CHANNEL : u32 = 4;
+
+// Just enough complex arithmetic for the Fresnel equations of absorbing media
+#[derive(Copy, Clone)]
+struct Complex {
+    re : f32,
+    im : f32,
+}
+
+impl Complex {
+    fn new(re : f32, im : f32) -> Complex {
+        return Complex{re, im};
+    }
+
+    fn real(re : f32) -> Complex {
+        return Complex::new(re, 0.0);
+    }
+
+    fn norm_squared(&self) -> f32 {
+        return self.re * self.re + self.im * self.im;
+    }
+
+    // Principal root, with a non-negative real part
+    fn sqrt(&self) -> Complex {
+        let modulus = self.norm_squared().sqrt();
+        let re = (0.5 * (modulus + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (modulus - self.re)).max(0.0).sqrt();
+        return Complex::new(re, if self.im < 0.0 { -im } else { im });
+    }
+
+    // e^(i z)
+    fn exp_i(&self) -> Complex {
+        let magnitude = (-self.im).exp();
+        return Complex::new(magnitude * self.re.cos(), magnitude * self.re.sin());
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other : Complex) -> Complex {
+        return Complex::new(self.re + other.re, self.im + other.im);
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other : Complex) -> Complex {
+        return Complex::new(self.re - other.re, self.im - other.im);
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other : Complex) -> Complex {
+        return Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re);
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other : Complex) -> Complex {
+        let denominator = other.norm_squared();
+        return Complex::new((self.re * other.re + self.im * other.im) / denominator, (self.im * other.re - self.re * other.im) / denominator);
+    }
+}
+
+// Amplitude reflection coefficients of one interface for s and p polarised light. q is n cos(theta)
+// of each side, which carries the angle through both media whether or not they absorb
+fn interface_reflection(n_i : Complex, q_i : Complex, n_t : Complex, q_t : Complex) -> (Complex, Complex) {
+    let s = (q_i - q_t) / (q_i + q_t);
+    let (n_i2, n_t2) = (n_i * n_i, n_t * n_t);
+    let p = (n_t2 * q_i - n_i2 * q_t) / (n_t2 * q_i + n_i2 * q_t);
+    return (s, p);
+}
+
+// Reflectance of a film of the given thickness and ior between an incident medium and a substrate,
+// for one wavelength. Light bouncing back and forth inside the film is summed in closed form (Airy),
+// and where the bounces arrive in or out of phase the colour brightens or cancels
+fn film_reflectance(cos_incident : f32, incident_ior : f32, film_ior : f32, thickness : f32, substrate : Complex, wavelength : f32) -> f32 {
+    let n1 = Complex::real(incident_ior);
+    let n2 = Complex::real(film_ior);
+    // Snell's invariant n sin(theta), the same in every layer
+    let invariant = incident_ior * incident_ior * (1.0 - cos_incident * cos_incident);
+    let q = |n : Complex| (n * n - Complex::real(invariant)).sqrt();
+    let (q1, q2, q3) = (Complex::real(incident_ior * cos_incident), q(n2), q(substrate));
+
+    let (top_s, top_p) = interface_reflection(n1, q1, n2, q2);
+    let (bottom_s, bottom_p) = interface_reflection(n2, q2, substrate, q3);
+    // Phase picked up by one round trip through the film
+    let phase = (Complex::real(4.0 * PI * thickness / wavelength) * q2).exp_i();
+    let airy = |top : Complex, bottom : Complex| (top + bottom * phase) / (Complex::real(1.0) + top * bottom * phase);
+    let reflectance = 0.5 * (airy(top_s, bottom_s).norm_squared() + airy(top_p, bottom_p).norm_squared());
+    return reflectance.clamp(0.0, 1.0);
+}
+
+// Reflectance of a thin film over a substrate of complex index eta + ik per channel, each channel averaged
+// over its band of wavelengths. A film thickness of 0 gives the substrate's own Fresnel reflectance
+pub fn fresnel_thin_film(cos_incident : f32, incident_ior : f32, film_ior : f32, thickness : f32, eta : &Vector3f, k : &Vector3f) -> Vector3f {
+    let cos_incident = cos_incident.clamp(0.0, 1.0);
+    let mut reflectance = Vector3f::zero();
+    for (channel, band) in CHANNEL_BANDS.iter().enumerate() {
+        let substrate = Complex::new(eta.axis(channel), k.axis(channel));
+        let mut total = 0.0;
+        for i in 0..WAVELENGTHS_PER_CHANNEL {
+            let wavelength = band[0] + (band[1] - band[0]) * (i as f32 + 0.5) / WAVELENGTHS_PER_CHANNEL as f32;
+            total += film_reflectance(cos_incident, incident_ior, film_ior, thickness, substrate, wavelength);
+        }
+        *reflectance.axis_mut(channel) = total / WAVELENGTHS_PER_CHANNEL as f32;
+    }
+    return reflectance;
+}
+
+pub enum FilmSubstrate {
+    // Clear material with this ior that light passes into, 1 for a film with air on both sides like a soap bubble
+    Dielectric(f32),
+    // Opaque metal with complex index eta + ik per channel
+    Conductor { eta : Vector3f, k : Vector3f },
+}
+
+// Iridescent thin film over a dielectric or a conductor, such as soap bubbles, oil on water, or the
+// heat tint on titanium and steel. Light reflecting off the top and the bottom of the film interferes,
+// so the colour shifts with the film's thickness and the viewing angle. Varying the thickness with a
+// texture gives the swirling bands of a bubble
+pub struct ThinFilm {
+    substrate : FilmSubstrate,
+    // Film thickness in nanometres, visible interference colours appear from about 100 to 1000
+    thickness : Box<dyn Texture>,
+    film_ior : f32,
+    // Perceptual roughness of the reflection, transmission into a dielectric substrate stays smooth
+    roughness : Box<dyn Texture>,
+}
+
+impl ThinFilm{
+    pub fn new(substrate : FilmSubstrate, thickness : f32, film_ior : f32) -> ThinFilm{
+        return ThinFilm{substrate, thickness : constant(thickness), film_ior, roughness : constant(0.0)};
+    }
+
+    pub fn dielectric(ior : f32, thickness : f32, film_ior : f32) -> ThinFilm{
+        return ThinFilm::new(FilmSubstrate::Dielectric(ior), thickness, film_ior);
+    }
+
+    pub fn conductor(eta : Vector3f, k : Vector3f, thickness : f32, film_ior : f32) -> ThinFilm{
+        return ThinFilm::new(FilmSubstrate::Conductor{eta, k}, thickness, film_ior);
+    }
+
+    // Soapy water with air on both sides, meant for a thin closed shell such as a sphere
+    pub fn soap_bubble(thickness : f32) -> ThinFilm{
+        return ThinFilm::dielectric(1.0, thickness, 1.33);
+    }
+
+    // Film thickness in nanometres from the texture's channel average, e.g. noise blending two uniform colours
+    pub fn with_thickness(mut self, thickness : Box<dyn Texture>) -> ThinFilm{
+        self.thickness = thickness;
+        return self;
+    }
+
+    pub fn with_roughness(mut self, roughness : Box<dyn Texture>) -> ThinFilm{
+        self.roughness = roughness;
+        return self;
+    }
+
+    fn distribution(&self, hit_data: &HitData) -> TrowbridgeReitz{
+        return TrowbridgeReitz::from_roughness(self.roughness.scalar_at(hit_data));
+    }
+
+    // Iors on either side of the film, the substrate as a complex index. Leaving a dielectric the film
+    // is seen from underneath, with the substrate as the incident medium and air beyond it
+    fn media(&self, hit_data: &HitData) -> (f32, Vector3f, Vector3f){
+        return match &self.substrate {
+            FilmSubstrate::Dielectric(ior) if !hit_data.hit_front_face => (*ior, Vector3f::one(), Vector3f::zero()),
+            FilmSubstrate::Dielectric(ior) => (1.0, Vector3f::uniform(*ior), Vector3f::zero()),
+            FilmSubstrate::Conductor{eta, k} => (1.0, *eta, *k),
+        };
+    }
+
+    fn reflectance(&self, cos_incident : f32, hit_data: &HitData) -> Vector3f{
+        let (incident_ior, eta, k) = self.media(hit_data);
+        let thickness = self.thickness.scalar_at(hit_data).max(0.0);
+        return fresnel_thin_film(cos_incident, incident_ior, self.film_ior, thickness, &eta, &k);
+    }
+
+    // Chance of reflecting rather than passing into the substrate, 1 for conductors
+    fn reflect_probability(&self, cos_outgoing : f32, hit_data: &HitData) -> f32{
+        if let FilmSubstrate::Conductor{..} = self.substrate {
+            return 1.0;
+        }
+        let reflectance = self.reflectance(cos_outgoing, hit_data);
+        return (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+    }
+}
+
+impl Material for ThinFilm{
+    fn sample(&self, outgoing: &Vector3f, hit_data: &HitData) -> Option<BsdfSample> {
+        if outgoing.z <= 0.0 {
+            return None;
+        }
+        let reflect_probability = self.reflect_probability(outgoing.z, hit_data);
+        if random::<f32>() < reflect_probability {
+            let fresnel = |cos : f32| self.reflectance(cos, hit_data) / reflect_probability;
+            let sample = sample_reflection(outgoing, &self.distribution(hit_data), fresnel)?;
+            return mixture_sample(self, outgoing, hit_data, sample);
+        }
+
+        // The film is too thin to bend the light, so it refracts into the substrate as without it
+        let (incident_ior, eta, _) = self.media(hit_data);
+        let incoming = (-*outgoing).get_refracted(&Vector3f::unit_z(), incident_ior / eta.x)?;
+        let transmittance = Vector3f::one() - self.reflectance(outgoing.z, hit_data);
+        return Some(BsdfSample::delta(incoming.unit_vector(), transmittance / (1.0 - reflect_probability)));
+    }
+
+    fn eval(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> Vector3f {
+        return eval_reflection(outgoing, incoming, &self.distribution(hit_data), |cos| self.reflectance(cos, hit_data));
+    }
+
+    fn pdf(&self, outgoing: &Vector3f, incoming: &Vector3f, hit_data: &HitData) -> f32 {
+        let reflect_probability = self.reflect_probability(outgoing.z, hit_data);
+        return reflect_probability * pdf_reflection(outgoing, incoming, &self.distribution(hit_data));
+    }
+
+    fn get_albedo(&self, hit_data: &HitData) -> Vector3f {
+        return self.reflectance(1.0, hit_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{fresnel_conductor, fresnel_dielectric};
+
+    #[test]
+    fn bare_substrate_without_a_film() {
+        for cos in [1.0, 0.7, 0.3] {
+            let glass = fresnel_thin_film(cos, 1.0, 1.33, 0.0, &Vector3f::uniform(1.5), &Vector3f::zero());
+            assert!((glass.x - fresnel_dielectric(cos, 1.0 / 1.5)).abs() < 1e-4);
+
+            let (eta, k) = (Vector3f::new(0.143, 0.374, 1.442), Vector3f::new(3.983, 2.385, 1.603));
+            let gold = fresnel_thin_film(cos, 1.0, 1.33, 0.0, &eta, &k);
+            let expected = fresnel_conductor(cos, &eta, &k);
+            assert!((gold.x - expected.x).abs() < 1e-3 && (gold.z - expected.z).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn quarter_and_half_wave_films_match_reference_values() {
+        let (wavelength, glass) = (550.0, Complex::real(1.5));
+        // A quarter wave of ior sqrt(1.5) on glass is a perfect anti-reflection coating
+        let matched = 1.5f32.sqrt();
+        assert!(film_reflectance(1.0, 1.0, matched, wavelength / (4.0 * matched), glass, wavelength) < 1e-5);
+        // A quarter wave of ior 2 reflects ((1.5 - 4) / (1.5 + 4))^2
+        assert!((film_reflectance(1.0, 1.0, 2.0, wavelength / 8.0, glass, wavelength) - 0.2066).abs() < 1e-3);
+        // A half wave film is as if it weren't there
+        assert!((film_reflectance(1.0, 1.0, 2.0, wavelength / 4.0, glass, wavelength) - 0.04).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reflectance_stays_in_range_and_shifts_with_thickness() {
+        let water = Vector3f::uniform(1.33);
+        let mut colours = Vec::new();
+        for thickness in [100.0, 250.0, 400.0, 800.0] {
+            for cos in [1.0, 0.6, 0.2, 0.0] {
+                let bubble = fresnel_thin_film(cos, 1.0, 1.33, thickness, &Vector3f::one(), &Vector3f::zero());
+                let on_water = fresnel_thin_film(cos, 1.0, 1.5, thickness, &water, &Vector3f::zero());
+                for channel in 0..3 {
+                    assert!((0.0..=1.0).contains(&bubble.axis(channel)) && (0.0..=1.0).contains(&on_water.axis(channel)));
+                }
+            }
+            colours.push(fresnel_thin_film(1.0, 1.0, 1.33, thickness, &Vector3f::one(), &Vector3f::zero()));
+        }
+        // Interference tints each thickness differently
+        assert!(colours.windows(2).all(|pair| (pair[0] - pair[1]).magnitude() > 0.01));
+        assert!(colours.iter().any(|colour| (colour.x - colour.z).abs() > 0.01));
+    }
+
+    #[test]
+    fn film_over_a_conductor_only_reflects() {
+        let film = ThinFilm::conductor(Vector3f::uniform(0.2), Vector3f::uniform(3.0), 300.0, 1.4);
+        let normal = Vector3f::unit_z();
+        let hit = HitData::new(1.0, Vector3f::zero(), normal, &-normal, &normal, &film);
+        let expected = film.reflectance(1.0, &hit);
+        for _ in 0..100 {
+            let sample = film.sample(&normal, &hit).unwrap();
+            assert!(sample.is_delta && sample.incoming.z > 0.0);
+            assert!((sample.weight.y - expected.y).abs() < 1e-5);
+        }
+    }
+}
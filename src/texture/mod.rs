@@ -0,0 +1,205 @@
+pub mod procedural;
+
+use std::io::Result;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::loaders::image::{load_image, Image};
+use crate::utils::noise::Perlin;
+use crate::vectorlib::{hit::HitData, point3::Point3, vector3::*};
+
+// Colour that varies over a surface, looked up with the (u, v) of a hit and its position in space.
+// Materials hold textures wherever they used to hold a fixed colour
+pub trait Texture {
+    fn value(&self, u: f32, v: f32, point: &Point3) -> Vector3f;
+
+    fn value_at(&self, hit_data: &HitData) -> Vector3f {
+        return self.value(hit_data.u, hit_data.v, &hit_data.at);
+    }
+
+    // Single number for inputs such as roughness, the average of the three channels
+    fn scalar_at(&self, hit_data: &HitData) -> f32 {
+        let value = self.value_at(hit_data);
+        return (value.x + value.y + value.z) / 3.0;
+    }
+}
+
+// The same colour everywhere
+pub struct SolidColor {
+    color: Vector3f,
+}
+
+impl SolidColor {
+    pub fn new(color: Vector3f) -> SolidColor {
+        return SolidColor { color };
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _point: &Point3) -> Vector3f {
+        return self.color;
+    }
+}
+
+// Same value in every channel everywhere, for scalar inputs such as roughness
+pub fn constant(value: f32) -> Box<dyn Texture> {
+    return Box::new(SolidColor::new(Vector3f::uniform(value)));
+}
+
+// 3D checkerboard of cubes with sides of length scale, alternating between two textures.
+// Working in space rather than uv means it needs no parameterization and never stretches
+pub struct Checker {
+    even: Box<dyn Texture>,
+    odd: Box<dyn Texture>,
+    scale: f32,
+}
+
+impl Checker {
+    pub fn new(even: Box<dyn Texture>, odd: Box<dyn Texture>, scale: f32) -> Checker {
+        return Checker { even, odd, scale };
+    }
+
+    pub fn from_colors(even: Vector3f, odd: Vector3f, scale: f32) -> Checker {
+        return Checker::new(Box::new(SolidColor::new(even)), Box::new(SolidColor::new(odd)), scale);
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f32, v: f32, point: &Point3) -> Vector3f {
+        let cell = (point.x / self.scale).floor() as i64 + (point.y / self.scale).floor() as i64 + (point.z / self.scale).floor() as i64;
+        if cell.rem_euclid(2) == 0 {
+            return self.even.value(u, v, point);
+        }
+        return self.odd.value(u, v, point);
+    }
+}
+
+// Picture wrapped over the uv square and repeated outside it, v = 0 is the bottom row of the image.
+// Pixels are squared back into linear colour to undo the gamma 2 the renderer writes its own images with
+pub struct ImageTexture {
+    image: Rc<Image>,
+}
+
+impl ImageTexture {
+    // The image is shared so one picture can back several textures
+    pub fn new(image: Rc<Image>) -> ImageTexture {
+        return ImageTexture { image };
+    }
+
+    // PNG or PPM/PGM file
+    pub fn load(path: &Path) -> Result<ImageTexture> {
+        return Ok(ImageTexture::new(Rc::new(load_image(path)?)));
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vector3f {
+        let x = x.rem_euclid(self.image.width as i64) as usize;
+        let y = y.rem_euclid(self.image.height as i64) as usize;
+        let pixel = self.image.get(x, y);
+        return pixel.multiply_element_wise(&pixel);
+    }
+}
+
+impl Texture for ImageTexture {
+    // Bilinear filtering between the four nearest pixel centers
+    fn value(&self, u: f32, v: f32, _point: &Point3) -> Vector3f {
+        if self.image.width == 0 || self.image.height == 0 {
+            return Vector3f::zero();
+        }
+        let x = u * self.image.width as f32 - 0.5;
+        let y = (1.0 - v) * self.image.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = Vector3f::lerp(&self.texel(x0, y0), &self.texel(x0 + 1, y0), tx);
+        let bottom = Vector3f::lerp(&self.texel(x0, y0 + 1), &self.texel(x0 + 1, y0 + 1), tx);
+        return Vector3f::lerp(&top, &bottom, ty);
+    }
+}
+
+// Perlin noise in space, blending from black to color. One octave gives soft blobs, more add finer detail
+pub struct NoiseTexture {
+    perlin: Perlin,
+    color: Vector3f,
+    // Noise features per world unit
+    frequency: f32,
+    octaves: u32,
+}
+
+impl NoiseTexture {
+    pub fn new(seed: u64, color: Vector3f, frequency: f32, octaves: u32) -> NoiseTexture {
+        return NoiseTexture { perlin: Perlin::new(seed), color, frequency, octaves: octaves.max(1) };
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, point: &Point3) -> Vector3f {
+        let noise = self.perlin.fbm(&(*point * self.frequency), self.octaves);
+        return (0.5 + 0.5 * noise).clamp(0.0, 1.0) * self.color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3f, b: Vector3f) -> bool {
+        return (a - b).magnitude() < 1e-5;
+    }
+
+    #[test]
+    fn solid_and_constant_textures() {
+        let color = Vector3f::new(0.2, 0.4, 0.9);
+        assert!(close(SolidColor::new(color).value(0.3, 0.7, &Vector3f::new(5.0, -2.0, 1.0)), color));
+        let hit_free = constant(0.25);
+        assert!(close(hit_free.value(0.0, 0.0, &Vector3f::zero()), Vector3f::uniform(0.25)));
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let white = Vector3f::one();
+        let black = Vector3f::zero();
+        let checker = Checker::from_colors(white, black, 0.5);
+        assert!(close(checker.value(0.0, 0.0, &Vector3f::new(0.25, 0.25, 0.25)), white));
+        assert!(close(checker.value(0.0, 0.0, &Vector3f::new(0.75, 0.25, 0.25)), black));
+        assert!(close(checker.value(0.0, 0.0, &Vector3f::new(0.75, 0.75, 0.25)), white));
+        // Cells carry on across zero without a doubled row
+        assert!(close(checker.value(0.0, 0.0, &Vector3f::new(-0.25, 0.25, 0.25)), black));
+        assert!(close(checker.value(0.0, 0.0, &Vector3f::new(-0.75, 0.25, 0.25)), white));
+    }
+
+    #[test]
+    fn image_texture_filters_and_wraps() {
+        // Top row red and green, bottom row blue and white, stored with gamma 2
+        let pixels = vec![Vector3f::new(1.0, 0.0, 0.0), Vector3f::new(0.0, 1.0, 0.0), Vector3f::new(0.0, 0.0, 1.0), Vector3f::uniform(0.5)];
+        let texture = ImageTexture::new(Rc::new(Image::new(2, 2, pixels)));
+        let point = Vector3f::zero();
+        assert!(close(texture.value(0.25, 0.75, &point), Vector3f::new(1.0, 0.0, 0.0)));
+        assert!(close(texture.value(0.25, 0.25, &point), Vector3f::new(0.0, 0.0, 1.0)));
+        assert!(close(texture.value(0.75, 0.25, &point), Vector3f::uniform(0.25)));
+        assert!(close(texture.value(0.5, 0.75, &point), Vector3f::new(0.5, 0.5, 0.0)));
+        assert!(close(texture.value(1.25, -0.75, &point), Vector3f::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn noise_texture_stays_in_range_and_follows_its_seed() {
+        let color = Vector3f::new(1.0, 0.5, 0.0);
+        let texture = NoiseTexture::new(7, color, 3.0, 4);
+        let same = NoiseTexture::new(7, color, 3.0, 4);
+        let other = NoiseTexture::new(8, color, 3.0, 4);
+        let mut differs = false;
+        for i in 0..200 {
+            let point = Vector3f::new(i as f32 * 0.137, (i % 13) as f32 * 0.291, (i % 7) as f32 * -0.53);
+            let value = texture.value(0.0, 0.0, &point);
+            assert!(value.x >= 0.0 && value.x <= 1.0);
+            assert!((value.y - 0.5 * value.x).abs() < 1e-6 && value.z == 0.0);
+            assert!(close(value, same.value(0.0, 0.0, &point)));
+            differs |= !close(value, other.value(0.0, 0.0, &point));
+        }
+        assert!(differs);
+
+        // Perlin noise is zero on the lattice, leaving mid grey
+        let single = NoiseTexture::new(7, Vector3f::one(), 1.0, 1);
+        assert!(close(single.value(0.0, 0.0, &Vector3f::new(3.0, -2.0, 5.0)), Vector3f::uniform(0.5)));
+    }
+}
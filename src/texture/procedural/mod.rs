@@ -0,0 +1,229 @@
+use std::f32::consts::PI;
+
+use crate::texture::Texture;
+use crate::utils::noise::{Perlin, Worley};
+use crate::vectorlib::{point3::Point3, vector3::*};
+
+// Solid patterns computed from the hit position, so they need no uvs and never stretch or seam.
+// Each one works out a value in [0,1] and blends between a low and a high colour with it, so the same
+// pattern can drive albedo, emission or (with black and white) a scalar input such as roughness
+
+fn blend(low: &Vector3f, high: &Vector3f, t: f32) -> Vector3f {
+    return Vector3f::lerp(low, high, t.clamp(0.0, 1.0));
+}
+
+// Stone with veins running across x, bent by turbulence
+pub struct Marble {
+    perlin: Perlin,
+    low: Vector3f,
+    high: Vector3f,
+    // Veins per world unit
+    frequency: f32,
+    // How far the veins are pushed around, 0 gives straight stripes
+    turbulence: f32,
+    octaves: u32,
+}
+
+impl Marble {
+    pub fn new(seed: u64, low: Vector3f, high: Vector3f, frequency: f32, turbulence: f32, octaves: u32) -> Marble {
+        return Marble { perlin: Perlin::new(seed), low, high, frequency, turbulence, octaves };
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f32, _v: f32, point: &Point3) -> Vector3f {
+        let phase = self.frequency * point.x + self.turbulence * self.perlin.turbulence(&(*point * self.frequency), self.octaves);
+        return blend(&self.low, &self.high, 0.5 + 0.5 * (PI * phase).sin());
+    }
+}
+
+// Growth rings around the y axis, wobbled by noise so they aren't perfect circles
+pub struct Wood {
+    perlin: Perlin,
+    light: Vector3f,
+    dark: Vector3f,
+    // Rings per world unit of radius
+    ring_frequency: f32,
+    // How far noise moves the rings, in world units
+    distortion: f32,
+    // Scale of the noise that distorts the rings, higher gives wigglier grain
+    grain_frequency: f32,
+}
+
+impl Wood {
+    pub fn new(seed: u64, light: Vector3f, dark: Vector3f, ring_frequency: f32, distortion: f32, grain_frequency: f32) -> Wood {
+        return Wood { perlin: Perlin::new(seed), light, dark, ring_frequency, distortion, grain_frequency };
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f32, _v: f32, point: &Point3) -> Vector3f {
+        let grain = Vector3f::new(point.x, point.y * 0.1, point.z) * self.grain_frequency;
+        let radius = (point.x * point.x + point.z * point.z).sqrt() + self.distortion * self.perlin.fbm(&grain, 4);
+        let rings = (radius * self.ring_frequency).fract();
+        // Mostly light early wood with narrow dark bands of late wood
+        let band = 0.5 - 0.5 * (2.0 * PI * rings).cos();
+        return blend(&self.light, &self.dark, band * band * band);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CellPattern {
+    // Distance to the nearest feature point, dark spots in bright cells
+    Distance,
+    // Difference between the two nearest distances, thin dark lines along the cell walls
+    Edges,
+    // Each cell filled with its own flat blend of low and high, like cracked tiles or crystals
+    Cells,
+}
+
+// Worley cellular noise
+pub struct Voronoi {
+    worley: Worley,
+    low: Vector3f,
+    high: Vector3f,
+    // Cells per world unit
+    frequency: f32,
+    pattern: CellPattern,
+}
+
+impl Voronoi {
+    pub fn new(seed: u64, low: Vector3f, high: Vector3f, frequency: f32, pattern: CellPattern) -> Voronoi {
+        return Voronoi { worley: Worley::new(seed), low, high, frequency, pattern };
+    }
+}
+
+impl Texture for Voronoi {
+    fn value(&self, _u: f32, _v: f32, point: &Point3) -> Vector3f {
+        let sample = self.worley.sample(&(*point * self.frequency));
+        let t = match self.pattern {
+            CellPattern::Distance => sample.f1,
+            // Saturates a short way from the wall so the lines stay thin
+            CellPattern::Edges => (sample.f2 - sample.f1) * 4.0,
+            CellPattern::Cells => (sample.cell >> 40) as f32 / (1_u64 << 24) as f32,
+        };
+        return blend(&self.low, &self.high, t);
+    }
+}
+
+// Summed absolute octaves of Perlin noise, billowy like smoke or fire
+pub struct Turbulence {
+    perlin: Perlin,
+    low: Vector3f,
+    high: Vector3f,
+    frequency: f32,
+    octaves: u32,
+}
+
+impl Turbulence {
+    pub fn new(seed: u64, low: Vector3f, high: Vector3f, frequency: f32, octaves: u32) -> Turbulence {
+        return Turbulence { perlin: Perlin::new(seed), low, high, frequency, octaves };
+    }
+}
+
+impl Texture for Turbulence {
+    fn value(&self, _u: f32, _v: f32, point: &Point3) -> Vector3f {
+        return blend(&self.low, &self.high, self.perlin.turbulence(&(*point * self.frequency), self.octaves));
+    }
+}
+
+// Looks up another texture at a point pushed around by fbm noise, which swirls any pattern into
+// something more organic. Warping a Voronoi gives cells with wobbly walls, warping fbm gives flowing marble
+pub struct DomainWarp {
+    inner: Box<dyn Texture>,
+    perlin: Perlin,
+    frequency: f32,
+    // How far points are moved, in world units
+    strength: f32,
+    octaves: u32,
+}
+
+impl DomainWarp {
+    pub fn new(inner: Box<dyn Texture>, seed: u64, frequency: f32, strength: f32, octaves: u32) -> DomainWarp {
+        return DomainWarp { inner, perlin: Perlin::new(seed), frequency, strength, octaves };
+    }
+
+    pub fn warp(&self, point: &Point3) -> Point3 {
+        // Three unrelated noise lookups, offset so the axes don't move together
+        let q = *point * self.frequency;
+        let offset = Vector3f::new(
+            self.perlin.fbm(&q, self.octaves),
+            self.perlin.fbm(&(q + Vector3f::new(5.2, 1.3, 2.8)), self.octaves),
+            self.perlin.fbm(&(q + Vector3f::new(1.7, 9.2, 4.1)), self.octaves),
+        );
+        return *point + self.strength * offset;
+    }
+}
+
+impl Texture for DomainWarp {
+    fn value(&self, u: f32, v: f32, point: &Point3) -> Vector3f {
+        return self.inner.value(u, v, &self.warp(point));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> impl Iterator<Item = Point3> {
+        return (0..300).map(|i| Point3::new(i as f32 * 0.173 - 20.0, (i % 17) as f32 * 0.311 - 2.0, (i % 11) as f32 * -0.47));
+    }
+
+    fn in_range(value: Vector3f) -> bool {
+        return [value.x, value.y, value.z].iter().all(|channel| (0.0..=1.0).contains(channel)) && value.x == value.y;
+    }
+
+    #[test]
+    fn patterns_blend_between_their_colours() {
+        let (black, white) = (Vector3f::zero(), Vector3f::one());
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(Marble::new(1, black, white, 2.0, 5.0, 6)),
+            Box::new(Wood::new(1, white, black, 4.0, 0.3, 2.0)),
+            Box::new(Voronoi::new(1, black, white, 3.0, CellPattern::Distance)),
+            Box::new(Voronoi::new(1, black, white, 3.0, CellPattern::Edges)),
+            Box::new(Voronoi::new(1, black, white, 3.0, CellPattern::Cells)),
+            Box::new(Turbulence::new(1, black, white, 2.0, 6)),
+            Box::new(DomainWarp::new(Box::new(Turbulence::new(1, black, white, 2.0, 6)), 2, 1.0, 0.5, 4)),
+        ];
+        for texture in textures.iter() {
+            for point in points() {
+                assert!(in_range(texture.value(0.0, 0.0, &point)));
+            }
+        }
+    }
+
+    #[test]
+    fn patterns_repeat_for_the_same_seed() {
+        let (black, white) = (Vector3f::zero(), Vector3f::one());
+        let pairs: Vec<[Box<dyn Texture>; 2]> = vec![
+            [Box::new(Marble::new(4, black, white, 2.0, 5.0, 6)), Box::new(Marble::new(4, black, white, 2.0, 5.0, 6))],
+            [Box::new(Wood::new(4, white, black, 4.0, 0.3, 2.0)), Box::new(Wood::new(4, white, black, 4.0, 0.3, 2.0))],
+            [Box::new(Voronoi::new(4, black, white, 3.0, CellPattern::Cells)), Box::new(Voronoi::new(4, black, white, 3.0, CellPattern::Cells))],
+        ];
+        for [a, b] in pairs.iter() {
+            for point in points() {
+                assert_eq!(a.value(0.0, 0.0, &point).x, b.value(0.0, 0.0, &point).x);
+            }
+        }
+    }
+
+    #[test]
+    fn undisturbed_patterns_are_regular() {
+        let (black, white) = (Vector3f::zero(), Vector3f::one());
+        // Without turbulence marble is sine stripes along x
+        let marble = Marble::new(1, black, white, 2.0, 0.0, 4);
+        assert!((marble.value(0.0, 0.0, &Point3::new(0.0, 3.0, 1.0)).x - 0.5).abs() < 1e-5);
+        assert!((marble.value(0.0, 0.0, &Point3::new(0.25, -1.0, 2.0)).x - 1.0).abs() < 1e-5);
+
+        // Without distortion wood rings are circles around the y axis, dark halfway between rings
+        let wood = Wood::new(1, white, black, 2.0, 0.0, 1.0);
+        assert!((wood.value(0.0, 0.0, &Point3::new(0.0, 5.0, 0.0)).x - 1.0).abs() < 1e-5);
+        assert!(wood.value(0.0, 0.0, &Point3::new(0.0, 2.0, 0.25)).x.abs() < 1e-5);
+        assert!(wood.value(0.0, 0.0, &Point3::new(0.15, 2.0, 0.2)).x.abs() < 1e-5);
+
+        // No strength, no warp
+        let warp = DomainWarp::new(Box::new(marble), 2, 1.0, 0.0, 4);
+        let point = Point3::new(0.3, 0.2, 0.1);
+        assert!((warp.warp(&point) - point).magnitude() < 1e-6);
+    }
+}
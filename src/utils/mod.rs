@@ -1,3 +1,6 @@
+pub mod noise;
+pub mod polynomial;
+
 use std::f32::consts::PI;
 
 pub trait Angle{
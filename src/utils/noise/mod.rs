@@ -0,0 +1,211 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::vectorlib::{point3::Point3, vector3::Magnitude};
+
+// Ken Perlin's improved gradient noise. The permutation table is shuffled from a seed so every
+// generator built with the same seed gives the same pattern
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+fn fade(t: f32) -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    return a + t * (b - a);
+}
+
+// Dot product of the offset with one of twelve gradient directions picked by the hash
+fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    return (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v });
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut permutation = [0; 512];
+        for i in 0..512 {
+            permutation[i] = table[i % 256];
+        }
+        return Perlin { permutation };
+    }
+
+    // Smooth noise in roughly [-1,1], zero at every integer lattice point
+    pub fn noise(&self, point: &Point3) -> f32 {
+        let (xf, yf, zf) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (x, y, z) = (point.x - xf, point.y - yf, point.z - zf);
+        let (xi, yi, zi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize, (zf as i32 & 255) as usize);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        return lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1.0, y, z)),
+                lerp(u, gradient(p[ab], x, y - 1.0, z), gradient(p[bb], x - 1.0, y - 1.0, z)),
+            ),
+            lerp(
+                v,
+                lerp(u, gradient(p[aa + 1], x, y, z - 1.0), gradient(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, gradient(p[ab + 1], x, y - 1.0, z - 1.0), gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0)),
+            ),
+        );
+    }
+
+    // Fractional Brownian motion, octaves of noise each at double the frequency and half the amplitude.
+    // Stays roughly in [-1,1]
+    pub fn fbm(&self, point: &Point3, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 0.5;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&(*point * frequency));
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        return sum / (1.0 - amplitude * 2.0).max(0.5);
+    }
+
+    // Same as fbm but summing the absolute value of each octave, which gives sharp creases. In [0,1)
+    pub fn turbulence(&self, point: &Point3, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 0.5;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(&(*point * frequency)).abs();
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        return sum;
+    }
+}
+
+// Distances from a point to the nearest and second nearest feature points of a Worley pattern,
+// plus a hash of the cell holding the nearest one so each cell can be given its own look
+pub struct WorleySample {
+    pub f1: f32,
+    pub f2: f32,
+    pub cell: u64,
+}
+
+// Steven Worley's cellular noise with one randomly placed feature point in every unit cube.
+// Feature points are hashed from the cube coordinates so nothing has to be stored
+pub struct Worley {
+    seed: u64,
+}
+
+// SplitMix64 finaliser, a cheap hash with good avalanche
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return x ^ (x >> 31);
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Worley {
+        return Worley { seed };
+    }
+
+    fn cell_hash(&self, x: i64, y: i64, z: i64) -> u64 {
+        let mut hash = mix(self.seed ^ 0x9e3779b97f4a7c15);
+        for coordinate in [x, y, z] {
+            hash = mix(hash ^ coordinate as u64);
+        }
+        return hash;
+    }
+
+    pub fn sample(&self, point: &Point3) -> WorleySample {
+        let base = [point.x.floor() as i64, point.y.floor() as i64, point.z.floor() as i64];
+        let mut nearest = WorleySample { f1: f32::INFINITY, f2: f32::INFINITY, cell: 0 };
+
+        // Searching the surrounding 3x3x3 block of cells finds the nearest point, and the second nearest in all but rare corner cases
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (x, y, z) = (base[0] + dx, base[1] + dy, base[2] + dz);
+                    let hash = self.cell_hash(x, y, z);
+                    let offset = |shift: u32| ((hash >> shift) & 0xFFFF) as f32 / 65536.0;
+                    let feature = Point3::new(x as f32 + offset(0), y as f32 + offset(16), z as f32 + offset(32));
+
+                    let distance = (feature - *point).magnitude();
+                    if distance < nearest.f1 {
+                        nearest.f2 = nearest.f1;
+                        nearest.f1 = distance;
+                        nearest.cell = hash;
+                    } else if distance < nearest.f2 {
+                        nearest.f2 = distance;
+                    }
+                }
+            }
+        }
+        return nearest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> impl Iterator<Item = Point3> {
+        return (0..300).map(|i| Point3::new(i as f32 * 0.173 - 20.0, (i % 17) as f32 * 0.311 - 2.0, (i % 11) as f32 * -0.47));
+    }
+
+    #[test]
+    fn turbulence_stays_in_range() {
+        let perlin = Perlin::new(3);
+        for point in points() {
+            let value = perlin.turbulence(&point, 5);
+            assert!((0.0..1.0).contains(&value));
+            assert!(perlin.fbm(&point, 5).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn worley_finds_the_nearest_feature_points() {
+        let worley = Worley::new(11);
+        for point in points() {
+            let sample = worley.sample(&point);
+            assert!(sample.f1 <= sample.f2);
+
+            // Brute force over a wider block of cells
+            let base = [point.x.floor() as i64, point.y.floor() as i64, point.z.floor() as i64];
+            let mut nearest = f32::INFINITY;
+            for dz in -2..=2 {
+                for dy in -2..=2 {
+                    for dx in -2..=2 {
+                        let (x, y, z) = (base[0] + dx, base[1] + dy, base[2] + dz);
+                        let hash = worley.cell_hash(x, y, z);
+                        let offset = |shift: u32| ((hash >> shift) & 0xFFFF) as f32 / 65536.0;
+                        let feature = Point3::new(x as f32 + offset(0), y as f32 + offset(16), z as f32 + offset(32));
+                        nearest = nearest.min((feature - point).magnitude());
+                    }
+                }
+            }
+            assert!((sample.f1 - nearest).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn worley_depends_only_on_its_seed() {
+        let point = Point3::new(1.3, -4.2, 0.7);
+        let (a, b, c) = (Worley::new(5).sample(&point), Worley::new(5).sample(&point), Worley::new(6).sample(&point));
+        assert!(a.f1 == b.f1 && a.f2 == b.f2 && a.cell == b.cell);
+        assert!(a.cell != c.cell);
+    }
+}
@@ -0,0 +1,199 @@
+use std::f64::consts::PI;
+
+// Closed form real roots of low degree polynomials, after "Solving Quartics and Cubics for Graphics"
+// (Schwarze, Graphics Gems I). Coefficients are given highest degree first and roots come back unsorted
+// as a fixed array plus the number of valid entries so the intersection code doesn't allocate.
+
+const EQUATION_EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    return x.abs() < EQUATION_EPSILON;
+}
+
+// a x^2 + b x + c = 0
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> ([f64; 2], usize) {
+    if is_zero(a) {
+        if is_zero(b) {
+            return ([0.0; 2], 0);
+        }
+        return ([-c / b, 0.0], 1);
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return ([0.0; 2], 0);
+    }
+
+    // Avoids cancellation when b is close to +-sqrt(discriminant)
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return ([0.0, 0.0], 2);
+    }
+    return ([q / a, c / q], 2);
+}
+
+// a x^3 + b x^2 + c x + d = 0
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> ([f64; 3], usize) {
+    if is_zero(a) {
+        let (quadratic_roots, count) = solve_quadratic(b, c, d);
+        return ([quadratic_roots[0], quadratic_roots[1], 0.0], count);
+    }
+
+    // Normal form x^3 + A x^2 + B x + C, then substitute x = y - A/3 to remove the square term
+    let (a, b, c) = (b / a, c / a, d / a);
+    let square_a = a * a;
+    let p = (-square_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * square_a - a * b / 3.0 + c) / 2.0;
+
+    let cube_p = p * p * p;
+    let discriminant = q * q + cube_p;
+
+    let mut roots = [0.0; 3];
+    let count;
+    if is_zero(discriminant) {
+        if is_zero(q) {
+            // One triple root
+            count = 1;
+        } else {
+            // One single and one double root
+            let u = (-q).cbrt();
+            roots[0] = 2.0 * u;
+            roots[1] = -u;
+            count = 2;
+        }
+    } else if discriminant < 0.0 {
+        // Three real roots, trigonometric method
+        let phi = (-q / (-cube_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        roots[0] = t * phi.cos();
+        roots[1] = -t * (phi + PI / 3.0).cos();
+        roots[2] = -t * (phi - PI / 3.0).cos();
+        count = 3;
+    } else {
+        let root_discriminant = discriminant.sqrt();
+        roots[0] = (root_discriminant - q).cbrt() - (root_discriminant + q).cbrt();
+        count = 1;
+    }
+
+    for root in roots.iter_mut().take(count) {
+        *root -= a / 3.0;
+    }
+    return (roots, count);
+}
+
+// a x^4 + b x^3 + c x^2 + d x + e = 0, roots are polished with a couple of Newton steps
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> ([f64; 4], usize) {
+    if is_zero(a) {
+        let (cubic_roots, count) = solve_cubic(b, c, d, e);
+        return ([cubic_roots[0], cubic_roots[1], cubic_roots[2], 0.0], count);
+    }
+
+    // Normal form x^4 + A x^3 + B x^2 + C x + D, then substitute x = y - A/4 to remove the cube term
+    let (na, nb, nc, nd) = (b / a, c / a, d / a, e / a);
+    let square_a = na * na;
+    let p = -3.0 / 8.0 * square_a + nb;
+    let q = square_a * na / 8.0 - na * nb / 2.0 + nc;
+    let r = -3.0 / 256.0 * square_a * square_a + square_a * nb / 16.0 - na * nc / 4.0 + nd;
+
+    let mut roots = [0.0; 4];
+    let mut count = 0;
+    let mut push = |root: f64| {
+        roots[count] = root;
+        count += 1;
+    };
+
+    if is_zero(r) {
+        // No absolute term, y (y^3 + p y + q) = 0
+        let (cubic_roots, cubic_count) = solve_cubic(1.0, 0.0, p, q);
+        cubic_roots.iter().take(cubic_count).for_each(|root| push(*root));
+        push(0.0);
+    } else {
+        // Take one root of the resolvent cubic to split the quartic into two quadratics
+        let (cubic_roots, _) = solve_cubic(1.0, -p / 2.0, -r, r * p / 2.0 - q * q / 8.0);
+        let z = cubic_roots[0];
+
+        let mut u = z * z - r;
+        let mut v = 2.0 * z - p;
+        if is_zero(u) {
+            u = 0.0;
+        } else if u > 0.0 {
+            u = u.sqrt();
+        } else {
+            return (roots, 0);
+        }
+        if is_zero(v) {
+            v = 0.0;
+        } else if v > 0.0 {
+            v = v.sqrt();
+        } else {
+            return (roots, 0);
+        }
+
+        let v_signed = if q < 0.0 { -v } else { v };
+        let (first, first_count) = solve_quadratic(1.0, v_signed, z - u);
+        let (second, second_count) = solve_quadratic(1.0, -v_signed, z + u);
+        first.iter().take(first_count).for_each(|root| push(*root));
+        second.iter().take(second_count).for_each(|root| push(*root));
+    }
+
+    let evaluate = |x: f64| (((a * x + b) * x + c) * x + d) * x + e;
+    let derivative = |x: f64| ((4.0 * a * x + 3.0 * b) * x + 2.0 * c) * x + d;
+    for root in roots.iter_mut().take(count) {
+        *root -= na / 4.0;
+        for _ in 0..2 {
+            let slope = derivative(*root);
+            if slope != 0.0 {
+                *root -= evaluate(*root) / slope;
+            }
+        }
+    }
+    return (roots, count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(roots: &[f64]) -> Vec<f64> {
+        let mut roots = roots.to_vec();
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        return roots;
+    }
+
+    fn assert_roots(roots: &[f64], expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len());
+        for (root, expected) in sorted(roots).iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "{} != {}", root, expected);
+        }
+    }
+
+    #[test]
+    fn solves_quadratics() {
+        let (roots, count) = solve_quadratic(1.0, -3.0, 2.0);
+        assert_roots(&roots[..count], &[1.0, 2.0]);
+        let (roots, count) = solve_quadratic(0.0, 2.0, -4.0);
+        assert_roots(&roots[..count], &[2.0]);
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0).1, 0);
+    }
+
+    #[test]
+    fn solves_cubics() {
+        // (x - 1)(x - 2)(x + 3)
+        let (roots, count) = solve_cubic(1.0, 0.0, -7.0, 6.0);
+        assert_roots(&roots[..count], &[-3.0, 1.0, 2.0]);
+        // x^3 + x has only the root at 0
+        let (roots, count) = solve_cubic(1.0, 0.0, 1.0, 0.0);
+        assert_roots(&roots[..count], &[0.0]);
+    }
+
+    #[test]
+    fn solves_quartics() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let (roots, count) = solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0);
+        assert_roots(&roots[..count], &[1.0, 2.0, 3.0, 4.0]);
+        // (x^2 - 4)(x^2 + 1)
+        let (roots, count) = solve_quartic(1.0, 0.0, -3.0, 0.0, -4.0);
+        assert_roots(&roots[..count], &[-2.0, 2.0]);
+        assert_eq!(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0).1, 0);
+    }
+}
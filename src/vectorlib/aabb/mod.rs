@@ -0,0 +1,109 @@
+use crate::vectorlib::{point3::Point3, ray::Ray, vector3::Vector3f};
+
+// Thickness given to flat boxes (quads, axis aligned triangles) so rays can still hit them
+pub const FLAT_BOX_PADDING: f32 = 1e-4;
+
+// Axis aligned bounding box, used to cull rays before testing the geometry inside it
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        return Aabb { min, max };
+    }
+
+    // Builds a box from two corners given in any order
+    pub fn from_points(a: Point3, b: Point3) -> Aabb {
+        return Aabb::new(
+            Vector3f::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            Vector3f::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        );
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        return Aabb::from_points(self.min, self.max).grow(other.min).grow(other.max);
+    }
+
+    // Overlap of the two boxes, None if they don't touch
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        let min = Vector3f::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z));
+        let max = Vector3f::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z));
+        if min.x > max.x || min.y > max.y || min.z > max.z {
+            return None;
+        }
+        return Some(Aabb::new(min, max));
+    }
+
+    // Smallest box containing both self and the point
+    pub fn grow(&self, point: Point3) -> Aabb {
+        return Aabb::new(
+            Vector3f::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            Vector3f::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        );
+    }
+
+    // Flat boxes (e.g. a single triangle on an axis plane) are padded so the slab test can still hit them
+    pub fn padded(&self, delta: f32) -> Aabb {
+        let mut padded = *self;
+        for axis in 0..3 {
+            if padded.max.axis(axis) - padded.min.axis(axis) < delta {
+                *padded.min.axis_mut(axis) -= delta / 2.0;
+                *padded.max.axis_mut(axis) += delta / 2.0;
+            }
+        }
+        return padded;
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        return [0, 1, 2, 3, 4, 5, 6, 7].map(|corner| Vector3f::new(
+            if corner & 1 == 0 { self.min.x } else { self.max.x },
+            if corner & 2 == 0 { self.min.y } else { self.max.y },
+            if corner & 4 == 0 { self.min.z } else { self.max.z },
+        ));
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        return (self.min + self.max) * 0.5;
+    }
+
+    // Index of the axis the box is longest along, 0 = x, 1 = y, 2 = z
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            return 0;
+        }
+        if extent.y > extent.z {
+            return 1;
+        }
+        return 2;
+    }
+
+    // Slab test, returns true if the ray passes through the box somewhere in [t_min, t_max]
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        return self.hit_range(ray, t_min, t_max).is_some();
+    }
+
+    // Part of [t_min, t_max] the ray spends inside the box
+    pub fn hit_range(&self, ray: &Ray, mut t_min: f32, mut t_max: f32) -> Option<(f32, f32)> {
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction().axis(axis);
+            let origin = ray.origin().axis(axis);
+
+            let mut t0 = (self.min.axis(axis) - origin) * inverse_direction;
+            let mut t1 = (self.max.axis(axis) - origin) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        return Some((t_min, t_max));
+    }
+}
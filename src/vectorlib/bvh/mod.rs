@@ -0,0 +1,104 @@
+use crate::vectorlib::{aabb::Aabb, point3::Point3, ray::Ray};
+
+const MAX_PRIMITIVES_PER_LEAF: usize = 4;
+
+// Interior nodes have count == 0, their left child is the next node and the right child is at second_child
+struct BvhNode {
+    bounds: Aabb,
+    start: usize,
+    count: usize,
+    second_child: usize,
+}
+
+// Bounding volume hierarchy over a list of primitive boxes.
+// It only stores indices so any owner (a mesh, a list of hittables) can use it for its own primitives
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(boxes: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(boxes.len() * 2),
+            indices: (0..boxes.len()).collect(),
+        };
+
+        if !boxes.is_empty() {
+            let centroids: Vec<_> = boxes.iter().map(|b| b.centroid()).collect();
+            bvh.build(boxes, &centroids, 0, boxes.len());
+        }
+        return bvh;
+    }
+
+    fn build(&mut self, boxes: &[Aabb], centroids: &[Point3], start: usize, end: usize) -> usize {
+        let mut bounds = boxes[self.indices[start]];
+        let mut centroid_bounds = Aabb::new(centroids[self.indices[start]], centroids[self.indices[start]]);
+        for &i in &self.indices[start + 1..end] {
+            bounds = bounds.surrounding(&boxes[i]);
+            centroid_bounds = centroid_bounds.grow(centroids[i]);
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bounds, start, count: end - start, second_child: 0 });
+
+        if end - start <= MAX_PRIMITIVES_PER_LEAF {
+            return node_index;
+        }
+
+        // Split at the median centroid along the longest axis
+        let axis = centroid_bounds.longest_axis();
+        let mid = start + (end - start) / 2;
+        self.indices[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            centroids[*a].axis(axis).total_cmp(&centroids[*b].axis(axis))
+        });
+
+        self.build(boxes, centroids, start, mid);
+        let second_child = self.build(boxes, centroids, mid, end);
+
+        self.nodes[node_index].count = 0;
+        self.nodes[node_index].second_child = second_child;
+        return node_index;
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        return self.nodes.first().map(|node| node.bounds);
+    }
+
+    // Walks every leaf the ray passes through, hit_primitive gets (primitive index, t_min, closest t so far)
+    // and returns the t of a closer hit if there was one. Returns the closest t found
+    pub fn traverse<F>(&self, ray: &Ray, t_min: f32, t_max: f32, mut hit_primitive: F) -> Option<f32>
+    where
+        F: FnMut(usize, f32, f32) -> Option<f32>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_t = t_max;
+        let mut closest_hit = None;
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.hit(ray, t_min, closest_t) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.second_child);
+                stack.push(node_index + 1);
+                continue;
+            }
+
+            for &primitive in &self.indices[node.start..node.start + node.count] {
+                if let Some(t) = hit_primitive(primitive, t_min, closest_t) {
+                    closest_t = t;
+                    closest_hit = Some(t);
+                }
+            }
+        }
+
+        return closest_hit;
+    }
+}
@@ -0,0 +1,106 @@
+use crate::utils::polynomial::solve_quadratic;
+use crate::vectorlib::{aabb::Aabb, cylinder::{azimuth_tangent, azimuth_u, radial_tangent}, frame::Frame, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Cone with a capped circular base of radius at base and its apex height along axis
+pub struct Cone {
+    pub frame: Frame,
+    pub radius: f32,
+    pub height: f32,
+    pub material : Box<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(base: Point3, axis: Vector3f, radius: f32, height: f32, material : Box<dyn Material>) -> Cone {
+        return Cone { frame: Frame::from_z(base, axis), radius, height, material };
+    }
+}
+
+impl<'a> Hittable<'a> for Cone {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let local_ray = self.frame.ray_to_local(ray);
+        let o = local_ray.origin();
+        let d = local_ray.direction();
+
+        let mut closest: Option<(f32, Vector3f, f32, f32)> = None;
+        let mut closest_t = t_max;
+
+        // Side, x^2 + y^2 = (k (height - z))^2 with k the radius shrinking per unit of height
+        let k = self.radius / self.height;
+        let k_squared = k * k;
+        let w = self.height - o.z;
+        let (roots, count) = solve_quadratic(
+            (d.x * d.x + d.y * d.y - k_squared * d.z * d.z) as f64,
+            2.0 * (o.x * d.x + o.y * d.y + k_squared * w * d.z) as f64,
+            (o.x * o.x + o.y * o.y - k_squared * w * w) as f64,
+        );
+        for root in roots.iter().take(count) {
+            let t = *root as f32;
+            let z = o.z + t * d.z;
+            // The equation also describes the mirrored cone above the apex, which is rejected here
+            if t < t_min || closest_t < t || z < 0.0 || self.height < z {
+                continue;
+            }
+            let local_hit = local_ray.at(t);
+            let mut normal = Vector3f::new(local_hit.x, local_hit.y, k_squared * (self.height - z));
+            // The gradient vanishes at the apex itself
+            if normal.near_zero() {
+                normal = Vector3f::unit_z();
+            }
+            closest = Some((t, normal.unit_vector(), azimuth_u(&local_hit), z / self.height));
+            closest_t = t;
+        }
+
+        // Base cap
+        if d.z != 0.0 {
+            let t = -o.z / d.z;
+            if t_min <= t && t <= closest_t {
+                let local_hit = local_ray.at(t);
+                let distance_squared = local_hit.x * local_hit.x + local_hit.y * local_hit.y;
+                if distance_squared <= self.radius * self.radius {
+                    closest = Some((t, -Vector3f::unit_z(), azimuth_u(&local_hit), distance_squared.sqrt() / self.radius));
+                }
+            }
+        }
+
+        let (t, local_normal, u, v) = closest?;
+        let outward_normal = self.frame.vector_to_world(&local_normal);
+        let local_hit = local_ray.at(t);
+        // The side has v rising from the rim to the apex, the base from the center outwards
+        let local_dpdv = if local_normal.z < 0.0 {
+            radial_tangent(&local_hit, self.radius)
+        } else {
+            Vector3f::new(0.0, 0.0, self.height) - radial_tangent(&local_hit, self.radius)
+        };
+
+        let hit_data = HitData::new(t, ray.at(t), outward_normal, ray.direction(), &outward_normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.frame.vector_to_world(&azimuth_tangent(&local_hit)), self.frame.vector_to_world(&local_dpdv));
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local_min = Vector3f::new(-self.radius, -self.radius, 0.0);
+        let local_max = Vector3f::new(self.radius, self.radius, self.height);
+        return Some(self.frame.bounds_to_world(&local_min, &local_max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn narrows_towards_the_apex() {
+        let cone = Cone::new(Vector3f::zero(), Vector3f::unit_y(), 1.0, 2.0, Box::new(Lambertian::new(Vector3f::one())));
+        // Halfway up the radius is 0.5
+        let side = cone.hit(&Ray::new(Vector3f::new(-3.0, 1.0, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).unwrap();
+        assert!((side.t - 2.5).abs() < 1e-4 && side.normal.x < 0.0 && side.normal.y > 0.0);
+
+        let base = cone.hit(&Ray::new(Vector3f::new(0.5, -3.0, 0.0), Vector3f::unit_y()), 0.001, f32::INFINITY).unwrap();
+        assert!((base.t - 3.0).abs() < 1e-5 && base.normal.y < -0.999);
+
+        assert!(cone.hit(&Ray::new(Vector3f::new(-3.0, 1.0, 0.6), Vector3f::unit_x()), 0.001, f32::INFINITY).is_none());
+        assert!(cone.hit(&Ray::new(Vector3f::new(-3.0, 2.5, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).is_none());
+    }
+}
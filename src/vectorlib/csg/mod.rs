@@ -0,0 +1,165 @@
+use crate::vectorlib::{aabb::Aabb, hit::*, ray::*};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // Left with right carved out of it
+    Difference,
+}
+
+impl CsgOperation {
+    fn is_inside(&self, inside_left: bool, inside_right: bool) -> bool {
+        return match self {
+            CsgOperation::Union => inside_left || inside_right,
+            CsgOperation::Intersection => inside_left && inside_right,
+            CsgOperation::Difference => inside_left && !inside_right,
+        };
+    }
+}
+
+// Constructive solid geometry node combining two closed hittables, nodes can be nested to build up shapes.
+// Surfaces keep the material of the child they came from, so a bite taken out of a sphere shows the cutter's material
+pub struct Csg<'a> {
+    pub left: Box<dyn Hittable<'a> + 'a>,
+    pub right: Box<dyn Hittable<'a> + 'a>,
+    pub operation: CsgOperation,
+}
+
+impl<'a> Csg<'a> {
+    pub fn new(left: impl Hittable<'a> + 'a, right: impl Hittable<'a> + 'a, operation: CsgOperation) -> Csg<'a> {
+        return Csg { left: Box::new(left), right: Box::new(right), operation };
+    }
+
+    pub fn union(left: impl Hittable<'a> + 'a, right: impl Hittable<'a> + 'a) -> Csg<'a> {
+        return Csg::new(left, right, CsgOperation::Union);
+    }
+
+    pub fn intersection(left: impl Hittable<'a> + 'a, right: impl Hittable<'a> + 'a) -> Csg<'a> {
+        return Csg::new(left, right, CsgOperation::Intersection);
+    }
+
+    pub fn difference(left: impl Hittable<'a> + 'a, right: impl Hittable<'a> + 'a) -> Csg<'a> {
+        return Csg::new(left, right, CsgOperation::Difference);
+    }
+}
+
+impl<'a> Hittable<'a> for Csg<'a> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        // Only the first crossing of each child is looked for past t_max, an exit there is what tells us the
+        // ray started inside. Later crossings are found one at a time and only as far as t_max
+        let mut next_left = self.left.hit(ray, t_min, f32::INFINITY);
+        let mut next_right = self.right.hit(ray, t_min, f32::INFINITY);
+
+        let mut inside_left = next_left.as_ref().is_some_and(|hit| !hit.hit_front_face);
+        let mut inside_right = next_right.as_ref().is_some_and(|hit| !hit.hit_front_face);
+        let mut inside = self.operation.is_inside(inside_left, inside_right);
+
+        // Step through the crossings of both children in order, looking for the first that changes the combined state
+        for _ in 0..MAX_HITS_ALONG_RAY {
+            let from_left = match (&next_left, &next_right) {
+                (Some(left), Some(right)) => left.t <= right.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let mut hit = if from_left { next_left.take()? } else { next_right.take()? };
+            if hit.t > t_max {
+                return None;
+            }
+
+            let after = hit.t + HIT_STEP_TOLERANCE;
+            if from_left {
+                inside_left = hit.hit_front_face;
+                next_left = self.left.hit(ray, after, t_max);
+            } else {
+                inside_right = hit.hit_front_face;
+                next_right = self.right.hit(ray, after, t_max);
+            }
+
+            let now_inside = self.operation.is_inside(inside_left, inside_right);
+            if now_inside != inside {
+                // The normal already faces back along the ray, only which side we're on changes.
+                // A carved out surface is entered by leaving the cutter so it comes out as a front face
+                hit.hit_front_face = now_inside;
+                return Some(hit);
+            }
+            inside = now_inside;
+        }
+        return None;
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return match self.operation {
+            CsgOperation::Union => Some(self.left.bounding_box()?.surrounding(&self.right.bounding_box()?)),
+            CsgOperation::Intersection => match (self.left.bounding_box(), self.right.bounding_box()) {
+                (Some(left), Some(right)) => Some(left.intersection(&right).unwrap_or(Aabb::new(left.min, left.min))),
+                (Some(bounds), None) | (None, Some(bounds)) => Some(bounds),
+                (None, None) => None,
+            },
+            CsgOperation::Difference => self.left.bounding_box(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vectorlib::{sphere::Sphere, vector3::Vector3f};
+
+    // Unit spheres overlapping between x = -0.5 and x = 0.5
+    fn pair() -> (Sphere, Sphere) {
+        let sphere = |x: f32| Sphere::new(Vector3f::new(x, 0.0, -5.0), 1.0, Box::new(Lambertian::new(Vector3f::one())));
+        return (sphere(-0.5), sphere(0.5));
+    }
+
+    fn along_x(y: f32) -> Ray {
+        return Ray::new(Vector3f::new(-5.0, y, -5.0), Vector3f::unit_x());
+    }
+
+    fn crossings(csg: &Csg, ray: &Ray) -> Vec<(f32, bool)> {
+        return csg.hits_along(ray, 0.001, f32::INFINITY).iter().map(|hit| (hit.t, hit.hit_front_face)).collect();
+    }
+
+    fn assert_crossings(found: Vec<(f32, bool)>, expected: &[(f32, bool)]) {
+        assert_eq!(found.len(), expected.len());
+        for ((t, front), (expected_t, expected_front)) in found.iter().zip(expected) {
+            assert!((t - expected_t).abs() < 1e-3 && front == expected_front, "{} {}", t, front);
+        }
+    }
+
+    #[test]
+    fn combines_the_crossings_of_both_children() {
+        let (a, b) = pair();
+        assert_crossings(crossings(&Csg::union(a, b), &along_x(0.0)), &[(3.5, true), (6.5, false)]);
+        let (a, b) = pair();
+        assert_crossings(crossings(&Csg::intersection(a, b), &along_x(0.0)), &[(4.5, true), (5.5, false)]);
+        let (a, b) = pair();
+        assert_crossings(crossings(&Csg::difference(a, b), &along_x(0.0)), &[(3.5, true), (4.5, false)]);
+    }
+
+    #[test]
+    fn misses_where_the_combination_is_empty() {
+        // At this height the spheres no longer overlap
+        let (a, b) = pair();
+        assert!(Csg::intersection(a, b).hit(&along_x(0.95), 0.001, f32::INFINITY).is_none());
+        let (a, b) = pair();
+        assert_eq!(Csg::union(a, b).hits_along(&along_x(0.95), 0.001, f32::INFINITY).len(), 4);
+        // Nothing is left of a sphere carved out by a larger one around it
+        let (a, _) = pair();
+        let cutter = Sphere::new(Vector3f::new(-0.5, 0.0, -5.0), 2.0, Box::new(Lambertian::new(Vector3f::one())));
+        assert!(Csg::difference(a, cutter).hit(&along_x(0.0), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn starts_inside_when_the_ray_does() {
+        let (a, b) = pair();
+        let union = Csg::union(a, b);
+        let inside = Ray::new(Vector3f::new(0.0, 0.0, -5.0), Vector3f::unit_x());
+        let hit = union.hit(&inside, 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-3 && !hit.hit_front_face);
+        // t_max before the exit leaves nothing to hit
+        assert!(union.hit(&inside, 0.001, 1.0).is_none());
+    }
+}
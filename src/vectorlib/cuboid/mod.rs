@@ -0,0 +1,124 @@
+use crate::vectorlib::{aabb::Aabb, frame::Frame, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Rectangular box, named Cuboid so it doesn't clash with std's Box.
+// It is stored as a frame at its center plus half extents so oriented and axis aligned boxes share the same code
+pub struct Cuboid {
+    pub frame: Frame,
+    pub half_extents: Vector3f,
+    pub material : Box<dyn Material>,
+}
+
+impl Cuboid {
+    pub fn axis_aligned(min: Point3, max: Point3, material : Box<dyn Material>) -> Cuboid {
+        let bounds = Aabb::from_points(min, max);
+        let frame = Frame::new(bounds.centroid(), Vector3f::unit_x(), Vector3f::unit_y());
+        return Cuboid { frame, half_extents: (bounds.max - bounds.min) / 2.0, material };
+    }
+
+    // x_axis and y_axis give the orientation of the box and are made orthonormal here
+    pub fn oriented(center: Point3, half_extents: Vector3f, x_axis: Vector3f, y_axis: Vector3f, material : Box<dyn Material>) -> Cuboid {
+        let x = x_axis.unit_vector();
+        let y = (y_axis - y_axis.dot(&x) * x).unit_vector();
+        return Cuboid { frame: Frame::new(center, x, y), half_extents, material };
+    }
+}
+
+impl<'a> Hittable<'a> for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let local_ray = self.frame.ray_to_local(ray);
+        let origin = local_ray.origin();
+        let direction = local_ray.direction();
+
+        // Slab test that also remembers which axis each bound came from
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        let mut near_axis = 0;
+        let mut far_axis = 0;
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / direction.axis(axis);
+            let extent = self.half_extents.axis(axis);
+            let mut t0 = (-extent - origin.axis(axis)) * inverse_direction;
+            let mut t1 = (extent - origin.axis(axis)) * inverse_direction;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_near {
+                t_near = t0;
+                near_axis = axis;
+            }
+            if t1 < t_far {
+                t_far = t1;
+                far_axis = axis;
+            }
+        }
+        if t_far < t_near {
+            return None;
+        }
+
+        // Entering face first, the exit face if the ray starts inside
+        let (t, axis) = if t_min <= t_near && t_near <= t_max {
+            (t_near, near_axis)
+        } else if t_min <= t_far && t_far <= t_max {
+            (t_far, far_axis)
+        } else {
+            return None;
+        };
+
+        let local_hit = local_ray.at(t);
+        let mut local_normal = Vector3f::zero();
+        *local_normal.axis_mut(axis) = local_hit.axis(axis).signum();
+        let outward_normal = self.frame.vector_to_world(&local_normal);
+
+        // Each face is mapped to [0,1]^2 using the two axes it spans
+        let u_axis = (axis + 1) % 3;
+        let v_axis = (axis + 2) % 3;
+        let u = 0.5 + local_hit.axis(u_axis) / (2.0 * self.half_extents.axis(u_axis));
+        let v = 0.5 + local_hit.axis(v_axis) / (2.0 * self.half_extents.axis(v_axis));
+        let mut local_dpdu = Vector3f::zero();
+        let mut local_dpdv = Vector3f::zero();
+        *local_dpdu.axis_mut(u_axis) = 2.0 * self.half_extents.axis(u_axis);
+        *local_dpdv.axis_mut(v_axis) = 2.0 * self.half_extents.axis(v_axis);
+
+        let hit_data = HitData::new(t, ray.at(t), outward_normal, ray.direction(), &outward_normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.frame.vector_to_world(&local_dpdu), self.frame.vector_to_world(&local_dpdv));
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return Some(self.frame.bounds_to_world(&-self.half_extents, &self.half_extents));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material() -> Box<dyn Material> {
+        return Box::new(Lambertian::new(Vector3f::one()));
+    }
+
+    #[test]
+    fn axis_aligned_box_hits_the_near_face() {
+        let cuboid = Cuboid::axis_aligned(Vector3f::new(-1.0, -1.0, -4.0), Vector3f::new(1.0, 1.0, -2.0), material());
+        let hit = cuboid.hit(&Ray::new(Vector3f::new(0.5, 0.5, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5 && hit.hit_front_face && hit.normal.z > 0.999);
+
+        // From inside the far face is hit as a back face
+        let inside = cuboid.hit(&Ray::new(Vector3f::new(0.0, 0.0, -3.0), Vector3f::unit_x()), 0.001, f32::INFINITY).unwrap();
+        assert!((inside.t - 1.0).abs() < 1e-5 && !inside.hit_front_face);
+
+        assert!(cuboid.hit(&Ray::new(Vector3f::new(1.5, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn oriented_box_turns_with_its_axes() {
+        // A unit cube turned 45 degrees about y reaches out to sqrt(2) along x
+        let cuboid = Cuboid::oriented(Vector3f::zero(), Vector3f::one(), Vector3f::new(1.0, 0.0, 1.0), Vector3f::unit_y(), material());
+        let hit = cuboid.hit(&Ray::new(Vector3f::new(-5.0, 0.0, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - (5.0 - 2.0f32.sqrt())).abs() < 1e-4);
+        let bounds = cuboid.bounding_box().unwrap();
+        assert!((bounds.max.x - 2.0f32.sqrt()).abs() < 1e-4 && (bounds.max.y - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::utils::polynomial::solve_quadratic;
+use crate::vectorlib::{aabb::Aabb, frame::Frame, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Deepest subdivision used when intersecting, deep enough for any curve that fits on screen
+const MAX_SUBDIVISION_DEPTH : i32 = 10;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CurveBasis {
+    // Passes through the first and last control point
+    Bezier,
+    // Uniform cubic B-spline, consecutive segments of a strand join with matching curvature
+    BSpline,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum CurveShape {
+    // Flat strip that always faces the incoming ray, cheap and fine for thin hair and grass seen from afar
+    Ribbon,
+    // Round tube, the normal sweeps around the curve across its width
+    Tube,
+}
+
+// Single cubic curve segment with a width that changes linearly along it. Control points are stored
+// in Bezier form whatever basis they were given in. Rays are intersected by recursively splitting the
+// curve in a space where the ray runs down the z axis, until each piece is close enough to a line
+pub struct Curve {
+    control_points: [Point3; 4],
+    widths: [f32; 2],
+    // Range of the strand this segment covers, reported as the u coordinate of hits
+    u_range: [f32; 2],
+    pub shape: CurveShape,
+    pub material : Box<dyn Material>,
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    return a + t * (b - a);
+}
+
+fn evaluate_bezier(points: &[Point3; 4], u: f32) -> Point3 {
+    let a = Vector3f::lerp(&points[0], &points[1], u);
+    let b = Vector3f::lerp(&points[1], &points[2], u);
+    let c = Vector3f::lerp(&points[2], &points[3], u);
+    let d = Vector3f::lerp(&a, &b, u);
+    let e = Vector3f::lerp(&b, &c, u);
+    return Vector3f::lerp(&d, &e, u);
+}
+
+fn bezier_derivative(points: &[Point3; 4], u: f32) -> Vector3f {
+    let s = 1.0 - u;
+    return 3.0 * (s * s * (points[1] - points[0]) + 2.0 * u * s * (points[2] - points[1]) + u * u * (points[3] - points[2]));
+}
+
+// de Casteljau split at the middle into two curves covering [0,0.5] and [0.5,1]
+fn split_bezier(points: &[Point3; 4]) -> ([Point3; 4], [Point3; 4]) {
+    let a = (points[0] + points[1]) / 2.0;
+    let b = (points[1] + points[2]) / 2.0;
+    let c = (points[2] + points[3]) / 2.0;
+    let d = (a + b) / 2.0;
+    let e = (b + c) / 2.0;
+    let middle = (d + e) / 2.0;
+    return ([points[0], a, d, middle], [middle, e, c, points[3]]);
+}
+
+fn b_spline_to_bezier(points: &[Point3; 4]) -> [Point3; 4] {
+    return [
+        (points[0] + 4.0 * points[1] + points[2]) / 6.0,
+        (2.0 * points[1] + points[2]) / 3.0,
+        (points[1] + 2.0 * points[2]) / 3.0,
+        (points[1] + 4.0 * points[2] + points[3]) / 6.0,
+    ];
+}
+
+fn bounds_of(points: &[Point3; 4]) -> Aabb {
+    return Aabb::from_points(points[0], points[1]).grow(points[2]).grow(points[3]);
+}
+
+// Where a ray hits a curve, in the parameters of the whole segment
+struct CurveHit {
+    t: f32,
+    u: f32,
+    // Across the width from 0 to 1, 0.5 on the center line
+    v: f32,
+}
+
+impl Curve {
+    // widths are at the start and end of the segment
+    pub fn new(control_points: [Point3; 4], widths: [f32; 2], basis: CurveBasis, shape: CurveShape, material : Box<dyn Material>) -> Curve {
+        let control_points = match basis {
+            CurveBasis::Bezier => control_points,
+            CurveBasis::BSpline => b_spline_to_bezier(&control_points),
+        };
+        return Curve { control_points, widths, u_range: [0.0, 1.0], shape, material };
+    }
+
+    // Splits a strand of control points into segments, tapering from root_width to tip_width.
+    // Bezier strands need 3k + 1 points and share end points, B-spline strands use every window of
+    // four points. make_material is called once per segment
+    pub fn strand(points: &[Point3], root_width: f32, tip_width: f32, basis: CurveBasis, shape: CurveShape, make_material: impl Fn() -> Box<dyn Material>) -> Vec<Curve> {
+        assert!(points.len() >= 4, "[ERR] Curve strand needs at least four control points");
+        let stride = match basis {
+            CurveBasis::Bezier => 3,
+            CurveBasis::BSpline => 1,
+        };
+        let segment_count = (points.len() - 4) / stride + 1;
+
+        let mut curves = Vec::with_capacity(segment_count);
+        for segment in 0..segment_count {
+            let start = segment * stride;
+            let control_points = [points[start], points[start + 1], points[start + 2], points[start + 3]];
+            let u_range = [segment as f32 / segment_count as f32, (segment + 1) as f32 / segment_count as f32];
+            let widths = u_range.map(|u| lerp(u, root_width, tip_width));
+            let mut curve = Curve::new(control_points, widths, basis, shape, make_material());
+            curve.u_range = u_range;
+            curves.push(curve);
+        }
+        return curves;
+    }
+
+    fn max_width(&self) -> f32 {
+        return self.widths[0].max(self.widths[1]);
+    }
+
+    // Splits until the flatness of the pieces is within a twentieth of the curve width
+    fn subdivision_depth(&self, points: &[Point3; 4]) -> i32 {
+        let mut flatness: f32 = 0.0;
+        for i in 0..2 {
+            let second_difference = points[i] - 2.0 * points[i + 1] + points[i + 2];
+            flatness = flatness.max(second_difference.x.abs()).max(second_difference.y.abs()).max(second_difference.z.abs());
+        }
+        if flatness <= 0.0 {
+            return 0;
+        }
+        let tolerance = self.max_width() * 0.05;
+        let depth = (std::f32::consts::SQRT_2 * 6.0 * flatness / (8.0 * tolerance)).log2() / 2.0;
+        return (depth as i32).clamp(0, MAX_SUBDIVISION_DEPTH);
+    }
+
+    // points are in ray space where the ray starts at the origin and travels along +z at unit speed,
+    // z_max is the distance of the closest hit found so far
+    fn hit_recursive(&self, points: &[Point3; 4], u0: f32, u1: f32, depth: i32, z_min: f32, z_max: f32) -> Option<CurveHit> {
+        let half_width = self.max_width() / 2.0;
+        let bounds = bounds_of(points);
+        if bounds.min.x - half_width > 0.0 || bounds.max.x + half_width < 0.0
+            || bounds.min.y - half_width > 0.0 || bounds.max.y + half_width < 0.0
+            || bounds.min.z - half_width > z_max || bounds.max.z + half_width < z_min {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first, second) = split_bezier(points);
+            let u_middle = (u0 + u1) / 2.0;
+            let first_hit = self.hit_recursive(&first, u0, u_middle, depth - 1, z_min, z_max);
+            let closest = first_hit.as_ref().map_or(z_max, |hit| hit.t);
+            let second_hit = self.hit_recursive(&second, u_middle, u1, depth - 1, z_min, closest);
+            return second_hit.or(first_hit);
+        }
+
+        // Treat the piece as a line and keep only the part between the planes through its end points
+        // perpendicular to the end tangents, so neighbouring pieces don't both report the hit
+        let start_edge = (points[1].y - points[0].y) * -points[0].y + points[0].x * (points[0].x - points[1].x);
+        let end_edge = (points[2].y - points[3].y) * -points[3].y + points[3].x * (points[3].x - points[2].x);
+        if start_edge < 0.0 || end_edge < 0.0 {
+            return None;
+        }
+
+        let segment_x = points[3].x - points[0].x;
+        let segment_y = points[3].y - points[0].y;
+        let length_squared = segment_x * segment_x + segment_y * segment_y;
+        if length_squared == 0.0 {
+            return None;
+        }
+        let w = ((-points[0].x * segment_x - points[0].y * segment_y) / length_squared).clamp(0.0, 1.0);
+        let u = lerp(w, u0, u1);
+
+        let hit_width = lerp(u, self.widths[0], self.widths[1]);
+        let on_curve = evaluate_bezier(points, w);
+        let distance_squared = on_curve.x * on_curve.x + on_curve.y * on_curve.y;
+        if distance_squared > hit_width * hit_width / 4.0 || on_curve.z < z_min || on_curve.z > z_max {
+            return None;
+        }
+
+        // Which side of the center line the ray passes on decides whether v is above or below 0.5
+        let derivative = bezier_derivative(points, w);
+        let distance = distance_squared.sqrt();
+        let side = derivative.x * -on_curve.y + on_curve.x * derivative.y;
+        let v = if side > 0.0 { 0.5 + distance / hit_width } else { 0.5 - distance / hit_width };
+        return Some(CurveHit { t: on_curve.z, u, v });
+    }
+}
+
+impl<'a> Hittable<'a> for Curve {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let speed = ray.direction().magnitude();
+        let ray_frame = Frame::from_z(*ray.origin(), *ray.direction());
+        let local_points = self.control_points.map(|point| ray_frame.point_to_local(&point));
+
+        let depth = self.subdivision_depth(&local_points);
+        let curve_hit = self.hit_recursive(&local_points, 0.0, 1.0, depth, t_min * speed, t_max * speed)?;
+
+        let center = evaluate_bezier(&self.control_points, curve_hit.u);
+        let tangent = bezier_derivative(&self.control_points, curve_hit.u).unit_vector();
+        let radius = lerp(curve_hit.u, self.widths[0], self.widths[1]) / 2.0;
+
+        // Facing the ray, flattened onto the plane perpendicular to the curve
+        let towards_ray = -*ray.direction() / speed;
+        let facing = towards_ray - towards_ray.dot(&tangent) * tangent;
+        let facing = if facing.near_zero() { tangent.orthonormal_basis().0 } else { facing.unit_vector() };
+
+        let (t, normal) = match self.shape {
+            CurveShape::Ribbon => (curve_hit.t / speed, facing),
+            CurveShape::Tube => {
+                // Intersect the cylinder the tube locally follows, around the tangent line through the hit
+                let offset = *ray.origin() - center;
+                let across_origin = offset - offset.dot(&tangent) * tangent;
+                let across_direction = *ray.direction() - ray.direction().dot(&tangent) * tangent;
+                let (roots, count) = solve_quadratic(
+                    across_direction.dot(&across_direction) as f64,
+                    2.0 * across_origin.dot(&across_direction) as f64,
+                    (across_origin.dot(&across_origin) - radius * radius) as f64,
+                );
+                // The curve test passes rays that just miss the cylinder, those graze it where they come closest.
+                // Rays running along the tube keep the depth the curve test found
+                let t = if across_direction.near_zero() {
+                    curve_hit.t / speed
+                } else if count > 0 {
+                    roots[..count].iter().fold(f64::INFINITY, |closest, root| closest.min(*root)) as f32
+                } else {
+                    -across_origin.dot(&across_direction) / across_direction.dot(&across_direction)
+                };
+                let normal = across_origin + t * across_direction;
+                let normal = if normal.near_zero() { facing } else { normal.unit_vector() };
+                (t, normal)
+            }
+        };
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // u runs along the whole strand and v across the width
+        let u = lerp(curve_hit.u, self.u_range[0], self.u_range[1]);
+        let dpdu = bezier_derivative(&self.control_points, curve_hit.u) / (self.u_range[1] - self.u_range[0]);
+        let dpdv = 2.0 * radius * tangent.cross(&normal);
+        let hit_data = HitData::new(t, ray.at(t), normal, ray.direction(), &normal, self.material.as_ref())
+            .with_uv(u, curve_hit.v)
+            .with_tangents(dpdu, dpdv);
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The curve stays inside the hull of its control points
+        let half_width = self.max_width() / 2.0;
+        let bounds = bounds_of(&self.control_points);
+        return Some(Aabb::new(bounds.min - Vector3f::uniform(half_width), bounds.max + Vector3f::uniform(half_width)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3f, b: Vector3f) -> bool {
+        return (a - b).magnitude() < 1e-4;
+    }
+
+    // Straight along x from -1 to 1, five units in front of the origin
+    fn straight(shape: CurveShape) -> Curve {
+        let points = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].map(|x| Vector3f::new(x, 0.0, -5.0));
+        return Curve::new(points, [0.2, 0.2], CurveBasis::Bezier, shape, Box::new(Lambertian::new(Vector3f::one())));
+    }
+
+    fn down_z(x: f32, y: f32) -> Ray {
+        return Ray::new(Vector3f::new(x, y, 0.0), Vector3f::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn bezier_split_and_basis_change() {
+        let points = [Vector3f::zero(), Vector3f::new(1.0, 2.0, 0.0), Vector3f::new(3.0, 2.0, 1.0), Vector3f::new(4.0, 0.0, 0.0)];
+        assert!(close(evaluate_bezier(&points, 0.0), points[0]));
+        assert!(close(evaluate_bezier(&points, 1.0), points[3]));
+
+        let (first, second) = split_bezier(&points);
+        assert!(close(first[3], evaluate_bezier(&points, 0.5)));
+        assert!(close(evaluate_bezier(&first, 0.5), evaluate_bezier(&points, 0.25)));
+        assert!(close(evaluate_bezier(&second, 0.5), evaluate_bezier(&points, 0.75)));
+
+        // A B-spline over evenly spaced points on a line runs from the second point to the third
+        let line = [0.0, 1.0, 2.0, 3.0].map(|x| Vector3f::new(x, 0.0, 0.0));
+        let bezier = b_spline_to_bezier(&line);
+        assert!(close(bezier[0], line[1]) && close(bezier[3], line[2]));
+    }
+
+    #[test]
+    fn ribbon_hits_across_its_width_only() {
+        let curve = straight(CurveShape::Ribbon);
+        let hit = curve.hit(&down_z(0.0, 0.0), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-3);
+        assert!((hit.u - 0.5).abs() < 1e-2 && (hit.v - 0.5).abs() < 1e-2);
+        assert!(close(hit.normal, Vector3f::new(0.0, 0.0, 1.0)));
+
+        let off_center = curve.hit(&down_z(0.5, 0.05), 0.001, f32::INFINITY).unwrap();
+        assert!((off_center.u - 0.75).abs() < 1e-2);
+        assert!((off_center.v - 0.5).abs() > 0.2);
+
+        assert!(curve.hit(&down_z(0.0, 0.15), 0.001, f32::INFINITY).is_none());
+        assert!(curve.hit(&down_z(1.5, 0.0), 0.001, f32::INFINITY).is_none());
+        assert!(curve.hit(&down_z(0.0, 0.0), 0.001, 4.0).is_none());
+    }
+
+    #[test]
+    fn tube_hits_its_round_surface() {
+        let curve = straight(CurveShape::Tube);
+        let hit = curve.hit(&down_z(0.0, 0.05), 0.001, f32::INFINITY).unwrap();
+        let depth = (0.1f32 * 0.1 - 0.05 * 0.05).sqrt();
+        assert!((hit.t - (5.0 - depth)).abs() < 1e-3);
+        assert!(close(hit.normal, Vector3f::new(0.0, 0.5, depth / 0.1)));
+        assert!(curve.hit(&down_z(0.0, 0.15), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn bent_curve_is_hit_on_its_path() {
+        let points = [Vector3f::new(-1.0, 0.0, -5.0), Vector3f::new(-0.5, 1.0, -5.0), Vector3f::new(0.5, 1.0, -5.0), Vector3f::new(1.0, 0.0, -5.0)];
+        let curve = Curve::new(points, [0.05, 0.05], CurveBasis::Bezier, CurveShape::Ribbon, Box::new(Lambertian::new(Vector3f::one())));
+        let top = evaluate_bezier(&points, 0.5);
+        let hit = curve.hit(&down_z(top.x, top.y), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.u - 0.5).abs() < 1e-2);
+        assert!(curve.hit(&down_z(0.0, 0.0), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn strands_split_into_segments_covering_the_strand() {
+        let points: Vec<Point3> = (0..7).map(|i| Vector3f::new(i as f32, 0.0, 0.0)).collect();
+        let material = || -> Box<dyn Material> { Box::new(Lambertian::new(Vector3f::one())) };
+        let bezier = Curve::strand(&points, 0.2, 0.0, CurveBasis::Bezier, CurveShape::Ribbon, material);
+        assert_eq!(bezier.len(), 2);
+        assert_eq!(bezier[1].u_range, [0.5, 1.0]);
+        assert_eq!(bezier[1].widths, [0.1, 0.0]);
+
+        let b_spline = Curve::strand(&points, 0.2, 0.2, CurveBasis::BSpline, CurveShape::Ribbon, material);
+        assert_eq!(b_spline.len(), 4);
+        assert!(close(b_spline[0].control_points[3], b_spline[1].control_points[0]));
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::PI;
+
+use crate::utils::polynomial::solve_quadratic;
+use crate::vectorlib::{aabb::Aabb, frame::Frame, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Capped cylinder standing on base and extending height along axis
+pub struct Cylinder {
+    pub frame: Frame,
+    pub radius: f32,
+    pub height: f32,
+    pub material : Box<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3, axis: Vector3f, radius: f32, height: f32, material : Box<dyn Material>) -> Cylinder {
+        return Cylinder { frame: Frame::from_z(base, axis), radius, height, material };
+    }
+}
+
+// Angle around the local z axis mapped to [0,1]
+pub fn azimuth_u(local_point: &Point3) -> f32 {
+    return (local_point.y.atan2(local_point.x) + PI) / (2.0 * PI);
+}
+
+// Derivative of a point with respect to azimuth_u, circling the local z axis
+pub fn azimuth_tangent(local_point: &Point3) -> Vector3f {
+    return 2.0 * PI * Vector3f::new(-local_point.y, local_point.x, 0.0);
+}
+
+// Direction away from the local z axis, scaled to the radius so it matches v running from center to rim
+pub fn radial_tangent(local_point: &Point3, radius: f32) -> Vector3f {
+    let distance = (local_point.x * local_point.x + local_point.y * local_point.y).sqrt().max(f32::EPSILON);
+    return radius * Vector3f::new(local_point.x, local_point.y, 0.0) / distance;
+}
+
+impl<'a> Hittable<'a> for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let local_ray = self.frame.ray_to_local(ray);
+        let o = local_ray.origin();
+        let d = local_ray.direction();
+
+        let mut closest: Option<(f32, Vector3f, f32, f32)> = None;
+        let mut closest_t = t_max;
+
+        // Side, x^2 + y^2 = r^2 between the caps
+        let (roots, count) = solve_quadratic(
+            (d.x * d.x + d.y * d.y) as f64,
+            2.0 * (o.x * d.x + o.y * d.y) as f64,
+            (o.x * o.x + o.y * o.y - self.radius * self.radius) as f64,
+        );
+        for root in roots.iter().take(count) {
+            let t = *root as f32;
+            let z = o.z + t * d.z;
+            if t < t_min || closest_t < t || z < 0.0 || self.height < z {
+                continue;
+            }
+            let local_hit = local_ray.at(t);
+            let normal = Vector3f::new(local_hit.x, local_hit.y, 0.0) / self.radius;
+            closest = Some((t, normal, azimuth_u(&local_hit), z / self.height));
+            closest_t = t;
+        }
+
+        // Caps at z = 0 and z = height, v goes from the center to the rim
+        if d.z != 0.0 {
+            for (cap_z, cap_normal) in [(0.0, -Vector3f::unit_z()), (self.height, Vector3f::unit_z())] {
+                let t = (cap_z - o.z) / d.z;
+                if t < t_min || closest_t < t {
+                    continue;
+                }
+                let local_hit = local_ray.at(t);
+                let distance_squared = local_hit.x * local_hit.x + local_hit.y * local_hit.y;
+                if distance_squared > self.radius * self.radius {
+                    continue;
+                }
+                closest = Some((t, cap_normal, azimuth_u(&local_hit), distance_squared.sqrt() / self.radius));
+                closest_t = t;
+            }
+        }
+
+        let (t, local_normal, u, v) = closest?;
+        let outward_normal = self.frame.vector_to_world(&local_normal);
+        let local_hit = local_ray.at(t);
+        // The side has v along the axis, the caps from the center outwards
+        let local_dpdv = if local_normal.z == 0.0 { Vector3f::new(0.0, 0.0, self.height) } else { radial_tangent(&local_hit, self.radius) };
+
+        let hit_data = HitData::new(t, ray.at(t), outward_normal, ray.direction(), &outward_normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.frame.vector_to_world(&azimuth_tangent(&local_hit)), self.frame.vector_to_world(&local_dpdv));
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local_min = Vector3f::new(-self.radius, -self.radius, 0.0);
+        let local_max = Vector3f::new(self.radius, self.radius, self.height);
+        return Some(self.frame.bounds_to_world(&local_min, &local_max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_the_side_and_the_caps() {
+        let cylinder = Cylinder::new(Vector3f::zero(), Vector3f::unit_y(), 1.0, 2.0, Box::new(Lambertian::new(Vector3f::one())));
+        let side = cylinder.hit(&Ray::new(Vector3f::new(-3.0, 1.0, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).unwrap();
+        assert!((side.t - 2.0).abs() < 1e-5 && side.normal.x < -0.999);
+
+        let cap = cylinder.hit(&Ray::new(Vector3f::new(0.5, 5.0, 0.0), -Vector3f::unit_y()), 0.001, f32::INFINITY).unwrap();
+        assert!((cap.t - 3.0).abs() < 1e-5 && cap.normal.y > 0.999);
+
+        // Above the top and beside the side
+        assert!(cylinder.hit(&Ray::new(Vector3f::new(-3.0, 2.5, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).is_none());
+        assert!(cylinder.hit(&Ray::new(Vector3f::new(1.5, 5.0, 0.0), -Vector3f::unit_y()), 0.001, f32::INFINITY).is_none());
+    }
+}
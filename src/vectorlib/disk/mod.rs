@@ -0,0 +1,77 @@
+use std::f32::consts::PI;
+
+use crate::vectorlib::{aabb::{Aabb, FLAT_BOX_PADDING}, hit::*, plane::hit_plane, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Flat circle facing along normal
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Vector3f,
+    pub radius: f32,
+    pub material : Box<dyn Material>,
+    tangent: Vector3f,
+    bitangent: Vector3f,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vector3f, radius: f32, material : Box<dyn Material>) -> Disk {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        return Disk { center, normal, radius, material, tangent, bitangent };
+    }
+}
+
+impl<'a> Hittable<'a> for Disk {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let t = hit_plane(ray, &self.center, &self.normal, t_min, t_max)?;
+        let hit_point = ray.at(t);
+
+        let offset = hit_point - self.center;
+        let distance_squared = offset.square_magnitude();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        // Polar mapping, u goes around the rim and v goes from the center (0) to the edge (1)
+        let angle = offset.dot(&self.bitangent).atan2(offset.dot(&self.tangent));
+        let u = (angle + PI) / (2.0 * PI);
+        let distance = distance_squared.sqrt();
+        let v = distance / self.radius;
+        let around = self.normal.cross(&offset);
+        let dpdu = 2.0 * PI * around;
+        let dpdv = self.radius * offset / distance.max(f32::EPSILON);
+
+        let hit_data = HitData::new(t, hit_point, self.normal, ray.direction(), &self.normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(dpdu, dpdv);
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Extent along each axis is radius * sin(angle between the normal and that axis)
+        let extent = Vector3f::new(
+            (1.0 - self.normal.x * self.normal.x).max(0.0).sqrt(),
+            (1.0 - self.normal.y * self.normal.y).max(0.0).sqrt(),
+            (1.0 - self.normal.z * self.normal.z).max(0.0).sqrt(),
+        ) * self.radius;
+        return Some(Aabb::new(self.center - extent, self.center + extent).padded(FLAT_BOX_PADDING));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn hits_inside_the_radius_only() {
+        let disk = Disk::new(Vector3f::new(0.0, 0.0, -2.0), Vector3f::unit_z(), 1.0, Box::new(Lambertian::new(Vector3f::one())));
+        let hit = disk.hit(&Ray::new(Vector3f::new(0.5, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5 && hit.hit_front_face);
+        assert!((hit.v - 0.5).abs() < 1e-5);
+        assert!(disk.hit(&Ray::new(Vector3f::new(1.1, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).is_none());
+
+        let bounds = disk.bounding_box().unwrap();
+        assert!(bounds.min.x <= -1.0 && bounds.max.x >= 1.0 && bounds.min.z < -2.0 && bounds.max.z > -2.0);
+    }
+}
@@ -0,0 +1,52 @@
+use crate::vectorlib::{aabb::Aabb, point3::Point3, ray::Ray, vector3::*};
+
+// Orthonormal coordinate frame placed at origin. Shapes use it to intersect in their own local space,
+// since the axes are unit length a ray keeps the same t in both spaces
+#[derive(Copy, Clone)]
+pub struct Frame {
+    pub origin: Point3,
+    pub x: Vector3f,
+    pub y: Vector3f,
+    pub z: Vector3f,
+}
+
+impl Frame {
+    // x and y are assumed orthonormal, z completes a right handed frame
+    pub fn new(origin: Point3, x: Vector3f, y: Vector3f) -> Frame {
+        let z = x.cross(&y);
+        return Frame { origin, x, y, z };
+    }
+
+    // Frame whose z axis points along the given direction, x and y are arbitrary
+    pub fn from_z(origin: Point3, z: Vector3f) -> Frame {
+        let z = z.unit_vector();
+        let (x, y) = z.orthonormal_basis();
+        return Frame { origin, x, y, z };
+    }
+
+    pub fn vector_to_local(&self, vector: &Vector3f) -> Vector3f {
+        return Vector3f::new(vector.dot(&self.x), vector.dot(&self.y), vector.dot(&self.z));
+    }
+
+    pub fn point_to_local(&self, point: &Point3) -> Point3 {
+        return self.vector_to_local(&(*point - self.origin));
+    }
+
+    pub fn vector_to_world(&self, vector: &Vector3f) -> Vector3f {
+        return vector.x * self.x + vector.y * self.y + vector.z * self.z;
+    }
+
+    pub fn point_to_world(&self, point: &Point3) -> Point3 {
+        return self.origin + self.vector_to_world(point);
+    }
+
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        return Ray::new_at_time(self.point_to_local(ray.origin()), self.vector_to_local(ray.direction()), ray.time());
+    }
+
+    // World space box around a local space box, found from its eight corners
+    pub fn bounds_to_world(&self, local_min: &Point3, local_max: &Point3) -> Aabb {
+        let corners = Aabb::new(*local_min, *local_max).corners().map(|corner| self.point_to_world(&corner));
+        return corners.iter().fold(Aabb::new(corners[0], corners[0]), |bounds, corner| bounds.grow(*corner));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::loaders::image::Image;
+use crate::material::*;
+use crate::utils::noise::Perlin;
+use crate::vectorlib::{aabb::{Aabb, FLAT_BOX_PADDING}, hit::*, mesh::intersect_triangle, point3::*, ray::*, vector3::*};
+
+// Grid coordinates (i, j) of the three corners of one triangle
+type GridTriangle = [(usize, usize); 3];
+
+// Terrain over the xz rectangle from origin to origin + size, with one height sample per grid vertex.
+// Samples are in [0,1] and scaled by size.y, stored x fastest then z. Each cell is split into two
+// triangles and rays walk the cells they cross in order, so only a thin line of cells is ever tested
+pub struct Heightfield {
+    pub resolution: [usize; 2],
+    pub origin: Point3,
+    pub size: Vector3f,
+    pub material: Box<dyn Material>,
+    heights: Vec<f32>,
+    normals: Vec<Vector3f>,
+    // Lowest and highest vertex of each cell, used to skip cells the ray passes above or below
+    cell_ranges: Vec<(f32, f32)>,
+    bounds: Aabb,
+}
+
+impl Heightfield {
+    pub fn new(resolution: [usize; 2], samples: Vec<f32>, origin: Point3, size: Vector3f, material: Box<dyn Material>) -> Heightfield {
+        let [nx, nz] = resolution;
+        assert!(nx >= 2 && nz >= 2, "[ERR] Heightfield needs at least two samples on each axis");
+        assert_eq!(samples.len(), nx * nz, "[ERR] Heightfield needs one sample per grid vertex");
+
+        let heights: Vec<f32> = samples.iter().map(|sample| origin.y + size.y * sample).collect();
+        let height = |i: usize, j: usize| heights[j * nx + i];
+        let cell_width = size.x / (nx - 1) as f32;
+        let cell_depth = size.z / (nz - 1) as f32;
+
+        // Smooth normals from central differences, one sided along the borders
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let slope_x = (height(right, j) - height(left, j)) / ((right - left) as f32 * cell_width);
+                let slope_z = (height(i, front) - height(i, back)) / ((front - back) as f32 * cell_depth);
+                normals.push(Vector3f::new(-slope_x, 1.0, -slope_z).unit_vector());
+            }
+        }
+
+        let mut cell_ranges = Vec::with_capacity((nx - 1) * (nz - 1));
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                let corners = [height(i, j), height(i + 1, j), height(i, j + 1), height(i + 1, j + 1)];
+                let low = corners.iter().fold(f32::INFINITY, |low, h| low.min(*h));
+                let high = corners.iter().fold(f32::NEG_INFINITY, |high, h| high.max(*h));
+                cell_ranges.push((low, high));
+            }
+        }
+
+        let low = heights.iter().fold(f32::INFINITY, |low, h| low.min(*h));
+        let high = heights.iter().fold(f32::NEG_INFINITY, |high, h| high.max(*h));
+        let bounds = Aabb::new(
+            Vector3f::new(origin.x, low, origin.z),
+            Vector3f::new(origin.x + size.x, high, origin.z + size.z),
+        ).padded(FLAT_BOX_PADDING);
+
+        return Heightfield { resolution, origin, size, material, heights, normals, cell_ranges, bounds };
+    }
+
+    // Greyscale heightmap, the brightness of each pixel is its height. Image rows run along +z.
+    // An image only one pixel across on an axis has that row or column repeated so there are cells to hit,
+    // an empty one gives flat ground
+    pub fn from_image(image: &Image, origin: Point3, size: Vector3f, material: Box<dyn Material>) -> Heightfield {
+        let resolution = [image.width.max(2), image.height.max(2)];
+        let mut samples = Vec::with_capacity(resolution[0] * resolution[1]);
+        for y in 0..resolution[1] {
+            for x in 0..resolution[0] {
+                if image.width == 0 || image.height == 0 {
+                    samples.push(0.0);
+                    continue;
+                }
+                samples.push(image.luminance(x.min(image.width - 1), y.min(image.height - 1)));
+            }
+        }
+        return Heightfield::new(resolution, samples, origin, size, material);
+    }
+
+    // Rolling terrain from fbm noise, frequency is the number of noise cells across the whole field
+    pub fn from_fbm(resolution: [usize; 2], origin: Point3, size: Vector3f, seed: u64, octaves: u32, frequency: f32, material: Box<dyn Material>) -> Heightfield {
+        let perlin = Perlin::new(seed);
+        let [nx, nz] = resolution;
+        let mut samples = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let point = Vector3f::new(
+                    frequency * i as f32 / (nx - 1).max(1) as f32,
+                    0.0,
+                    frequency * j as f32 / (nz - 1).max(1) as f32,
+                );
+                samples.push((perlin.fbm(&point, octaves) * 0.5 + 0.5).clamp(0.0, 1.0));
+            }
+        }
+        return Heightfield::new(resolution, samples, origin, size, material);
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        let [nx, nz] = self.resolution;
+        return Vector3f::new(
+            self.origin.x + self.size.x * i as f32 / (nx - 1) as f32,
+            self.heights[j * nx + i],
+            self.origin.z + self.size.z * j as f32 / (nz - 1) as f32,
+        );
+    }
+
+    // Nearest hit on either triangle of a cell, returns (t, vertex indices, b1, b2)
+    fn hit_cell(&self, i: usize, j: usize, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, GridTriangle, f32, f32)> {
+        // Both triangles wind so their geometric normal points up
+        let triangles = [
+            [(i, j), (i, j + 1), (i + 1, j + 1)],
+            [(i, j), (i + 1, j + 1), (i + 1, j)],
+        ];
+        let mut closest = None;
+        let mut closest_t = t_max;
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|(i, j)| self.vertex(i, j));
+            if let Some((t, b1, b2)) = intersect_triangle(a, b, c, ray, t_min, closest_t) {
+                closest_t = t;
+                closest = Some((t, triangle, b1, b2));
+            }
+        }
+        return closest;
+    }
+}
+
+impl<'a> Hittable<'a> for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let (t_enter, t_exit) = self.bounds.hit_range(ray, t_min, t_max)?;
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let cell_counts = [self.resolution[0] - 1, self.resolution[1] - 1];
+        let cell_sizes = [self.size.x / cell_counts[0] as f32, self.size.z / cell_counts[1] as f32];
+        let grid_origin = [self.origin.x, self.origin.z];
+        let ray_origin = [origin.x, origin.z];
+        let ray_direction = [direction.x, direction.z];
+
+        // 2D DDA over the cells in xz, starting from the cell the ray enters the bounds in
+        let entry = ray.at(t_enter);
+        let entry = [entry.x, entry.z];
+        let mut cell = [0_usize; 2];
+        let mut step = [0_isize; 2];
+        let mut t_next = [f32::INFINITY; 2];
+        let mut t_delta = [f32::INFINITY; 2];
+        for axis in 0..2 {
+            let position = ((entry[axis] - grid_origin[axis]) / cell_sizes[axis]).floor();
+            cell[axis] = (position.max(0.0) as usize).min(cell_counts[axis] - 1);
+            if ray_direction[axis] != 0.0 {
+                step[axis] = if ray_direction[axis] > 0.0 { 1 } else { -1 };
+                let boundary_cell = if step[axis] > 0 { cell[axis] + 1 } else { cell[axis] };
+                let boundary = grid_origin[axis] + boundary_cell as f32 * cell_sizes[axis];
+                t_next[axis] = (boundary - ray_origin[axis]) / ray_direction[axis];
+                t_delta[axis] = cell_sizes[axis] / ray_direction[axis].abs();
+            }
+        }
+
+        let mut t_cell_enter = t_enter;
+        loop {
+            let t_cell_exit = t_next[0].min(t_next[1]).min(t_exit);
+            let (low, high) = self.cell_ranges[cell[1] * cell_counts[0] + cell[0]];
+            let y_enter = origin.y + t_cell_enter * direction.y;
+            let y_exit = origin.y + t_cell_exit * direction.y;
+            let overlaps = y_enter.min(y_exit) <= high + FLAT_BOX_PADDING && low - FLAT_BOX_PADDING <= y_enter.max(y_exit);
+
+            // Cells are visited front to back so the first hit is the closest one
+            if overlaps {
+                if let Some((t, triangle, b1, b2)) = self.hit_cell(cell[0], cell[1], ray, t_min, t_max) {
+                    let [a, b, c] = triangle.map(|(i, j)| self.vertex(i, j));
+                    let geometric_normal = (b - a).cross(&(c - a)).unit_vector();
+                    let [n0, n1, n2] = triangle.map(|(i, j)| self.normals[j * self.resolution[0] + i]);
+                    let smooth_normal = (1.0 - b1 - b2) * n0 + b1 * n1 + b2 * n2;
+
+                    // u and v follow x and z across the whole field, the tangents climb with the slope of the triangle
+                    let hit_point = ray.at(t);
+                    let u = (hit_point.x - self.origin.x) / self.size.x;
+                    let v = (hit_point.z - self.origin.z) / self.size.z;
+                    let dpdu = self.size.x * Vector3f::new(1.0, -geometric_normal.x / geometric_normal.y, 0.0);
+                    let dpdv = self.size.z * Vector3f::new(0.0, -geometric_normal.z / geometric_normal.y, 1.0);
+
+                    let hit_data = HitData::new(t, hit_point, geometric_normal, direction, &geometric_normal, self.material.as_ref())
+                        .with_uv(u, v)
+                        .with_tangents(dpdu, dpdv)
+                        .with_shading_normal(smooth_normal);
+                    return Some(hit_data);
+                }
+            }
+
+            if t_cell_exit >= t_exit {
+                return None;
+            }
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= cell_counts[axis] as isize {
+                return None;
+            }
+            cell[axis] = next as usize;
+            t_cell_enter = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return Some(self.bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_image_pads_degenerate_images() {
+        let material = || -> Box<dyn Material> { Box::new(Lambertian::new(Vector3f::one())) };
+        let column = Image::new(1, 3, vec![Vector3f::uniform(0.5); 3]);
+        let heightfield = Heightfield::from_image(&column, Vector3f::zero(), Vector3f::one(), material());
+        assert_eq!(heightfield.resolution, [2, 3]);
+
+        // A ray straight down onto the repeated column lands at the pixel's height
+        let ray = Ray::new(Vector3f::new(0.5, 2.0, 0.5), -Vector3f::unit_y());
+        let hit = heightfield.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        assert!((hit.at.y - 0.5).abs() < 1e-4);
+
+        let empty = Image::new(0, 0, Vec::new());
+        assert_eq!(Heightfield::from_image(&empty, Vector3f::zero(), Vector3f::one(), material()).resolution, [2, 2]);
+    }
+}
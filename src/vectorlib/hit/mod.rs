@@ -1,6 +1,6 @@
 use std::rc::Rc;
 
-use crate::vectorlib::{point3::*, ray::*, vector3::*};
+use crate::vectorlib::{aabb::Aabb, bvh::Bvh, frame::Frame, point3::*, ray::*, vector3::*};
 use crate::material::*;
 
 #[derive(Clone)]
@@ -10,6 +10,18 @@ pub struct HitData<'a>{
     pub t: f32,
     pub hit_front_face: bool,
     pub material : &'a dyn Material,
+    // Interpolated per-vertex colour for meshes that have one, materials multiply it into their albedo
+    pub vertex_color: Option<Vector3f>,
+    // Surface coordinates of the hit, each primitive documents its own mapping
+    pub u: f32,
+    pub v: f32,
+    // How the hit point moves as u and v change. Primitives without a parameterization leave an
+    // arbitrary pair of unit tangents perpendicular to the normal
+    pub dpdu: Vector3f,
+    pub dpdv: Vector3f,
+    // Normal used for shading, such as an interpolated vertex normal. normal stays the true surface
+    // normal and both always face the same side
+    pub shading_normal: Vector3f,
 }
 
 impl<'a> HitData<'a>{
@@ -22,7 +34,13 @@ impl<'a> HitData<'a>{
             t,
             // Set to some default for now
             hit_front_face: (false),
-            material : material,
+            material,
+            vertex_color: None,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vector3f::zero(),
+            dpdv: Vector3f::zero(),
+            shading_normal: normal,
         };
 
         // Determine if we hit front or back
@@ -33,33 +51,134 @@ impl<'a> HitData<'a>{
             true => outward_normal.clone(),
             false => -outward_normal.clone(),
         };
+        hit_data.shading_normal = hit_data.normal;
+        (hit_data.dpdu, hit_data.dpdv) = outward_normal.unit_vector().orthonormal_basis();
         return hit_data;
     }
 
+    pub fn with_uv(mut self, u: f32, v: f32) -> HitData<'a>{
+        self.u = u;
+        self.v = v;
+        return self;
+    }
+
+    // Degenerate tangents, such as at the pole of a sphere, keep the default pair
+    pub fn with_tangents(mut self, dpdu: Vector3f, dpdv: Vector3f) -> HitData<'a>{
+        if !dpdu.near_zero() && !dpdv.near_zero() {
+            self.dpdu = dpdu;
+            self.dpdv = dpdv;
+        }
+        return self;
+    }
+
+    // shading_normal is flipped onto the same side as the geometric normal if needed
+    pub fn with_shading_normal(mut self, shading_normal: Vector3f) -> HitData<'a>{
+        if shading_normal.near_zero() {
+            return self;
+        }
+        let shading_normal = shading_normal.unit_vector();
+        self.shading_normal = if shading_normal.dot(&self.normal) < 0.0 { -shading_normal } else { shading_normal };
+        return self;
+    }
+
+    // Orthonormal frame around the shading normal with x following dpdu, for materials that work in local space
+    pub fn shading_frame(&self) -> Frame {
+        let z = self.shading_normal;
+        let x = self.dpdu - self.dpdu.dot(&z) * z;
+        let x = if x.near_zero() { z.orthonormal_basis().0 } else { x.unit_vector() };
+        return Frame::new(self.at, x, z.cross(&x));
+    }
+
 }
 
+// Gap left after each hit when walking along a ray so the same surface isn't found twice
+pub(crate) const HIT_STEP_TOLERANCE : f32 = 0.0001;
+// Stops runaway walks through degenerate geometry
+pub(crate) const MAX_HITS_ALONG_RAY : usize = 64;
+
 pub trait Hittable<'a> {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData>;
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>>;
+    // None for unbounded objects
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Every surface crossing in [t_min, t_max] in order. For closed objects hit_front_face tells entries
+    // from exits, so consecutive hits give the intervals of the ray that are inside the object
+    fn hits_along(&self, ray: &Ray, t_min: f32, t_max: f32) -> Vec<HitData<'_>> {
+        let mut hits = Vec::new();
+        let mut t = t_min;
+        while hits.len() < MAX_HITS_ALONG_RAY {
+            match self.hit(ray, t, t_max) {
+                Some(hit) => {
+                    t = hit.t + HIT_STEP_TOLERANCE;
+                    hits.push(hit);
+                }
+                None => break,
+            }
+        }
+        return hits;
+    }
 }
 
 pub struct HittableList<'a> {
     objects: Vec<Rc<Box<dyn Hittable<'a> + 'a>>>,
+    // Built on request by build_bvh, unbounded objects (planes) can't go in it and are always tested
+    bvh: Option<Bvh>,
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl<'a> Default for HittableList<'a> {
+    fn default() -> HittableList<'a> {
+        return HittableList::new();
+    }
 }
 
 impl<'a> HittableList<'a> {
     pub fn new() -> HittableList<'a> {
-        HittableList { objects: Vec::new() }
+        HittableList { objects: Vec::new(), bvh: None, bounded: Vec::new(), unbounded: Vec::new() }
     }
 
     pub fn add(&mut self, object: impl Hittable<'a> + 'a) {
         self.objects.push(Rc::new(Box::new(object)));
+        // Any existing hierarchy no longer covers every object
+        self.bvh = None;
     }
 
     pub fn clear_all(&mut self){
         self.objects.clear();
+        self.bvh = None;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.objects.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.objects.is_empty();
     }
 
-    pub fn hit(&self,ray : &Ray, t_min : f32, t_max : f32) -> Option<HitData>{
+    // Worth calling once the scene is built if it has more than a handful of objects
+    pub fn build_bvh(&mut self){
+        let mut boxes = Vec::new();
+        self.bounded.clear();
+        self.unbounded.clear();
+        for (index, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => {
+                    boxes.push(bounds);
+                    self.bounded.push(index);
+                }
+                None => self.unbounded.push(index),
+            }
+        }
+        self.bvh = Some(Bvh::new(&boxes));
+    }
+
+    pub fn hit(&self,ray : &Ray, t_min : f32, t_max : f32) -> Option<HitData<'_>>{
+        if let Some(bvh) = &self.bvh {
+            return self.hit_with_bvh(bvh, ray, t_min, t_max);
+        }
+
         let mut closest_hit : Option<HitData> = None;
         let mut closest_t : f32 = t_max;
 
@@ -82,4 +201,87 @@ impl<'a> HittableList<'a> {
 
         return closest_hit;
     }
-}
\ No newline at end of file
+
+    fn hit_with_bvh(&self, bvh : &Bvh, ray : &Ray, t_min : f32, t_max : f32) -> Option<HitData<'_>>{
+        let mut closest_hit : Option<HitData> = None;
+        let mut closest_t : f32 = t_max;
+
+        for index in self.unbounded.iter(){
+            if let Some(hit) = self.objects[*index].hit(ray, t_min, closest_t) {
+                closest_t = hit.t;
+                closest_hit = Some(hit);
+            }
+        }
+
+        bvh.traverse(ray, t_min, closest_t, |primitive, t_min, closest_t| {
+            let hit = self.objects[self.bounded[primitive]].hit(ray, t_min, closest_t)?;
+            let t = hit.t;
+            closest_hit = Some(hit);
+            return Some(t);
+        });
+
+        return closest_hit;
+    }
+}
+
+// Lets a whole list be nested in another list or placed with an Instance
+impl<'a> Hittable<'a> for HittableList<'a> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        return HittableList::hit(self, ray, t_min, t_max);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut bounds: Option<Aabb> = None;
+        for object in self.objects.iter() {
+            let object_bounds = object.bounding_box()?;
+            bounds = Some(match bounds {
+                Some(bounds) => bounds.surrounding(&object_bounds),
+                None => object_bounds,
+            });
+        }
+        return bounds;
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3f, b: Vector3f) -> bool {
+        return (a - b).magnitude() < 1e-4;
+    }
+
+    #[test]
+    fn shading_normal_stays_on_the_geometric_side() {
+        let material = Lambertian::new(Vector3f::one());
+        let normal = Vector3f::new(0.0, 0.0, 1.0);
+        let hit = HitData::new(1.0, Vector3f::zero(), normal, &Vector3f::new(0.0, 0.0, -1.0), &normal, &material);
+        assert!(hit.hit_front_face);
+        assert!((hit.dpdu.dot(&normal)).abs() < 1e-5 && (hit.dpdv.dot(&normal)).abs() < 1e-5);
+
+        let tilted = hit.clone().with_shading_normal(Vector3f::new(0.0, -2.0, -2.0));
+        assert!(close(tilted.shading_normal, Vector3f::new(0.0, 0.5f32.sqrt(), 0.5f32.sqrt())));
+        assert!(close(tilted.normal, normal));
+        assert!(close(hit.clone().with_shading_normal(Vector3f::zero()).shading_normal, normal));
+
+        // Hits from behind flip both normals towards the ray
+        let back = HitData::new(1.0, Vector3f::zero(), normal, &Vector3f::new(0.0, 0.0, 1.0), &normal, &material);
+        assert!(!back.hit_front_face);
+        assert!(close(back.with_shading_normal(Vector3f::new(0.0, 0.0, 1.0)).shading_normal, -normal));
+    }
+
+    #[test]
+    fn shading_frame_follows_dpdu() {
+        let material = Lambertian::new(Vector3f::one());
+        let normal = Vector3f::new(0.0, 0.0, 1.0);
+        let hit = HitData::new(1.0, Vector3f::zero(), normal, &Vector3f::new(0.0, 0.0, -1.0), &normal, &material)
+            .with_tangents(Vector3f::new(3.0, 0.0, 1.0), Vector3f::new(0.0, 2.0, 0.0));
+        let frame = hit.shading_frame();
+        assert!(close(frame.vector_to_world(&Vector3f::new(1.0, 0.0, 0.0)), Vector3f::new(1.0, 0.0, 0.0)));
+        assert!(close(frame.vector_to_world(&Vector3f::new(0.0, 1.0, 0.0)), Vector3f::new(0.0, 1.0, 0.0)));
+        assert!(close(frame.vector_to_world(&Vector3f::new(0.0, 0.0, 1.0)), normal));
+
+        // Degenerate tangents keep the default pair
+        let kept = hit.clone().with_tangents(Vector3f::zero(), Vector3f::new(1.0, 0.0, 0.0));
+        assert!(close(kept.dpdu, hit.dpdu));
+    }
+}
@@ -0,0 +1,141 @@
+use std::rc::Rc;
+
+use crate::vectorlib::{aabb::Aabb, hit::*, ray::*, transform::{Keyframe, Transform}, vector3::*};
+
+// Places shared geometry in the world with a transform. The geometry is behind an Rc so one mesh
+// can be instanced any number of times while its triangles are only stored once
+pub struct Instance<'a> {
+    pub object: Rc<dyn Hittable<'a> + 'a>,
+    pub transform: Transform,
+}
+
+impl<'a> Instance<'a> {
+    pub fn new(object: Rc<dyn Hittable<'a> + 'a>, transform: Transform) -> Instance<'a> {
+        return Instance { object, transform };
+    }
+}
+
+fn hit_transformed<'h>(object: &'h dyn Hittable<'_>, transform: &Transform, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'h>> {
+    let object_ray = transform.ray_to_object(ray);
+    let mut hit = object.hit(&object_ray, t_min, t_max)?;
+
+    // t carries over unchanged, only the point, normals and tangents need to go back to world space.
+    // The inverse transpose keeps the normal facing the same side of the ray so hit_front_face still holds
+    hit.at = transform.point(&hit.at);
+    hit.normal = transform.normal(&hit.normal).unit_vector();
+    hit.shading_normal = transform.normal(&hit.shading_normal).unit_vector();
+    hit.dpdu = transform.vector(&hit.dpdu);
+    hit.dpdv = transform.vector(&hit.dpdv);
+    return Some(hit);
+}
+
+impl<'a> Hittable<'a> for Instance<'a> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        return hit_transformed(self.object.as_ref(), &self.transform, ray, t_min, t_max);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return Some(self.transform.bounds(&self.object.bounding_box()?));
+    }
+}
+
+// Steps per keyframe segment used to find the box swept out by an interpolated rotation
+const MOTION_BOUNDS_SAMPLES: usize = 16;
+
+// Instance whose transform changes over the exposure, found by interpolating between keyframes
+// at the time of each ray. Before the first and after the last keyframe the object holds still
+pub struct MovingInstance<'a> {
+    pub object: Rc<dyn Hittable<'a> + 'a>,
+    keyframes: Vec<Keyframe>,
+}
+
+impl<'a> MovingInstance<'a> {
+    pub fn new(object: Rc<dyn Hittable<'a> + 'a>, mut keyframes: Vec<Keyframe>) -> MovingInstance<'a> {
+        assert!(!keyframes.is_empty(), "[ERR] A moving instance needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        return MovingInstance { object, keyframes };
+    }
+
+    // Straight line motion from start at time 0 to end at time 1
+    pub fn linear(object: Rc<dyn Hittable<'a> + 'a>, start: Vector3f, end: Vector3f) -> MovingInstance<'a> {
+        return MovingInstance::new(object, vec![Keyframe::translation_only(0.0, start), Keyframe::translation_only(1.0, end)]);
+    }
+
+    pub fn keyframe_at(&self, time: f32) -> Keyframe {
+        let next = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0];
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1];
+        }
+
+        let (previous, next) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - previous.time) / (next.time - previous.time);
+        return previous.interpolate(next, t);
+    }
+
+    pub fn transform_at(&self, time: f32) -> Transform {
+        return self.keyframe_at(time).transform();
+    }
+}
+
+impl<'a> Hittable<'a> for MovingInstance<'a> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        return hit_transformed(self.object.as_ref(), &self.transform_at(ray.time()), ray, t_min, t_max);
+    }
+
+    // Union of the object's box at every keyframe and at steps in between, so acceleration
+    // structures see the whole volume the object sweeps through
+    fn bounding_box(&self) -> Option<Aabb> {
+        let object_bounds = self.object.bounding_box()?;
+        let mut bounds = self.keyframes[0].transform().bounds(&object_bounds);
+        for pair in self.keyframes.windows(2) {
+            for step in 1..=MOTION_BOUNDS_SAMPLES {
+                let t = step as f32 / MOTION_BOUNDS_SAMPLES as f32;
+                let transform = pair[0].interpolate(&pair[1], t).transform();
+                bounds = bounds.surrounding(&transform.bounds(&object_bounds));
+            }
+        }
+        return Some(bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vectorlib::sphere::Sphere;
+
+    #[test]
+    fn instances_share_one_object() {
+        let sphere: Rc<dyn Hittable> = Rc::new(Sphere::new(Vector3f::zero(), 1.0, Box::new(Lambertian::new(Vector3f::one()))));
+        let moved = Instance::new(sphere.clone(), Transform::translate(Vector3f::new(0.0, 0.0, -5.0)));
+        let stretched = Instance::new(sphere, Transform::scale(Vector3f::new(1.0, 1.0, 3.0)));
+
+        let ray = Ray::new(Vector3f::zero(), Vector3f::new(0.0, 0.0, -1.0));
+        let hit = moved.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-4 && (hit.at.z + 4.0).abs() < 1e-4 && hit.normal.z > 0.999);
+        let bounds = moved.bounding_box().unwrap();
+        assert!((bounds.min.z + 6.0).abs() < 1e-4 && (bounds.max.z + 4.0).abs() < 1e-4);
+
+        // From outside along z the stretched sphere is reached three units out
+        let along_z = Ray::new(Vector3f::new(0.0, 0.0, 10.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!((stretched.hit(&along_z, 0.001, f32::INFINITY).unwrap().t - 7.0).abs() < 1e-4);
+        assert!(stretched.hit(&Ray::new(Vector3f::new(1.5, 0.0, 10.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn moving_instance_follows_its_keyframes() {
+        let sphere: Rc<dyn Hittable> = Rc::new(Sphere::new(Vector3f::zero(), 1.0, Box::new(Lambertian::new(Vector3f::one()))));
+        let moving = MovingInstance::linear(sphere, Vector3f::new(0.0, 0.0, -5.0), Vector3f::new(4.0, 0.0, -5.0));
+        assert!((moving.keyframe_at(0.25).translation.x - 1.0).abs() < 1e-5);
+        assert!((moving.keyframe_at(-1.0).translation.x).abs() < 1e-5 && (moving.keyframe_at(2.0).translation.x - 4.0).abs() < 1e-5);
+
+        let towards = |x: f32, time: f32| Ray::new_at_time(Vector3f::new(x, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0), time);
+        assert!(moving.hit(&towards(4.0, 1.0), 0.001, f32::INFINITY).is_some());
+        assert!(moving.hit(&towards(4.0, 0.0), 0.001, f32::INFINITY).is_none());
+        let bounds = moving.bounding_box().unwrap();
+        assert!(bounds.min.x <= -1.0 && bounds.max.x >= 5.0);
+    }
+}
@@ -0,0 +1,170 @@
+use std::ops;
+
+use crate::vectorlib::{point3::Point3, vector3::*};
+
+// Row major 4x4 matrix, m[row][column], vectors are treated as columns (M * v)
+#[derive(Copy, Clone)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Matrix4 {
+        return Matrix4 { m };
+    }
+
+    pub fn identity() -> Matrix4 {
+        return Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+    }
+
+    // From 16 values in column major order, the layout glTF and OpenGL use
+    pub fn from_column_major(values: &[f32]) -> Matrix4 {
+        let mut matrix = Matrix4::identity();
+        for column in 0..4 {
+            for row in 0..4 {
+                matrix.m[row][column] = values[column * 4 + row];
+            }
+        }
+        return matrix;
+    }
+
+    pub fn translation(offset: Vector3f) -> Matrix4 {
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][3] = offset.x;
+        matrix.m[1][3] = offset.y;
+        matrix.m[2][3] = offset.z;
+        return matrix;
+    }
+
+    pub fn scale(factors: Vector3f) -> Matrix4 {
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][0] = factors.x;
+        matrix.m[1][1] = factors.y;
+        matrix.m[2][2] = factors.z;
+        return matrix;
+    }
+
+    // Rotation by angle radians counter clockwise around axis (Rodrigues' formula)
+    pub fn rotation(axis: Vector3f, angle: f32) -> Matrix4 {
+        let a = axis.unit_vector();
+        let (sin, cos) = angle.sin_cos();
+        let one_minus_cos = 1.0 - cos;
+        return Matrix4::new([
+            [cos + a.x * a.x * one_minus_cos, a.x * a.y * one_minus_cos - a.z * sin, a.x * a.z * one_minus_cos + a.y * sin, 0.0],
+            [a.y * a.x * one_minus_cos + a.z * sin, cos + a.y * a.y * one_minus_cos, a.y * a.z * one_minus_cos - a.x * sin, 0.0],
+            [a.z * a.x * one_minus_cos - a.y * sin, a.z * a.y * one_minus_cos + a.x * sin, cos + a.z * a.z * one_minus_cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+    }
+
+    // Rotation from a unit quaternion (x, y, z, w)
+    pub fn from_quaternion(x: f32, y: f32, z: f32, w: f32) -> Matrix4 {
+        return Matrix4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+    }
+
+    pub fn transform_point(&self, point: &Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * point.x + m[0][1] * point.y + m[0][2] * point.z + m[0][3];
+        let y = m[1][0] * point.x + m[1][1] * point.y + m[1][2] * point.z + m[1][3];
+        let z = m[2][0] * point.x + m[2][1] * point.y + m[2][2] * point.z + m[2][3];
+        let w = m[3][0] * point.x + m[3][1] * point.y + m[3][2] * point.z + m[3][3];
+        if w == 1.0 || w == 0.0 {
+            return Vector3f::new(x, y, z);
+        }
+        return Vector3f::new(x, y, z) / w;
+    }
+
+    // Directions ignore the translation part
+    pub fn transform_vector(&self, vector: &Vector3f) -> Vector3f {
+        let m = &self.m;
+        return Vector3f::new(
+            m[0][0] * vector.x + m[0][1] * vector.y + m[0][2] * vector.z,
+            m[1][0] * vector.x + m[1][1] * vector.y + m[1][2] * vector.z,
+            m[2][0] * vector.x + m[2][1] * vector.y + m[2][2] * vector.z,
+        );
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut transposed = *self;
+        for row in 0..4 {
+            for column in 0..4 {
+                transposed.m[row][column] = self.m[column][row];
+            }
+        }
+        return transposed;
+    }
+
+    // General inverse by cofactor expansion, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let m = &self.m;
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant.abs() < f32::EPSILON * f32::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+
+        return Some(Matrix4::new([
+            [
+                (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inverse_determinant,
+                (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inverse_determinant,
+                (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inverse_determinant,
+                (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inverse_determinant,
+            ],
+            [
+                (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inverse_determinant,
+                (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inverse_determinant,
+                (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inverse_determinant,
+                (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inverse_determinant,
+            ],
+            [
+                (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inverse_determinant,
+                (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inverse_determinant,
+                (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inverse_determinant,
+                (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inverse_determinant,
+            ],
+            [
+                (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inverse_determinant,
+                (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inverse_determinant,
+                (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inverse_determinant,
+                (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inverse_determinant,
+            ],
+        ]));
+    }
+}
+
+impl ops::Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, rhs: Matrix4) -> Self::Output {
+        let mut product = Matrix4::new([[0.0; 4]; 4]);
+        for row in 0..4 {
+            for column in 0..4 {
+                product.m[row][column] = (0..4).map(|k| self.m[row][k] * rhs.m[k][column]).sum();
+            }
+        }
+        return product;
+    }
+}
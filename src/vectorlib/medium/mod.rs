@@ -0,0 +1,167 @@
+use rand::random;
+
+use crate::vectorlib::{aabb::Aabb, hit::*, point3::Point3, ray::*, vector3::*, voxel_grid::VoxelGrid};
+use crate::material::*;
+
+// Fog or smoke of constant density filling a closed boundary. Each ray travels an exponentially distributed
+// distance before scattering, if that lands inside the boundary the medium reports a hit there and its
+// phase material picks the new direction, otherwise the ray passes through untouched
+pub struct ConstantMedium<'a> {
+    pub boundary: Box<dyn Hittable<'a> + 'a>,
+    // Extinction coefficient, the expected number of scattering events per unit of distance
+    pub density: f32,
+    pub phase_material: Box<dyn Material>,
+}
+
+impl<'a> ConstantMedium<'a> {
+    pub fn new(boundary: impl Hittable<'a> + 'a, density: f32, phase_material: Box<dyn Material>) -> ConstantMedium<'a> {
+        return ConstantMedium { boundary: Box::new(boundary), density, phase_material };
+    }
+
+}
+
+// Distance until the next interaction in a medium of the given density, with probability density density * exp(-density * distance)
+fn sample_free_flight(density: f32) -> f32 {
+    return -(1.0 - random::<f32>()).ln() / density;
+}
+
+impl<'a> Hittable<'a> for ConstantMedium<'a> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let crossings = self.boundary.hits_along(ray, t_min, f32::INFINITY);
+        let direction_length = ray.direction().magnitude();
+
+        // A first crossing that is an exit means the ray starts inside the medium
+        let mut enter_t = match crossings.first() {
+            Some(first) if !first.hit_front_face => Some(t_min),
+            _ => None,
+        };
+
+        for crossing in crossings.iter() {
+            if crossing.hit_front_face {
+                enter_t = Some(crossing.t);
+                continue;
+            }
+            let start = match enter_t.take() {
+                Some(start) => start,
+                None => continue,
+            };
+            if start > t_max {
+                return None;
+            }
+
+            // Free flight is memoryless so each separate interval inside the boundary can be sampled afresh
+            let distance_inside = (crossing.t.min(t_max) - start) * direction_length;
+            let flight = sample_free_flight(self.density);
+            if flight < distance_inside {
+                let t = start + flight / direction_length;
+                // Scattering events have no surface, the normal is arbitrary and never used by the phase material
+                let normal = -ray.direction().unit_vector();
+                return Some(HitData::new(t, ray.at(t), normal, ray.direction(), &normal, self.phase_material.as_ref()));
+            }
+        }
+        return None;
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return self.boundary.bounding_box();
+    }
+}
+
+// Spatially varying medium such as a cloud, with density read from a voxel grid and scaled by density_scale.
+// Collisions are found with delta tracking: tentative collisions are sampled against the majorant (the
+// grid's maximum density) and each is accepted as real with probability density / majorant
+pub struct HeterogeneousMedium {
+    pub grid: VoxelGrid,
+    pub density_scale: f32,
+    pub phase_material: Box<dyn Material>,
+    majorant: f32,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(grid: VoxelGrid, density_scale: f32, phase_material: Box<dyn Material>) -> HeterogeneousMedium {
+        let majorant = grid.max_density() * density_scale;
+        return HeterogeneousMedium { grid, density_scale, phase_material, majorant };
+    }
+
+    pub fn density_at(&self, point: &Point3) -> f32 {
+        return self.grid.density_at(point) * self.density_scale;
+    }
+}
+
+impl<'a> Hittable<'a> for HeterogeneousMedium {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let (start, end) = self.grid.bounds.hit_range(ray, t_min, t_max)?;
+
+        let direction_length = ray.direction().magnitude();
+        let mut t = start;
+        loop {
+            t += sample_free_flight(self.majorant) / direction_length;
+            if t >= end {
+                return None;
+            }
+
+            let point = ray.at(t);
+            // Otherwise it was a null collision with the fictitious medium topping the density up to the majorant
+            if random::<f32>() * self.majorant < self.density_at(&point) {
+                let normal = -ray.direction().unit_vector();
+                return Some(HitData::new(t, point, normal, ray.direction(), &normal, self.phase_material.as_ref()));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return Some(self.grid.bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::phase::PhaseMaterial;
+    use crate::vectorlib::sphere::Sphere;
+
+    fn fog(density: f32) -> ConstantMedium<'static> {
+        let boundary = Sphere::new(Vector3f::new(0.0, 0.0, -5.0), 1.0, Box::new(PhaseMaterial::isotropic(Vector3f::one())));
+        return ConstantMedium::new(boundary, density, Box::new(PhaseMaterial::isotropic(Vector3f::one())));
+    }
+
+    fn scattered_fraction(medium: &ConstantMedium, ray: &Ray, check: impl Fn(f32) -> bool) -> f32 {
+        let trials = 20000;
+        let mut scattered = 0;
+        for _ in 0..trials {
+            if let Some(hit) = medium.hit(ray, 0.001, f32::INFINITY) {
+                assert!(check(hit.t));
+                scattered += 1;
+            }
+        }
+        return scattered as f32 / trials as f32;
+    }
+
+    #[test]
+    fn free_flight_has_the_mean_of_the_density() {
+        let samples = 50000;
+        let mean = (0..samples).map(|_| sample_free_flight(2.0)).sum::<f32>() / samples as f32;
+        assert!((mean - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn constant_medium_scatters_with_beer_lambert_probability() {
+        let medium = fog(0.5);
+
+        // Crossing the full diameter of 2 inside the boundary
+        let through = Ray::new(Vector3f::zero(), Vector3f::new(0.0, 0.0, -1.0));
+        let fraction = scattered_fraction(&medium, &through, |t| (4.0..=6.0).contains(&t));
+        assert!((fraction - (1.0 - (-1.0f32).exp())).abs() < 0.02);
+
+        // Starting at the center only the radius is left to cross
+        let inside = Ray::new(Vector3f::new(0.0, 0.0, -5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let fraction = scattered_fraction(&medium, &inside, |t| t <= 1.0);
+        assert!((fraction - (1.0 - (-0.5f32).exp())).abs() < 0.02);
+
+        let past = Ray::new(Vector3f::new(2.0, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert_eq!(scattered_fraction(&medium, &past, |_| true), 0.0);
+    }
+}
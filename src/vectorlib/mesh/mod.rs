@@ -0,0 +1,177 @@
+use crate::vectorlib::{aabb::{Aabb, FLAT_BOX_PADDING}, bvh::Bvh, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+const PARALLEL_TOLERANCE : f32 = 1e-8;
+
+// Moller-Trumbore, returns (t, b1, b2) where b1 and b2 are the barycentric weights of b and c
+pub fn intersect_triangle(a: Point3, b: Point3, c: Point3, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+
+    let p = ray.direction().cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < PARALLEL_TOLERANCE {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = *ray.origin() - a;
+    let b1 = s.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(&edge_1);
+    let b2 = ray.direction().dot(&q) * inverse_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inverse_determinant;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    return Some((t, b1, b2));
+}
+
+// Indexed triangle mesh with optional per-vertex normals, colours and texture coordinates.
+// Triangles are kept in their own BVH so the whole mesh is a single hittable
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vector3f>,
+    colors: Vec<Vector3f>,
+    uvs: Vec<[f32; 2]>,
+    triangles: Vec<[u32; 3]>,
+    material: Box<dyn Material>,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    // normals and colors are either empty or have one entry per position
+    pub fn new(positions: Vec<Point3>, normals: Vec<Vector3f>, colors: Vec<Vector3f>, triangles: Vec<[u32; 3]>, material: Box<dyn Material>) -> TriangleMesh {
+        assert!(normals.is_empty() || normals.len() == positions.len(), "[ERR] Mesh needs one normal per vertex");
+        assert!(colors.is_empty() || colors.len() == positions.len(), "[ERR] Mesh needs one colour per vertex");
+
+        let boxes: Vec<Aabb> = triangles.iter().map(|triangle| {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            return Aabb::from_points(a, b).grow(c).padded(FLAT_BOX_PADDING);
+        }).collect();
+        let bvh = Bvh::new(&boxes);
+
+        return TriangleMesh { positions, normals, colors, uvs: Vec::new(), triangles, material, bvh };
+    }
+
+    // One (u, v) per position. Without them each triangle is mapped as (0,0), (1,0), (1,1)
+    pub fn with_uvs(mut self, uvs: Vec<[f32; 2]>) -> TriangleMesh {
+        assert!(uvs.is_empty() || uvs.len() == self.positions.len(), "[ERR] Mesh needs one uv per vertex");
+        self.uvs = uvs;
+        return self;
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        return self.triangles.len();
+    }
+
+    fn triangle_uvs(&self, indices: [u32; 3]) -> [[f32; 2]; 3] {
+        if self.uvs.is_empty() {
+            return [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        }
+        return indices.map(|i| self.uvs[i as usize]);
+    }
+
+    fn interpolate(values: &[Vector3f], [i0, i1, i2]: [u32; 3], b1: f32, b2: f32) -> Vector3f {
+        let b0 = 1.0 - b1 - b2;
+        return b0 * values[i0 as usize] + b1 * values[i1 as usize] + b2 * values[i2 as usize];
+    }
+}
+
+impl<'a> Hittable<'a> for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let mut closest: Option<(usize, f32, f32)> = None;
+        let t = self.bvh.traverse(ray, t_min, t_max, |triangle, t_min, closest_t| {
+            let [a, b, c] = self.triangles[triangle].map(|i| self.positions[i as usize]);
+            let (t, b1, b2) = intersect_triangle(a, b, c, ray, t_min, closest_t)?;
+            closest = Some((triangle, b1, b2));
+            return Some(t);
+        })?;
+        let (triangle, b1, b2) = closest?;
+
+        let indices = self.triangles[triangle];
+        let [a, b, c] = indices.map(|i| self.positions[i as usize]);
+        let geometric_normal = (b - a).cross(&(c - a)).unit_vector();
+
+        let uvs = self.triangle_uvs(indices);
+        let b0 = 1.0 - b1 - b2;
+        let u = b0 * uvs[0][0] + b1 * uvs[1][0] + b2 * uvs[2][0];
+        let v = b0 * uvs[0][1] + b1 * uvs[1][1] + b2 * uvs[2][1];
+
+        // Solve the edges for the change in position per unit of u and v, there is none when the uvs are degenerate
+        let (du_02, dv_02) = (uvs[0][0] - uvs[2][0], uvs[0][1] - uvs[2][1]);
+        let (du_12, dv_12) = (uvs[1][0] - uvs[2][0], uvs[1][1] - uvs[2][1]);
+        let (dp_02, dp_12) = (a - c, b - c);
+        let determinant = du_02 * dv_12 - dv_02 * du_12;
+        let (dpdu, dpdv) = if determinant.abs() < PARALLEL_TOLERANCE {
+            (Vector3f::zero(), Vector3f::zero())
+        } else {
+            ((dv_12 * dp_02 - dv_02 * dp_12) / determinant, (du_02 * dp_12 - du_12 * dp_02) / determinant)
+        };
+
+        // Smooth normals only shade, the flat one still decides the front face
+        let mut hit_data = HitData::new(t, ray.at(t), geometric_normal, ray.direction(), &geometric_normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(dpdu, dpdv);
+        if !self.normals.is_empty() {
+            hit_data = hit_data.with_shading_normal(TriangleMesh::interpolate(&self.normals, indices, b1, b2));
+        }
+        if !self.colors.is_empty() {
+            hit_data.vertex_color = Some(TriangleMesh::interpolate(&self.colors, indices, b1, b2));
+        }
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return self.bvh.bounding_box();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3f, b: Vector3f) -> bool {
+        return (a - b).magnitude() < 1e-4;
+    }
+
+    fn triangle(normals: Vec<Vector3f>) -> TriangleMesh {
+        let positions = vec![Vector3f::new(0.0, 0.0, -2.0), Vector3f::new(2.0, 0.0, -2.0), Vector3f::new(0.0, 2.0, -2.0)];
+        return TriangleMesh::new(positions, normals, Vec::new(), vec![[0, 1, 2]], Box::new(Lambertian::new(Vector3f::one())));
+    }
+
+    #[test]
+    fn uvs_give_interpolated_coordinates_and_tangents() {
+        let mesh = triangle(Vec::new()).with_uvs(vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        let ray = Ray::new(Vector3f::new(0.5, 1.0, 0.0), Vector3f::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        assert!(close(hit.dpdu, Vector3f::new(2.0, 0.0, 0.0)));
+        assert!(close(hit.dpdv, Vector3f::new(0.0, 2.0, 0.0)));
+
+        let miss = Ray::new(Vector3f::new(1.5, 1.5, 0.0), Vector3f::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&miss, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn vertex_normals_only_shade() {
+        let tilted = Vector3f::new(1.0, 0.0, 1.0);
+        let mesh = triangle(vec![tilted, tilted, tilted]);
+        let ray = Ray::new(Vector3f::new(0.5, 0.5, 0.0), Vector3f::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!(close(hit.normal, Vector3f::new(0.0, 0.0, 1.0)));
+        assert!(close(hit.shading_normal, tilted.unit_vector()));
+
+        // Vertex normals pointing away are turned to the side of the geometric normal
+        let flipped = triangle(vec![-tilted, -tilted, -tilted]);
+        assert!(close(flipped.hit(&ray, 0.001, f32::INFINITY).unwrap().shading_normal, tilted.unit_vector()));
+    }
+}
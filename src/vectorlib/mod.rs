@@ -3,4 +3,25 @@ pub mod point3;
 pub mod ray;
 pub mod vector3;
 pub mod hit;
-pub mod sphere;
\ No newline at end of file
+pub mod sphere;
+pub mod aabb;
+pub mod bvh;
+pub mod mesh;
+pub mod matrix4;
+pub mod plane;
+pub mod disk;
+pub mod quad;
+pub mod frame;
+pub mod cuboid;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod transform;
+pub mod instance;
+pub mod quaternion;
+pub mod csg;
+pub mod sdf;
+pub mod medium;
+pub mod voxel_grid;
+pub mod heightfield;
+pub mod curve;
\ No newline at end of file
@@ -0,0 +1,79 @@
+use crate::vectorlib::{aabb::Aabb, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+const PARALLEL_TOLERANCE : f32 = 1e-8;
+
+// Infinite plane through point, the normal side is the front face
+pub struct Plane {
+    pub point: Point3,
+    pub normal: Vector3f,
+    pub material : Box<dyn Material>,
+    // Directions u and v are measured along, in world units
+    tangent: Vector3f,
+    bitangent: Vector3f,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vector3f, material : Box<dyn Material>) -> Plane {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        return Plane { point, normal, material, tangent, bitangent };
+    }
+}
+
+// t where the ray crosses the plane through point with the given normal, if it is in range
+pub fn hit_plane(ray: &Ray, point: &Point3, normal: &Vector3f, t_min: f32, t_max: f32) -> Option<f32> {
+    let denominator = normal.dot(ray.direction());
+    if denominator.abs() < PARALLEL_TOLERANCE {
+        return None;
+    }
+
+    let t = (*point - *ray.origin()).dot(normal) / denominator;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    return Some(t);
+}
+
+impl<'a> Hittable<'a> for Plane {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let t = hit_plane(ray, &self.point, &self.normal, t_min, t_max)?;
+        let hit_point = ray.at(t);
+
+        // Planar mapping, u and v are distances from the reference point so textures tile every world unit
+        let offset = hit_point - self.point;
+        let u = offset.dot(&self.tangent);
+        let v = offset.dot(&self.bitangent);
+
+        let hit_data = HitData::new(t, hit_point, self.normal, ray.direction(), &self.normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.tangent, self.bitangent);
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn hits_from_either_side_and_misses_parallel_rays() {
+        let plane = Plane::new(Vector3f::new(0.0, -1.0, 0.0), Vector3f::unit_y(), Box::new(Lambertian::new(Vector3f::one())));
+        let from_above = plane.hit(&Ray::new(Vector3f::new(2.0, 1.0, 3.0), Vector3f::new(0.0, -1.0, 0.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((from_above.t - 2.0).abs() < 1e-5 && from_above.hit_front_face);
+        // u and v measure distance from the reference point along the plane
+        assert!((from_above.u * from_above.u + from_above.v * from_above.v - 13.0).abs() < 1e-4);
+
+        let from_below = plane.hit(&Ray::new(Vector3f::new(0.0, -3.0, 0.0), Vector3f::unit_y()), 0.001, f32::INFINITY).unwrap();
+        assert!(!from_below.hit_front_face && from_below.normal.y < 0.0);
+
+        assert!(plane.hit(&Ray::new(Vector3f::zero(), Vector3f::unit_x()), 0.001, f32::INFINITY).is_none());
+        assert!(plane.hit(&Ray::new(Vector3f::zero(), Vector3f::unit_y()), 0.001, f32::INFINITY).is_none());
+        assert!(plane.hit(&Ray::new(Vector3f::zero(), -Vector3f::unit_y()), 0.001, 0.5).is_none());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::vectorlib::{aabb::{Aabb, FLAT_BOX_PADDING}, hit::*, plane::hit_plane, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Parallelogram with one corner at corner and sides along edge_u and edge_v.
+// The front face is on the side of edge_u x edge_v
+pub struct Quad {
+    pub corner: Point3,
+    pub edge_u: Vector3f,
+    pub edge_v: Vector3f,
+    pub material : Box<dyn Material>,
+    normal: Vector3f,
+    // Scaled normal used to find the (u, v) of a point on the plane, n / (n . n) with n unnormalised
+    w: Vector3f,
+}
+
+impl Quad {
+    pub fn new(corner: Point3, edge_u: Vector3f, edge_v: Vector3f, material : Box<dyn Material>) -> Quad {
+        let n = edge_u.cross(&edge_v);
+        let normal = n.unit_vector();
+        let w = n / n.square_magnitude();
+        return Quad { corner, edge_u, edge_v, material, normal, w };
+    }
+
+    // Axis aligned rectangles facing +z, +y and +x respectively, k is the position on the remaining axis
+    pub fn xy_rect(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material : Box<dyn Material>) -> Quad {
+        return Quad::new(Vector3f::new(x0, y0, k), Vector3f::new(x1 - x0, 0.0, 0.0), Vector3f::new(0.0, y1 - y0, 0.0), material);
+    }
+
+    pub fn xz_rect(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material : Box<dyn Material>) -> Quad {
+        return Quad::new(Vector3f::new(x0, k, z0), Vector3f::new(0.0, 0.0, z1 - z0), Vector3f::new(x1 - x0, 0.0, 0.0), material);
+    }
+
+    pub fn yz_rect(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, material : Box<dyn Material>) -> Quad {
+        return Quad::new(Vector3f::new(k, y0, z0), Vector3f::new(0.0, y1 - y0, 0.0), Vector3f::new(0.0, 0.0, z1 - z0), material);
+    }
+}
+
+impl<'a> Hittable<'a> for Quad {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let t = hit_plane(ray, &self.corner, &self.normal, t_min, t_max)?;
+        let hit_point = ray.at(t);
+
+        // (u, v) are the coordinates of the hit along the edges, both in [0,1] inside the quad
+        let offset = hit_point - self.corner;
+        let u = self.w.dot(&offset.cross(&self.edge_v));
+        let v = self.w.dot(&self.edge_u.cross(&offset));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        let hit_data = HitData::new(t, hit_point, self.normal, ray.direction(), &self.normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.edge_u, self.edge_v);
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let opposite = self.corner + self.edge_u + self.edge_v;
+        let bounds = Aabb::from_points(self.corner, opposite)
+            .grow(self.corner + self.edge_u)
+            .grow(self.corner + self.edge_v);
+        return Some(bounds.padded(FLAT_BOX_PADDING));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_hits_onto_the_edges() {
+        let quad = Quad::new(Vector3f::new(-1.0, -1.0, -3.0), Vector3f::new(2.0, 0.0, 0.0), Vector3f::new(1.0, 2.0, 0.0), Box::new(Lambertian::new(Vector3f::one())));
+        // Corner plus half of each edge
+        let hit = quad.hit(&Ray::new(Vector3f::new(0.5, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-5 && hit.hit_front_face);
+        assert!((hit.u - 0.5).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        // Inside the bounding rectangle but outside the slanted parallelogram
+        assert!(quad.hit(&Ray::new(Vector3f::new(-0.9, 0.9, 0.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn axis_aligned_rectangles_face_their_axis() {
+        let material = || -> Box<dyn Material> { Box::new(Lambertian::new(Vector3f::one())) };
+        let rects = [
+            (Quad::xy_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vector3f::unit_z()),
+            (Quad::xz_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vector3f::unit_y()),
+            (Quad::yz_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vector3f::unit_x()),
+        ];
+        for (rect, axis) in rects.iter() {
+            let origin = 0.5 * (Vector3f::one() - *axis) + *axis;
+            let hit = rect.hit(&Ray::new(origin, -*axis), 0.001, f32::INFINITY).unwrap();
+            assert!(hit.hit_front_face && (hit.t - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::vectorlib::{matrix4::Matrix4, vector3::*};
+
+// Unit quaternion for rotations that need to be interpolated, such as keyframed motion
+#[derive(Copy, Clone)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Quaternion {
+        return Quaternion { x, y, z, w };
+    }
+
+    pub fn identity() -> Quaternion {
+        return Quaternion::new(0.0, 0.0, 0.0, 1.0);
+    }
+
+    // angle is in radians, counter clockwise looking down the axis
+    pub fn from_axis_angle(axis: Vector3f, angle: f32) -> Quaternion {
+        let axis = axis.unit_vector();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        return Quaternion::new(axis.x * sin, axis.y * sin, axis.z * sin, cos);
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        return self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w;
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        return Quaternion::new(self.x / length, self.y / length, self.z / length, self.w / length);
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        return Quaternion::new(-self.x, -self.y, -self.z, self.w);
+    }
+
+    // Spherical interpolation along the shorter arc, t in [0,1]
+    pub fn slerp(&self, target: &Quaternion, t: f32) -> Quaternion {
+        let mut target = *target;
+        let mut cos_theta = self.dot(&target);
+        if cos_theta < 0.0 {
+            target = Quaternion::new(-target.x, -target.y, -target.z, -target.w);
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly parallel, fall back to a normalised lerp to avoid dividing by sin(theta) ~ 0
+        let (start_weight, target_weight) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.min(1.0).acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        return Quaternion::new(
+            start_weight * self.x + target_weight * target.x,
+            start_weight * self.y + target_weight * target.y,
+            start_weight * self.z + target_weight * target.z,
+            start_weight * self.w + target_weight * target.w,
+        )
+        .normalized();
+    }
+
+    pub fn rotation_matrix(&self) -> Matrix4 {
+        return Matrix4::from_quaternion(self.x, self.y, self.z, self.w);
+    }
+}
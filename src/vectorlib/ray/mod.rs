@@ -1,18 +1,26 @@
-use crate::vectorlib::{point3::Point3, vector3::Vector3f, sphere::*};
+use crate::constants::SKY_LIGHT;
+use crate::vectorlib::{point3::Point3, vector3::Vector3f};
 
 use super::{hit::HittableList, vector3::{Normalize, Lerp}};
 
 pub struct Ray {
     origin: Point3,
     direction: Vector3f,
+    // When during the exposure the ray was fired, moving objects are hit where they are at this time
+    time: f32,
 }
 
 // use super::{Point3, Ray, Vector3f};
 impl Ray {
     pub fn new(origin: Point3, direction: Vector3f) -> Ray {
+        return Ray::new_at_time(origin, direction, 0.0);
+    }
+
+    pub fn new_at_time(origin: Point3, direction: Vector3f, time: f32) -> Ray {
         return Ray{ 
             origin : (origin), 
-            direction : (direction)
+            direction : (direction),
+            time,
         };
     }
 
@@ -27,6 +35,10 @@ impl Ray {
     pub fn direction(&self) -> &Point3 {
         return &self.direction;
     }
+
+    pub fn time(&self) -> f32 {
+        return self.time;
+    }
 }
 
 pub trait Fireable {
@@ -41,20 +53,25 @@ impl Fireable for Ray{
             return Vector3f::zero();
         }
 
-        let maybe_hit: Option<super::hit::HitData> = meshes.hit(self, SHADOW_ACNE_TOLERANCE, std::f32::INFINITY);
-        if maybe_hit.is_some() {
-            let hit = maybe_hit.unwrap();
+        let maybe_hit: Option<super::hit::HitData> = meshes.hit(self, SHADOW_ACNE_TOLERANCE, f32::INFINITY);
+        if let Some(hit) = maybe_hit {
+            let emitted = hit.material.emitted(self, &hit);
 
-            let attenuation : &Vector3f;
-            let scattered_ray : Ray;
-            {
-                let attenuation_and_scattered_ray = hit.material.scatter(self, &hit);
-                attenuation = attenuation_and_scattered_ray.0;
-                scattered_ray = attenuation_and_scattered_ray.1;
+            // Absorbed, no point following the scattered ray
+            let (attenuation, scattered_ray) = match hit.material.scatter_in_world(self, &hit, meshes) {
+                Some(scattered) => scattered,
+                None => return emitted,
+            };
+            if attenuation.near_zero() {
+                return emitted;
             }
 
             // With every bounce we lose half the energy contribution to color 
-            return attenuation.multiply_element_wise(&scattered_ray.find_color_from_ray_in_world(meshes, bounces_left - 1));
+            return emitted + attenuation.multiply_element_wise(&scattered_ray.find_color_from_ray_in_world(meshes, bounces_left - 1));
+        }
+
+        if !SKY_LIGHT {
+            return Vector3f::zero();
         }
     
         // Hit nothing so get naturally emissive background color
@@ -66,4 +83,31 @@ impl Fireable for Ray{
     
         return white.lerp(&sky_blue, t);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::vectorlib::sphere::Sphere;
+
+    #[test]
+    fn emitted_light_is_gathered_along_the_path() {
+        // A grey ball inside a glowing shell, every bounce off the ball heads straight for the shell
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Vector3f::zero(), 1.0, Box::new(Lambertian::new(Vector3f::uniform(0.5)))));
+        world.add(Sphere::new(Vector3f::zero(), 10.0, Box::new(DiffuseLight::new(Vector3f::one(), 2.0))));
+
+        let at_ball = Ray::new(Vector3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0));
+        let at_shell = Ray::new(Vector3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, 1.0));
+        for _ in 0..20 {
+            assert!((at_ball.find_color_from_ray_in_world(&world, 8).x - 1.0).abs() < 1e-5);
+        }
+        assert!((at_shell.find_color_from_ray_in_world(&world, 8).y - 2.0).abs() < 1e-5);
+        assert!(at_shell.find_color_from_ray_in_world(&world, 0).near_zero());
+
+        // Seen from inside the shell is its back face
+        let mut dark = HittableList::new();
+        dark.add(Sphere::new(Vector3f::zero(), 10.0, Box::new(DiffuseLight::new(Vector3f::one(), 2.0).with_one_sided(true))));
+        assert!(at_shell.find_color_from_ray_in_world(&dark, 8).near_zero());
+    }
+}
@@ -0,0 +1,239 @@
+use std::f32::consts::PI;
+
+use crate::vectorlib::{aabb::Aabb, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+const DEFAULT_MAX_STEPS : u32 = 256;
+const DEFAULT_EPSILON : f32 = 1e-4;
+
+// Shape defined by a signed distance function (negative inside, positive outside) and rendered by sphere tracing.
+// There is no closed form intersection so the ray is marched through the bounding box one safe step at a time
+pub struct Sdf {
+    distance: Box<dyn Fn(Point3) -> f32>,
+    bounds: Aabb,
+    pub material : Box<dyn Material>,
+    max_steps: u32,
+    epsilon: f32,
+    // Fraction of the distance estimate to step by, below 1 for functions that overestimate such as fractals
+    step_scale: f32,
+}
+
+impl Sdf {
+    // bounds must contain the whole surface, marching only happens inside it
+    pub fn new(distance: impl Fn(Point3) -> f32 + 'static, bounds: Aabb, material : Box<dyn Material>) -> Sdf {
+        return Sdf {
+            distance: Box::new(distance),
+            bounds,
+            material,
+            max_steps: DEFAULT_MAX_STEPS,
+            epsilon: DEFAULT_EPSILON,
+            step_scale: 1.0,
+        };
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Sdf {
+        self.max_steps = max_steps;
+        return self;
+    }
+
+    // How close to the surface counts as a hit, also the offset used to estimate the gradient
+    pub fn with_epsilon(mut self, epsilon: f32) -> Sdf {
+        self.epsilon = epsilon;
+        return self;
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f32) -> Sdf {
+        self.step_scale = step_scale;
+        return self;
+    }
+
+    pub fn distance(&self, point: Point3) -> f32 {
+        return (self.distance)(point);
+    }
+
+    // Gradient from four samples on a tetrahedron, cheaper than central differences on each axis
+    pub fn normal(&self, point: Point3) -> Vector3f {
+        let h = self.epsilon;
+        let offsets = [
+            Vector3f::new(1.0, -1.0, -1.0),
+            Vector3f::new(-1.0, -1.0, 1.0),
+            Vector3f::new(-1.0, 1.0, -1.0),
+            Vector3f::new(1.0, 1.0, 1.0),
+        ];
+        let mut gradient = Vector3f::zero();
+        for offset in offsets {
+            gradient = gradient + offset * self.distance(point + offset * h);
+        }
+        return gradient.unit_vector();
+    }
+}
+
+impl<'a> Hittable<'a> for Sdf {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let (start, end) = self.bounds.hit_range(ray, t_min, t_max)?;
+
+        // March in unit steps along the direction and convert back to the ray's t at the end
+        let direction_length = ray.direction().magnitude();
+        let direction = *ray.direction() / direction_length;
+        let mut distance_travelled = start * direction_length;
+        let end = end * direction_length;
+
+        // Rays that start inside march on |f| and stop when they reach the surface from the other side.
+        // Scattered rays start on the surface they left, so they first have to step clear of it
+        let mut leaving_surface = true;
+        for _ in 0..self.max_steps {
+            let point = *ray.origin() + distance_travelled * direction;
+            let distance = self.distance(point).abs();
+            if distance < self.epsilon {
+                if leaving_surface {
+                    distance_travelled += self.epsilon;
+                    continue;
+                }
+                let t = distance_travelled / direction_length;
+                let normal = self.normal(point);
+                // Spherical mapping around the middle of the bounds like Sphere's, the tangents are any
+                // pair perpendicular to the gradient as the distance function has no parameterization
+                let p = (point - self.bounds.centroid()).unit_vector();
+                let u = ((-p.z).atan2(p.x) + PI) / (2.0 * PI);
+                let v = (-p.y).clamp(-1.0, 1.0).acos() / PI;
+                let (dpdu, dpdv) = normal.orthonormal_basis();
+                let hit_data = HitData::new(t, point, normal, ray.direction(), &normal, self.material.as_ref())
+                    .with_uv(u, v)
+                    .with_tangents(dpdu, dpdv);
+                return Some(hit_data);
+            }
+
+            leaving_surface = false;
+            distance_travelled += distance * self.step_scale;
+            if distance_travelled > end {
+                return None;
+            }
+        }
+        return None;
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        return Some(self.bounds);
+    }
+}
+
+// A few distance functions and operators to build shapes from, after Inigo Quilez's articles
+
+pub fn sphere_distance(point: Point3, center: Point3, radius: f32) -> f32 {
+    return (point - center).magnitude() - radius;
+}
+
+// Box of the given half extents around center with its edges rounded off by radius
+pub fn rounded_box_distance(point: Point3, center: Point3, half_extents: Vector3f, radius: f32) -> f32 {
+    let p = point - center;
+    let q = Vector3f::new(p.x.abs(), p.y.abs(), p.z.abs()) - half_extents + Vector3f::uniform(radius);
+    let outside = Vector3f::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+    let inside = q.x.max(q.y.max(q.z)).min(0.0);
+    return outside + inside - radius;
+}
+
+// Torus lying in the xz plane around center
+pub fn torus_distance(point: Point3, center: Point3, major_radius: f32, minor_radius: f32) -> f32 {
+    let p = point - center;
+    let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+    return (ring * ring + p.y * p.y).sqrt() - minor_radius;
+}
+
+// Polynomial smooth minimum, k is roughly how far the blend between the two shapes reaches.
+// A k of 0 or less is a hard union
+pub fn smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    return b + (a - b) * h - k * h * (1.0 - h);
+}
+
+// a with b carved out of it, blended over k
+pub fn smooth_subtraction(a: f32, b: f32, k: f32) -> f32 {
+    return -smooth_union(-a, b, k);
+}
+
+pub fn smooth_intersection(a: f32, b: f32, k: f32) -> f32 {
+    return -smooth_union(-a, -b, k);
+}
+
+// Distance estimate for the power-n Mandelbulb centered on the origin, fits inside a radius of about 1.2.
+// The estimate is not exact so pair it with a step scale below 1
+pub fn mandelbulb_distance(point: Point3, power: f32, iterations: u32) -> f32 {
+    let mut z = point;
+    let mut derivative = 1.0;
+    let mut radius = 0.0;
+
+    for _ in 0..iterations {
+        radius = z.magnitude();
+        if radius > 2.0 || radius == 0.0 {
+            break;
+        }
+
+        // z = z^power + point in spherical coordinates
+        let theta = (z.z / radius).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        derivative = radius.powf(power - 1.0) * power * derivative + 1.0;
+
+        let scaled_radius = radius.powf(power);
+        z = scaled_radius * Vector3f::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) + point;
+    }
+
+    if radius == 0.0 {
+        return 0.0;
+    }
+    return 0.5 * radius.ln() * radius / derivative;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_sphere() -> Sdf {
+        let bounds = Aabb::new(Vector3f::uniform(-1.5), Vector3f::uniform(1.5));
+        return Sdf::new(|point| sphere_distance(point, Vector3f::zero(), 1.0), bounds, Box::new(Lambertian::new(Vector3f::one())));
+    }
+
+    #[test]
+    fn marches_onto_the_surface() {
+        let sdf = unit_sphere();
+        let hit = sdf.hit(&Ray::new(Vector3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -2.0)), 0.001, f32::INFINITY).unwrap();
+        // t is in the ray's own units, not along the normalised direction
+        assert!((hit.t - 2.0).abs() < 1e-3 && hit.hit_front_face && hit.normal.z > 0.999);
+        assert!(hit.dpdu.dot(&hit.normal).abs() < 1e-4 && hit.dpdv.dot(&hit.normal).abs() < 1e-4);
+        assert!((hit.dpdu.magnitude() - 1.0).abs() < 1e-4 && (hit.v - 0.5).abs() < 1e-3);
+
+        assert!(sdf.hit(&Ray::new(Vector3f::new(1.2, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, f32::INFINITY).is_none());
+        assert!(sdf.hit(&Ray::new(Vector3f::new(0.0, 0.0, 5.0), Vector3f::new(0.0, 0.0, -1.0)), 0.001, 3.0).is_none());
+    }
+
+    #[test]
+    fn leaves_the_surface_it_starts_on() {
+        let sdf = unit_sphere();
+        let hit = sdf.hit(&Ray::new(Vector3f::new(0.0, 0.0, 1.0), Vector3f::new(0.0, 0.0, -1.0)), 0.0, f32::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-3 && !hit.hit_front_face);
+    }
+
+    #[test]
+    fn distance_functions_match_their_shapes() {
+        assert!((sphere_distance(Vector3f::new(3.0, 0.0, 0.0), Vector3f::zero(), 1.0) - 2.0).abs() < 1e-6);
+        assert!((rounded_box_distance(Vector3f::new(2.0, 0.0, 0.0), Vector3f::zero(), Vector3f::one(), 0.2) - 1.0).abs() < 1e-6);
+        assert!((torus_distance(Vector3f::new(2.0, 0.0, 0.0), Vector3f::zero(), 2.0, 0.5) + 0.5).abs() < 1e-6);
+        // Points well inside the set stay at distance 0
+        assert!(mandelbulb_distance(Vector3f::zero(), 8.0, 10) == 0.0);
+        assert!(mandelbulb_distance(Vector3f::uniform(2.0), 8.0, 10) > 0.0);
+    }
+
+    #[test]
+    fn smooth_operators_blend_and_fall_back_to_hard_ones() {
+        // Equal distances blend to below either, k of 0 gives exactly the hard union without dividing by zero
+        assert!((smooth_union(1.0, 1.0, 0.5) - 0.875).abs() < 1e-6);
+        assert_eq!(smooth_union(1.0, 1.0, 0.0), 1.0);
+        assert_eq!(smooth_union(0.3, -0.2, 0.0), -0.2);
+        assert_eq!(smooth_subtraction(0.3, -0.2, 0.0), 0.3);
+        assert_eq!(smooth_intersection(0.3, -0.2, 0.0), 0.3);
+        // Far apart the blend has no effect
+        assert!((smooth_union(0.0, 5.0, 0.5)).abs() < 1e-6);
+    }
+}
@@ -1,25 +1,36 @@
-use std::rc::Rc;
+use std::f32::consts::PI;
 
-use rand::{random, rngs::ThreadRng};
-
-use crate::vectorlib::{hit::*, point3::*, ray::*, vector3::*};
+use crate::vectorlib::{aabb::Aabb, hit::*, point3::*, ray::*, vector3::*};
 use crate::material::*;
 
 pub struct Sphere {
     pub center: Point3,
     pub radius: f32,
     pub material : Box<dyn Material>,
+    // How far the center moves between time 0 and time 1, zero for still spheres
+    pub motion: Vector3f,
 }
 
 impl Sphere {
     pub fn new(center: Vector3f, radius: f32, material : Box<dyn Material>) -> Sphere {
-        return Sphere { center, radius, material };
+        return Sphere { center, radius, material, motion: Vector3f::zero() };
+    }
+
+    // Sphere moving in a straight line from center_start at time 0 to center_end at time 1, holding
+    // still before and after so a shutter outside [0,1] can't carry it out of its bounding box
+    pub fn new_moving(center_start: Vector3f, center_end: Vector3f, radius: f32, material : Box<dyn Material>) -> Sphere {
+        return Sphere { center: center_start, radius, material, motion: center_end - center_start };
+    }
+
+    pub fn center_at(&self, time: f32) -> Point3 {
+        return self.center + time.clamp(0.0, 1.0) * self.motion;
     }
 }
 
 impl<'a> Hittable<'a> for Sphere {
-    fn hit(self: &Self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData> {
-        let oc = ray.origin().clone() - self.center;
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let center = self.center_at(ray.time());
+        let oc = ray.origin().clone() - center;
 
         let a = ray.direction().square_magnitude();
         let b_half = oc.dot(ray.direction());
@@ -48,14 +59,33 @@ impl<'a> Hittable<'a> for Sphere {
         let t = root;
         let hit_point = ray.at(t);
         // Vector from center of circle to point of intersection turnt into a unit vector 
-        let normal = (hit_point - self.center) / self.radius;
-        
-        return Some(HitData::new(t, ray.at(t), normal, ray.direction(), &normal, self.material.as_ref()));
+        let normal = (hit_point - center) / self.radius;
+
+        // Spherical mapping, u goes around the y axis starting at -x and v goes from the bottom (0) to the top (1)
+        let p = normal.unit_vector();
+        let u = ((-p.z).atan2(p.x) + PI) / (2.0 * PI);
+        let v = (-p.y).clamp(-1.0, 1.0).acos() / PI;
+        let ring_radius = (p.x * p.x + p.z * p.z).sqrt();
+        let dpdu = 2.0 * PI * self.radius * Vector3f::new(p.z, 0.0, -p.x);
+        let dpdv = PI * self.radius * Vector3f::new(-p.x * p.y, ring_radius * ring_radius, -p.y * p.z) / ring_radius.max(f32::EPSILON);
+
+        let hit_data = HitData::new(t, ray.at(t), normal, ray.direction(), &normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(dpdu, dpdv);
+        return Some(hit_data);
+    }
+
+    // Covers the whole path, and center_at holds still outside [0,1], so the sphere is inside its box at any time
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = Vector3f::uniform(self.radius.abs());
+        let start = Aabb::new(self.center - radius, self.center + radius);
+        let end = Aabb::new(self.center_at(1.0) - radius, self.center_at(1.0) + radius);
+        return Some(start.surrounding(&end));
     }
 }
 
-const INVERSE_SQRT_THREE : f32 = 0.5773502691896257; // (1.0/3.0).sqrt()
-const TWICE_INVERSE_SQRT_THREE : f32 = 1.1547005383792515; // (1.0/3.0).sqrt() * 2
+const INVERSE_SQRT_THREE : f32 = 0.577_350_26; // (1.0/3.0).sqrt()
+const TWICE_INVERSE_SQRT_THREE : f32 = 1.154_700_5; // (1.0/3.0).sqrt() * 2
 
 fn random_in_unit_sphere_hack() -> Vector3f{
     // v is a vector in sphere of radius 
@@ -82,12 +112,12 @@ fn random_in_unit_sphere() -> Vector3f{
     }
 }
 
-fn random_in_unit_lambertian() -> Vector3f{
+pub fn random_in_unit_lambertian() -> Vector3f{
     return random_in_unit_sphere().unit_vector();
 }
 
 pub fn random_vec_in_unit_sphere() ->Vector3f{
-    if (crate::constants::QUICK_RENDER) {
+    if crate::constants::QUICK_RENDER {
         return random_in_unit_sphere_hack();
     }
     return random_in_unit_sphere();
@@ -101,4 +131,39 @@ pub fn random_in_hemisphere(normal : &Vector3f) -> Vector3f{
         return -in_unit_sphere;
     }
     return in_unit_sphere;
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_ray_time() {
+        let sphere = Sphere::new_moving(Vector3f::new(0.0, 0.0, -5.0), Vector3f::new(4.0, 0.0, -5.0), 1.0, Box::new(Lambertian::new(Vector3f::one())));
+        let towards = |x: f32, time: f32| Ray::new_at_time(Vector3f::new(x, 0.0, 0.0), Vector3f::new(0.0, 0.0, -1.0), time);
+        assert!((sphere.hit(&towards(0.0, 0.0), 0.001, f32::INFINITY).unwrap().t - 4.0).abs() < 1e-5);
+        assert!(sphere.hit(&towards(0.0, 1.0), 0.001, f32::INFINITY).is_none());
+        assert!(sphere.hit(&towards(2.0, 0.5), 0.001, f32::INFINITY).is_some());
+
+        // Outside [0,1] the sphere stays at the end of its path, inside its bounding box
+        let bounds = sphere.bounding_box().unwrap();
+        for time in [-3.0, 7.0] {
+            let center = sphere.center_at(time);
+            assert!(center.x - 1.0 >= bounds.min.x && center.x + 1.0 <= bounds.max.x);
+        }
+        assert!(sphere.hit(&towards(4.0, 7.0), 0.001, f32::INFINITY).is_some());
+    }
+
+    #[test]
+    fn uvs_and_tangents_follow_the_spherical_mapping() {
+        let sphere = Sphere::new(Vector3f::zero(), 2.0, Box::new(Lambertian::new(Vector3f::one())));
+        let hit = sphere.hit(&Ray::new(Vector3f::new(5.0, 0.0, 0.0), Vector3f::new(-1.0, 0.0, 0.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((hit.u - 0.5).abs() < 1e-5 && (hit.v - 0.5).abs() < 1e-5);
+        assert!((hit.dpdu - Vector3f::new(0.0, 0.0, -4.0 * PI)).magnitude() < 1e-4);
+        assert!((hit.dpdv - Vector3f::new(0.0, 2.0 * PI, 0.0)).magnitude() < 1e-4);
+
+        let top = sphere.hit(&Ray::new(Vector3f::new(0.0, 5.0, 0.0), Vector3f::new(0.0, -1.0, 0.0)), 0.001, f32::INFINITY).unwrap();
+        assert!((top.v - 1.0).abs() < 1e-5);
+        // The pole has no u direction, the tangents still lie in the surface
+        assert!(top.dpdu.dot(&top.normal).abs() < 1e-4 && top.dpdv.dot(&top.normal).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,114 @@
+use std::f32::consts::PI;
+
+use crate::utils::polynomial::solve_quartic;
+use crate::vectorlib::{aabb::Aabb, frame::Frame, hit::*, point3::*, ray::*, vector3::*};
+use crate::material::*;
+
+// Ring around center, axis is the normal of the plane the ring lies in.
+// major_radius is from the center to the middle of the tube and minor_radius is the radius of the tube
+pub struct Torus {
+    pub frame: Frame,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material : Box<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vector3f, major_radius: f32, minor_radius: f32, material : Box<dyn Material>) -> Torus {
+        return Torus { frame: Frame::from_z(center, axis), major_radius, minor_radius, material };
+    }
+}
+
+impl<'a> Hittable<'a> for Torus {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitData<'_>> {
+        let local_ray = self.frame.ray_to_local(ray);
+        let direction_length = local_ray.direction().magnitude();
+        let d = *local_ray.direction() / direction_length;
+
+        // The quartic is badly conditioned far from the torus, so start the ray where it enters the bounding sphere
+        let bounding_radius = self.major_radius + self.minor_radius;
+        let oc = *local_ray.origin();
+        let b_half = oc.dot(&d);
+        let discriminant = b_half * b_half - (oc.square_magnitude() - bounding_radius * bounding_radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let start = (-b_half - discriminant.sqrt()).max(0.0);
+        let o = oc + start * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with p = o + s d and |d| = 1
+        let (o, d) = (
+            [o.x as f64, o.y as f64, o.z as f64],
+            [d.x as f64, d.y as f64, d.z as f64],
+        );
+        let major_squared = (self.major_radius * self.major_radius) as f64;
+        let minor_squared = (self.minor_radius * self.minor_radius) as f64;
+        let f = o[0] * d[0] + o[1] * d[1] + o[2] * d[2];
+        let origin_squared = o[0] * o[0] + o[1] * o[1] + o[2] * o[2];
+        let g = origin_squared + major_squared - minor_squared;
+
+        let (roots, count) = solve_quartic(
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * g - 4.0 * major_squared * (1.0 - d[2] * d[2]),
+            4.0 * f * g - 8.0 * major_squared * (f - o[2] * d[2]),
+            g * g - 4.0 * major_squared * (origin_squared - o[2] * o[2]),
+        );
+
+        // Convert back to the parameter of the original ray
+        let t = roots
+            .iter()
+            .take(count)
+            .map(|s| (start + *s as f32) / direction_length)
+            .filter(|t| t_min <= *t && *t <= t_max)
+            .min_by(|a, b| a.total_cmp(b))?;
+
+        let local_hit = local_ray.at(t);
+        let ring_distance = (local_hit.x * local_hit.x + local_hit.y * local_hit.y).sqrt();
+        // Normal points away from the closest point on the ring running through the middle of the tube
+        let ring_point = Vector3f::new(local_hit.x, local_hit.y, 0.0) * (self.major_radius / ring_distance.max(f32::EPSILON));
+        let local_normal = (local_hit - ring_point).unit_vector();
+        let outward_normal = self.frame.vector_to_world(&local_normal);
+
+        // u goes around the ring, v goes around the tube
+        let u = (local_hit.y.atan2(local_hit.x) + PI) / (2.0 * PI);
+        let v = (local_hit.z.atan2(ring_distance - self.major_radius) + PI) / (2.0 * PI);
+        let local_dpdu = 2.0 * PI * Vector3f::new(-local_hit.y, local_hit.x, 0.0);
+        let outwards = Vector3f::new(local_hit.x, local_hit.y, 0.0) / ring_distance.max(f32::EPSILON);
+        let local_dpdv = 2.0 * PI * (-local_hit.z * outwards + Vector3f::new(0.0, 0.0, ring_distance - self.major_radius));
+
+        let hit_data = HitData::new(t, ray.at(t), outward_normal, ray.direction(), &outward_normal, self.material.as_ref())
+            .with_uv(u, v)
+            .with_tangents(self.frame.vector_to_world(&local_dpdu), self.frame.vector_to_world(&local_dpdv));
+        return Some(hit_data);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let local_min = Vector3f::new(-outer, -outer, -self.minor_radius);
+        let local_max = Vector3f::new(outer, outer, self.minor_radius);
+        return Some(self.frame.bounds_to_world(&local_min, &local_max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_the_tube_and_passes_through_the_hole() {
+        let torus = Torus::new(Vector3f::zero(), Vector3f::unit_y(), 2.0, 0.5, Box::new(Lambertian::new(Vector3f::one())));
+        let ray = Ray::new(Vector3f::new(-5.0, 0.0, 0.0), Vector3f::unit_x());
+        let outer = torus.hit(&ray, 0.001, f32::INFINITY).unwrap();
+        assert!((outer.t - 2.5).abs() < 1e-3 && outer.normal.x < -0.99);
+        // Past the first tube the ray comes out into the hole and enters the tube again
+        let exit = torus.hit(&ray, outer.t + 0.01, f32::INFINITY).unwrap();
+        assert!((exit.t - 3.5).abs() < 1e-3 && !exit.hit_front_face);
+        let inner = torus.hit(&ray, exit.t + 0.01, f32::INFINITY).unwrap();
+        assert!((inner.t - 6.5).abs() < 1e-3 && inner.hit_front_face);
+
+        // Straight down the axis through the hole, and just over the top of the tube
+        assert!(torus.hit(&Ray::new(Vector3f::new(0.0, 5.0, 0.0), -Vector3f::unit_y()), 0.001, f32::INFINITY).is_none());
+        assert!(torus.hit(&Ray::new(Vector3f::new(-5.0, 0.6, 0.0), Vector3f::unit_x()), 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::vectorlib::{aabb::Aabb, matrix4::Matrix4, point3::Point3, quaternion::Quaternion, ray::Ray, vector3::*};
+
+// Affine transform from object space to world space, the inverse is kept alongside
+// since every ray needs to be taken back into object space
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        return Transform { matrix: Matrix4::identity(), inverse: Matrix4::identity() };
+    }
+
+    // None for singular matrices, such as a scale of zero along some axis
+    pub fn from_matrix(matrix: Matrix4) -> Option<Transform> {
+        let inverse = matrix.inverse()?;
+        return Some(Transform { matrix, inverse });
+    }
+
+    pub fn translate(offset: Vector3f) -> Transform {
+        return Transform { matrix: Matrix4::translation(offset), inverse: Matrix4::translation(-offset) };
+    }
+
+    pub fn scale(factors: Vector3f) -> Transform {
+        let inverse_factors = Vector3f::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z);
+        return Transform { matrix: Matrix4::scale(factors), inverse: Matrix4::scale(inverse_factors) };
+    }
+
+    pub fn uniform_scale(factor: f32) -> Transform {
+        return Transform::scale(Vector3f::uniform(factor));
+    }
+
+    // angle is in radians, counter clockwise looking down the axis
+    pub fn rotate(axis: Vector3f, angle: f32) -> Transform {
+        let matrix = Matrix4::rotation(axis, angle);
+        // Rotations are orthogonal so the inverse is the transpose
+        return Transform { matrix, inverse: matrix.transpose() };
+    }
+
+    pub fn rotate_x(angle: f32) -> Transform {
+        return Transform::rotate(Vector3f::unit_x(), angle);
+    }
+
+    pub fn rotate_y(angle: f32) -> Transform {
+        return Transform::rotate(Vector3f::unit_y(), angle);
+    }
+
+    pub fn rotate_z(angle: f32) -> Transform {
+        return Transform::rotate(Vector3f::unit_z(), angle);
+    }
+
+    // Scale, then rotate, then translate, with the inverse built from the parts rather than by inverting
+    pub fn from_trs(translation: Vector3f, rotation: Quaternion, scale: Vector3f) -> Transform {
+        let rotation_matrix = rotation.rotation_matrix();
+        let inverse_scale = Vector3f::new(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z);
+        return Transform {
+            matrix: Matrix4::translation(translation) * rotation_matrix * Matrix4::scale(scale),
+            inverse: Matrix4::scale(inverse_scale) * rotation_matrix.transpose() * Matrix4::translation(-translation),
+        };
+    }
+
+    // Applies self first and then next, so scale.then(&rotate).then(&translate) reads in order
+    pub fn then(&self, next: &Transform) -> Transform {
+        return Transform { matrix: next.matrix * self.matrix, inverse: self.inverse * next.inverse };
+    }
+
+    pub fn inverted(&self) -> Transform {
+        return Transform { matrix: self.inverse, inverse: self.matrix };
+    }
+
+    pub fn point(&self, point: &Point3) -> Point3 {
+        return self.matrix.transform_point(point);
+    }
+
+    pub fn vector(&self, vector: &Vector3f) -> Vector3f {
+        return self.matrix.transform_vector(vector);
+    }
+
+    // Normals use the inverse transpose, the result is not normalised
+    pub fn normal(&self, normal: &Vector3f) -> Vector3f {
+        return self.inverse.transpose().transform_vector(normal);
+    }
+
+    // The direction is not normalised so t is the same in both spaces
+    pub fn ray_to_object(&self, ray: &Ray) -> Ray {
+        return Ray::new_at_time(self.inverse.transform_point(ray.origin()), self.inverse.transform_vector(ray.direction()), ray.time());
+    }
+
+    pub fn bounds(&self, object_bounds: &Aabb) -> Aabb {
+        let corners = object_bounds.corners().map(|corner| self.point(&corner));
+        return corners.iter().fold(Aabb::new(corners[0], corners[0]), |bounds, corner| bounds.grow(*corner));
+    }
+}
+
+// Pose of an object at a point in time, poses between keyframes are interpolated
+#[derive(Copy, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vector3f,
+    pub rotation: Quaternion,
+    pub scale: Vector3f,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, translation: Vector3f, rotation: Quaternion, scale: Vector3f) -> Keyframe {
+        return Keyframe { time, translation, rotation, scale };
+    }
+
+    pub fn translation_only(time: f32, translation: Vector3f) -> Keyframe {
+        return Keyframe::new(time, translation, Quaternion::identity(), Vector3f::one());
+    }
+
+    pub fn transform(&self) -> Transform {
+        return Transform::from_trs(self.translation, self.rotation, self.scale);
+    }
+
+    // Translation and scale are lerped and the rotation slerped, t in [0,1] from self to next
+    pub fn interpolate(&self, next: &Keyframe, t: f32) -> Keyframe {
+        return Keyframe {
+            time: self.time + (next.time - self.time) * t,
+            translation: Vector3f::lerp(&self.translation, &next.translation, t),
+            rotation: self.rotation.slerp(&next.rotation, t),
+            scale: Vector3f::lerp(&self.scale, &next.scale, t),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3f, b: Vector3f) -> bool {
+        return (a - b).magnitude() < 1e-4;
+    }
+
+    #[test]
+    fn from_matrix_needs_an_inverse() {
+        assert!(Transform::from_matrix(Matrix4::scale(Vector3f::new(1.0, 0.0, 1.0))).is_none());
+        let transform = Transform::from_matrix(Matrix4::translation(Vector3f::new(1.0, 2.0, 3.0)) * Matrix4::scale(Vector3f::uniform(2.0))).unwrap();
+        let point = Vector3f::new(0.5, -1.0, 4.0);
+        assert!(close(transform.inverted().point(&transform.point(&point)), point));
+        assert!(close(transform.point(&Vector3f::one()), Vector3f::new(3.0, 4.0, 5.0)));
+    }
+
+    #[test]
+    fn composes_in_order() {
+        // Scaling after translating moves the offset too
+        let scale_then_move = Transform::uniform_scale(2.0).then(&Transform::translate(Vector3f::unit_x()));
+        let move_then_scale = Transform::translate(Vector3f::unit_x()).then(&Transform::uniform_scale(2.0));
+        assert!(close(scale_then_move.point(&Vector3f::one()), Vector3f::new(3.0, 2.0, 2.0)));
+        assert!(close(move_then_scale.point(&Vector3f::one()), Vector3f::new(4.0, 2.0, 2.0)));
+
+        let rotation = Transform::rotate_z(std::f32::consts::FRAC_PI_2);
+        assert!(close(rotation.vector(&Vector3f::unit_x()), Vector3f::unit_y()));
+        assert!(close(rotation.inverted().vector(&Vector3f::unit_y()), Vector3f::unit_x()));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let transform = Transform::scale(Vector3f::new(4.0, 1.0, 1.0));
+        // Normal of the plane x + y = 1, which becomes x / 4 + y = 1
+        let normal = transform.normal(&Vector3f::new(1.0, 1.0, 0.0));
+        let along_plane = transform.vector(&Vector3f::new(1.0, -1.0, 0.0));
+        assert!(normal.dot(&along_plane).abs() < 1e-5);
+    }
+
+    #[test]
+    fn trs_matches_its_matrix_inverse() {
+        let rotation = Quaternion::from_axis_angle(Vector3f::new(1.0, 2.0, 3.0).unit_vector(), 0.7);
+        let transform = Transform::from_trs(Vector3f::new(1.0, -2.0, 0.5), rotation, Vector3f::new(2.0, 0.5, 3.0));
+        let inverse = transform.matrix.inverse().unwrap();
+        let point = Vector3f::new(0.3, 0.2, -0.9);
+        assert!(close(transform.inverse.transform_point(&point), inverse.transform_point(&point)));
+    }
+}
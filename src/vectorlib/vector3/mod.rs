@@ -1,7 +1,7 @@
 use rand;
 use crate::utils::inv_sqrt;
 
-#[derive(Copy)]
+#[derive(Copy, Clone)]
 pub struct Vector3f {
     pub x: f32,
     pub y: f32,
@@ -72,6 +72,19 @@ impl Vector3f {
         return self.clone() - ((2.0 * self.dot(normal)) * normal.clone());
     }
 
+    // Snell's law for a unit direction hitting a unit normal that faces against it, eta_ratio is
+    // eta_incident / eta_transmitted. None on total internal reflection
+    pub fn get_refracted(&self, normal : &Vector3f, eta_ratio : f32) -> Option<Vector3f>{
+        let cos_theta = (-self.dot(normal)).min(1.0);
+        let sin_squared_transmitted = eta_ratio * eta_ratio * (1.0 - cos_theta * cos_theta);
+        if sin_squared_transmitted > 1.0 {
+            return None;
+        }
+        let perpendicular = eta_ratio * (self.clone() + cos_theta * normal.clone());
+        let parallel = -(1.0 - sin_squared_transmitted).sqrt() * normal.clone();
+        return Some(perpendicular + parallel);
+    }
+
     pub fn random_range(min : f32, max : f32) -> Vector3f{
         return (Vector3f::random() * (max-min)) + Vector3f::uniform(min);
     }
@@ -90,6 +103,31 @@ impl Vector3f {
         return Vector3f::new(self.x * other.x, self.y * other.y, self.z * other.z);
     }
 
+    // Two unit vectors that together with self (assumed unit length) form an orthonormal basis
+    pub fn orthonormal_basis(&self) -> (Vector3f, Vector3f){
+        // Pick whichever axis is least aligned with self to cross against
+        let helper = if self.x.abs() > 0.9 { Vector3f::unit_y() } else { Vector3f::unit_x() };
+        let tangent = helper.cross(self).unit_vector();
+        let bitangent = self.cross(&tangent);
+        return (tangent, bitangent);
+    }
+
+    // Component by index, 0 = x, 1 = y, 2 = z
+    pub fn axis(&self, axis: usize) -> f32 {
+        return match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        };
+    }
+
+    pub fn axis_mut(&mut self, axis: usize) -> &mut f32 {
+        return match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => &mut self.z,
+        };
+    }
 }
 
 impl ops::Add<Vector3f> for Vector3f {
@@ -156,12 +194,6 @@ impl fmt::Display for Vector3f {
     }
 }
 
-impl Clone for Vector3f{
-    fn clone(&self) -> Vector3f{
-        return Vector3f::new(self.x, self.y, self.z);
-    }
-}
-
 impl Magnitude for Vector3f {
     type Output = f32;
 
@@ -175,7 +207,7 @@ impl Magnitude for Vector3f {
 }
 
 impl Normalize for Vector3f {
-    fn normalize(self : &mut Self) -> Self {
+    fn normalize(&mut self) -> Self {
         let magnitude = self.magnitude();
         *self = self.clone() / magnitude;
         return *self;
@@ -198,11 +230,12 @@ impl DotProduct for Vector3f {
 }
 
 impl CrossProduct for Vector3f {
-    fn cross<'a>(mut self, other: &'a Self) -> Self {
-        self.x = self.y * other.z - self.z * other.y;
-        self.y = self.z * other.x - self.x * other.z;
-        self.z = self.x * other.y - self.y * other.x;
-        return self;
+    fn cross<'a>(self, other: &'a Self) -> Self {
+        return Vector3f::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        );
     }
 
     fn cross_product<'a, 'b>(v : &'a Self, u: &'b Self) -> Self {
@@ -211,7 +244,7 @@ impl CrossProduct for Vector3f {
 }
 
 impl Lerp for Vector3f{
-    fn lerp_self<'a, 'b>(self : &'a mut Self, target: &'b Self, t : f32) {
+    fn lerp_self<'a, 'b>(&'a mut self, target: &'b Self, t : f32) {
         let diff = target.clone() - *self;
         *self = *self + (diff * t);
     }
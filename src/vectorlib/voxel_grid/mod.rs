@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::utils::noise::Perlin;
+use crate::vectorlib::{aabb::Aabb, point3::Point3, vector3::*};
+
+// Dense grid of density values filling bounds, sampled with trilinear interpolation between voxel centers.
+// Values are stored x fastest, then y, then z
+pub struct VoxelGrid {
+    pub resolution: [usize; 3],
+    pub bounds: Aabb,
+    densities: Vec<f32>,
+    max_density: f32,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3], bounds: Aabb, densities: Vec<f32>) -> VoxelGrid {
+        assert_eq!(densities.len(), resolution[0] * resolution[1] * resolution[2], "[ERR] Voxel grid needs one density per voxel");
+        assert!(resolution.iter().all(|n| *n > 0), "[ERR] Voxel grid needs at least one voxel on each axis");
+        let max_density = densities.iter().fold(0.0_f32, |max, density| max.max(*density));
+        return VoxelGrid { resolution, bounds, densities, max_density };
+    }
+
+    // Fills the grid by evaluating density at every voxel center
+    pub fn from_fn(resolution: [usize; 3], bounds: Aabb, density: impl Fn(Point3) -> f32) -> VoxelGrid {
+        let size = bounds.max - bounds.min;
+        let mut densities = Vec::with_capacity(resolution[0] * resolution[1] * resolution[2]);
+        for z in 0..resolution[2] {
+            for y in 0..resolution[1] {
+                for x in 0..resolution[0] {
+                    let center = bounds.min + Vector3f::new(
+                        size.x * (x as f32 + 0.5) / resolution[0] as f32,
+                        size.y * (y as f32 + 0.5) / resolution[1] as f32,
+                        size.z * (z as f32 + 0.5) / resolution[2] as f32,
+                    );
+                    densities.push(density(center).max(0.0));
+                }
+            }
+        }
+        return VoxelGrid::new(resolution, bounds, densities);
+    }
+
+    // Puffy cloud made from fbm noise, fading out towards the edges of the box so it has no hard sides
+    pub fn procedural_cloud(resolution: [usize; 3], bounds: Aabb, seed: u64) -> VoxelGrid {
+        let perlin = Perlin::new(seed);
+        let center = bounds.centroid();
+        let half_size = (bounds.max - bounds.min) / 2.0;
+        return VoxelGrid::from_fn(resolution, bounds, |point| {
+            let offset = point - center;
+            let normalised = Vector3f::new(offset.x / half_size.x, offset.y / half_size.y, offset.z / half_size.z);
+            let falloff = (1.0 - normalised.magnitude()).max(0.0);
+            let noise = perlin.fbm(&(normalised * 3.0), 5) * 0.5 + 0.5;
+            return (noise * 2.0 * falloff - 0.2).max(0.0);
+        });
+    }
+
+    // Raw grid file: three little endian u32 giving the resolution along x, y and z,
+    // followed by one little endian f32 density per voxel in x fastest order
+    pub fn load_raw(path: &Path, bounds: Aabb) -> Result<VoxelGrid> {
+        let bytes = fs::read(path)?;
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, format!("[ERR] Voxel grid: {}", message));
+
+        if bytes.len() < 12 {
+            return Err(invalid("file is too short for a header"));
+        }
+        let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        let resolution = [read_u32(0), read_u32(4), read_u32(8)];
+        let count = resolution[0]
+            .checked_mul(resolution[1])
+            .and_then(|count| count.checked_mul(resolution[2]))
+            .ok_or_else(|| invalid("resolution is too large"))?;
+        if count == 0 {
+            return Err(invalid("resolution has a zero axis"));
+        }
+        let expected_length = count.checked_mul(4).and_then(|length| length.checked_add(12));
+        if expected_length != Some(bytes.len()) {
+            return Err(invalid(&format!("expected {} densities but the file holds {} bytes of data", count, bytes.len() - 12)));
+        }
+
+        let densities = bytes[12..]
+            .chunks_exact(4)
+            .map(|raw| f32::from_le_bytes(raw.try_into().unwrap()).max(0.0))
+            .collect();
+        return Ok(VoxelGrid::new(resolution, bounds, densities));
+    }
+
+    // Largest value in the grid, trilinear interpolation can never exceed it so it works as a majorant
+    pub fn max_density(&self) -> f32 {
+        return self.max_density;
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        return self.densities[(z * self.resolution[1] + y) * self.resolution[0] + x];
+    }
+
+    // Trilinear interpolation of the eight voxel centers around point, zero outside the bounds
+    pub fn density_at(&self, point: &Point3) -> f32 {
+        let size = self.bounds.max - self.bounds.min;
+        let mut base = [0usize; 3];
+        let mut weight = [0.0f32; 3];
+        for axis in 0..3 {
+            let relative = (point.axis(axis) - self.bounds.min.axis(axis)) / size.axis(axis);
+            if !(0.0..=1.0).contains(&relative) {
+                return 0.0;
+            }
+
+            // Continuous voxel coordinate with voxel centers on the integers
+            let n = self.resolution[axis];
+            let coordinate = (relative * n as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+            base[axis] = (coordinate.floor() as usize).min(n.saturating_sub(2));
+            weight[axis] = coordinate - base[axis] as f32;
+        }
+
+        let next = |axis: usize| (base[axis] + 1).min(self.resolution[axis] - 1);
+        let (x0, y0, z0) = (base[0], base[1], base[2]);
+        let (x1, y1, z1) = (next(0), next(1), next(2));
+        let (wx, wy, wz) = (weight[0], weight[1], weight[2]);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let front = lerp(
+            lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), wx),
+            lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), wx),
+            wy,
+        );
+        let back = lerp(
+            lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), wx),
+            lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), wx),
+            wy,
+        );
+        return lerp(front, back, wz);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<VoxelGrid> {
+        let path = std::env::temp_dir().join(format!("voxel_grid_{}_{}.raw", name, std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        let grid = VoxelGrid::load_raw(&path, Aabb::new(Vector3f::zero(), Vector3f::one()));
+        fs::remove_file(&path).unwrap();
+        return grid;
+    }
+
+    fn header(resolution: [u32; 3]) -> Vec<u8> {
+        return resolution.iter().flat_map(|axis| axis.to_le_bytes()).collect();
+    }
+
+    #[test]
+    fn load_raw_reads_densities() {
+        let mut bytes = header([2, 1, 1]);
+        bytes.extend(0.25f32.to_le_bytes());
+        bytes.extend(0.75f32.to_le_bytes());
+        let grid = load_bytes("valid", &bytes).unwrap();
+        assert_eq!(grid.resolution, [2, 1, 1]);
+        assert_eq!(grid.max_density(), 0.75);
+    }
+
+    #[test]
+    fn load_raw_rejects_malformed_headers() {
+        for resolution in [[u32::MAX, u32::MAX, u32::MAX], [0x4000_0000, 0x4000_0000, 4], [0, 4, 4], [2, 2, 2]] {
+            let error = load_bytes("malformed", &header(resolution)).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}